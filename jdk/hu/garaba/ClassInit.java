@@ -0,0 +1,95 @@
+package hu.garaba;
+
+public class ClassInit {
+	static class Failing {
+		static int value = fail();
+
+		static int fail() {
+			throw new IllegalArgumentException("bad initializer");
+		}
+	}
+
+	static class FailingWithError {
+		static int value = fail();
+
+		static int fail() {
+			throw new LinkageError();
+		}
+	}
+
+	static class Sub extends Failing {
+		static int other = 1;
+	}
+
+	static class Recursive {
+		static int first = 5;
+		static int second = Recursive.first + 1;
+	}
+
+	static class Slow {
+		static int value;
+
+		static {
+			try {
+				Thread.sleep(100);
+			} catch (InterruptedException e) {
+			}
+			value = 42;
+		}
+	}
+
+	interface WithDefault {
+		int FIELD = log("WithDefault initialized");
+
+		default void method() {
+		}
+	}
+
+	interface WithoutDefault {
+		int FIELD = log("WithoutDefault initialized");
+	}
+
+	static class Implementor implements WithDefault, WithoutDefault {
+		static int value = log("Implementor initialized");
+	}
+
+	static int log(String message) {
+		System.out.println(message);
+		return 0;
+	}
+
+	public static void main(String[] args) throws InterruptedException {
+		try {
+			System.out.println(Failing.value);
+		} catch (ExceptionInInitializerError e) {
+			System.out.println("ExceptionInInitializerError caused by " + e.getCause().getMessage());
+		}
+
+		try {
+			System.out.println(Failing.value);
+		} catch (NoClassDefFoundError e) {
+			System.out.println("NoClassDefFoundError on second use");
+		}
+
+		try {
+			System.out.println(FailingWithError.value);
+		} catch (LinkageError e) {
+			System.out.println("Errors are not wrapped");
+		}
+
+		try {
+			System.out.println(Sub.other);
+		} catch (NoClassDefFoundError e) {
+			System.out.println("Subclass of failed class can't be initialized");
+		}
+
+		System.out.println(Recursive.second);
+
+		Thread thread = new Thread(() -> System.out.println(Slow.value));
+		thread.start();
+		System.out.println(Slow.value);
+		thread.join();
+
+		System.out.println(Implementor.value);
+	}
+}
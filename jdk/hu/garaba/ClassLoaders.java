@@ -0,0 +1,55 @@
+package hu.garaba;
+
+public class ClassLoaders {
+	// Counts the classes it is asked for, and leaves the loading to its parent
+	static class CountingLoader extends ClassLoader {
+		int requests;
+
+		CountingLoader() {
+			super();
+		}
+
+		CountingLoader(ClassLoader parent) {
+			super(parent);
+		}
+
+		protected Class<?> loadClass(String name, boolean resolve) throws ClassNotFoundException {
+			requests++;
+			return super.loadClass(name, resolve);
+		}
+	}
+
+	// Finds classes itself if its parent can not
+	static class FallbackLoader extends ClassLoader {
+		FallbackLoader(ClassLoader parent) {
+			super(parent);
+		}
+
+		protected Class<?> findClass(String name) throws ClassNotFoundException {
+			System.out.println("findClass " + name);
+			throw new ClassNotFoundException(name);
+		}
+	}
+
+	public static void main(String[] args) throws ClassNotFoundException {
+		CountingLoader parent = new CountingLoader();
+		CountingLoader child = new CountingLoader(parent);
+		System.out.println(child.getParent() == parent ? 1 : 0);
+
+		Class<?> runnable = child.loadClass("java.lang.Runnable");
+		System.out.println(parent.requests);
+		System.out.println(child.requests);
+		System.out.println(runnable == parent.loadClass("java.lang.Runnable") ? 1 : 0);
+		System.out.println(runnable == child.loadClass("java.lang.Thread") ? 1 : 0);
+		System.out.println(runnable == Runnable.class ? 1 : 0);
+		System.out.println(parent.requests);
+
+		FallbackLoader fallback = new FallbackLoader(child);
+		try {
+			fallback.loadClass("hu.garaba.Missing");
+		} catch (ClassNotFoundException e) {
+			System.out.println("not found: " + e.getMessage());
+		}
+		System.out.println(child.requests);
+	}
+}
@@ -0,0 +1,63 @@
+package hu.garaba;
+
+public class Floats {
+	public static void main(String[] args) {
+		float[] values = { 0.0f, 1.0f, 2.0f, 2.5f, -0.75f, 1024.125f };
+		float sum = 0;
+		for (int i = 0; i < values.length; i++) {
+			sum += values[i];
+		}
+		System.out.println((double) sum);
+
+		float a = 7.5f;
+		float b = 2.0f;
+		System.out.println((double) (a - b));
+		System.out.println((double) (a * b));
+		System.out.println((double) (a / b));
+		System.out.println((double) (a % b));
+		System.out.println((double) (-a % b));
+		System.out.println((double) -a);
+
+		float nan = 0.0f / 0.0f;
+		float inf = 1.0f / 0.0f;
+		System.out.println(compare(a, b));
+		System.out.println(compare(b, a));
+		System.out.println(compare(a, a));
+		System.out.println(compare(nan, a));
+		System.out.println(nan == nan ? 1 : 0);
+		System.out.println(nan != nan ? 1 : 0);
+		System.out.println(inf > a ? 1 : 0);
+
+		System.out.println((int) nan);
+		System.out.println((int) inf);
+		System.out.println((int) -inf);
+		System.out.println((long) inf);
+		System.out.println((int) -2.75f);
+		System.out.println((long) 1234.5f);
+
+		int i = 16777217;
+		long l = 1L << 40;
+		double d = 0.1;
+		System.out.println((int) (float) i);
+		System.out.println((long) (float) (l + 1));
+		System.out.println((float) d == 0.1f ? 1 : 0);
+		System.out.println(half(3));
+	}
+
+	private static int compare(float x, float y) {
+		if (x < y) {
+			return -1;
+		} else if (x > y) {
+			return 1;
+		} else if (x == y) {
+			return 0;
+		}
+		return 2;
+	}
+
+	private static double half(int n) {
+		float f = n;
+		f /= 2;
+		return f;
+	}
+}
@@ -0,0 +1,68 @@
+package hu.garaba;
+
+public class Formatting {
+	public static void main(String[] args) {
+		double[] doubles = { 0.0, -0.0, 1.0, -12.5, 0.1, 0.1 + 0.2, 100.0, 0.001, 1.0E-4, 9999999.0,
+				1.0E7, 1.0 / 3.0, 2.0 / 3.0, 6.02214076E23, -1.25E-5, 1.7976931348623157E308,
+				4.9E-324, 1152921504606846976.0, 123456789012345.0, 1.0 / 0.0, -1.0 / 0.0, 0.0 / 0.0,
+				1.0E23, 1.0E-322, 1.0E-323, 1.2621774483536189E-29, -2.1235808073258287E25 };
+		for (int i = 0; i < doubles.length; i++) {
+			System.out.println(Double.toString(doubles[i]));
+			System.out.println(doubles[i]);
+		}
+
+		float[] floats = { 0.0f, 1.0f, 0.1f, 1.0f / 3.0f, 1.0E10f, 3.4028235E38f, 1.4E-45f,
+				4.7939306E8f, 16777216.0f, 2085304.25f, 1390958.75f, -0.5f, 1.0f / 0.0f, 0.055862505f };
+		for (int i = 0; i < floats.length; i++) {
+			System.out.println(Float.toString(floats[i]));
+			System.out.println(floats[i]);
+		}
+
+		System.out.println(Long.toString(-9223372036854775808L));
+		System.out.println(Long.toString(9223372036854775807L));
+		System.out.println(Long.toString(0L));
+		System.out.println(Character.toString('x'));
+		System.out.println('y');
+		System.out.println(true);
+
+		StringBuilder sb = new StringBuilder();
+		sb.append("int ").append(-42).append(", long ").append(1234567890123L)
+				.append(", float ").append(1.1f).append(", double ").append(2.5E-10)
+				.append(", char ").append('c').append(", boolean ").append(false)
+				.append(", null ").append((String) null).append(", object ").append((Object) "obj");
+		System.out.println(sb.toString());
+		System.out.println(sb.length());
+		System.out.println(sb.charAt(4));
+
+		StringBuilder grown = new StringBuilder(2);
+		for (int i = 0; i < 100; i++) {
+			grown.append(i);
+		}
+		System.out.println(grown.toString());
+		System.out.println(grown.length());
+
+		StringBuilder reversed = new StringBuilder("a\uD83D\uDE00bc").reverse();
+		System.out.println(reversed.length());
+		for (int i = 0; i < reversed.length(); i++) {
+			System.out.println((int) reversed.charAt(i));
+		}
+
+		StringBuilder truncated = new StringBuilder("Hello, world");
+		truncated.setLength(5);
+		System.out.println(truncated.toString());
+		truncated.setLength(7);
+		System.out.println((int) truncated.charAt(6));
+		System.out.println(truncated.append('!').length());
+
+		try {
+			sb.charAt(-1);
+		} catch (StringIndexOutOfBoundsException e) {
+			System.out.println(e.getMessage());
+		}
+		try {
+			truncated.setLength(-3);
+		} catch (StringIndexOutOfBoundsException e) {
+			System.out.println(e.getMessage());
+		}
+	}
+}
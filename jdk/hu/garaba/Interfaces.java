@@ -0,0 +1,119 @@
+package hu.garaba;
+
+public class Interfaces {
+	interface Shape {
+		int area();
+
+		default int doubleArea() {
+			return 2 * area();
+		}
+
+		default String name() {
+			return "shape";
+		}
+	}
+
+	interface Named {
+		default String name() {
+			return "named";
+		}
+	}
+
+	interface Polygon extends Shape {
+		int sides();
+
+		default String name() {
+			return "polygon";
+		}
+	}
+
+	static class Square implements Polygon {
+		private final int side;
+
+		Square(int side) {
+			this.side = side;
+		}
+
+		public int area() {
+			return side * side;
+		}
+
+		public int sides() {
+			return 4;
+		}
+	}
+
+	// Inherits the default methods through its superclass
+	static class BigSquare extends Square {
+		BigSquare() {
+			super(10);
+		}
+	}
+
+	// Both Polygon.name and Named.name are maximally specific, so it has to choose
+	static class Tile implements Polygon, Named {
+		public int area() {
+			return 1;
+		}
+
+		public int sides() {
+			return 4;
+		}
+
+		public String name() {
+			return Named.super.name();
+		}
+
+		String polygonName() {
+			return Polygon.super.name();
+		}
+	}
+
+	static class Circle implements Shape {
+		public int area() {
+			return 3;
+		}
+	}
+
+	public static void main(String[] args) {
+		Shape square = new Square(3);
+		System.out.println(square.area());
+		System.out.println(square.doubleArea());
+		System.out.println(square.name());
+
+		BigSquare big = new BigSquare();
+		System.out.println(big.doubleArea());
+		System.out.println(big.sides());
+		System.out.println(big.name());
+
+		Tile tile = new Tile();
+		System.out.println(tile.name());
+		System.out.println(tile.polygonName());
+
+		Shape circle = new Circle();
+		System.out.println(circle.doubleArea());
+		System.out.println(circle.name());
+
+		Object o = big;
+		System.out.println(o instanceof Shape ? 1 : 0);
+		System.out.println(o instanceof Polygon ? 1 : 0);
+		System.out.println(o instanceof Named ? 1 : 0);
+		System.out.println(tile instanceof Named ? 1 : 0);
+		System.out.println(((Polygon) o).sides());
+
+		Object c = circle;
+		try {
+			Polygon p = (Polygon) c;
+			System.out.println(p.sides());
+		} catch (ClassCastException e) {
+			System.out.println("not a polygon");
+		}
+
+		Object squares = new Square[] { new Square(1), new BigSquare() };
+		System.out.println(squares instanceof Shape[] ? 1 : 0);
+		System.out.println(squares instanceof Object[] ? 1 : 0);
+		System.out.println(squares instanceof BigSquare[] ? 1 : 0);
+		Shape[] shapes = (Shape[]) squares;
+		System.out.println(shapes[1].area());
+	}
+}
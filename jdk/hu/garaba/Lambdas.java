@@ -0,0 +1,98 @@
+package hu.garaba;
+
+public class Lambdas {
+	interface IntOperation {
+		int apply(int a);
+
+		default IntOperation andThen(IntOperation next) {
+			return a -> next.apply(apply(a));
+		}
+	}
+
+	interface Combiner {
+		String combine(String a, long b, double c);
+	}
+
+	interface Factory {
+		Point create(int x, int y);
+	}
+
+	interface Describer {
+		String describe(Point p);
+	}
+
+	static class Point {
+		private final int x;
+		private final int y;
+
+		Point(int x, int y) {
+			this.x = x;
+			this.y = y;
+		}
+
+		String describe() {
+			return "Point(" + x + ", " + y + ")";
+		}
+
+		public String toString() {
+			return "(" + x + "; " + y + ")";
+		}
+	}
+
+	private final int offset;
+
+	Lambdas(int offset) {
+		this.offset = offset;
+	}
+
+	static int twice(int a) {
+		return 2 * a;
+	}
+
+	IntOperation adder() {
+		return a -> a + offset;
+	}
+
+	public static void main(String[] args) {
+		int base = 7;
+		long big = 10000000000L;
+		char c = 'x';
+		boolean flag = true;
+		double d = 2.5;
+		String nothing = null;
+
+		System.out.println("base=" + base + ", big=" + big + ", c=" + c + ", flag=" + flag);
+		System.out.println("d=" + d + " null=" + nothing + " point=" + new Point(1, 2));
+		System.out.println(base + big + "" + c);
+
+		char high = '\uD83D';
+		char low = '\uDE00';
+		String pair = "a" + high + low;
+		System.out.println(pair.length() + " " + pair.indexOf(0x1F600) + " " + pair.hashCode());
+		String lone = high + "y" + low + base;
+		System.out.println(lone.length() + " " + (int) lone.charAt(0) + " " + (int) lone.charAt(2));
+
+		Runnable hello = () -> System.out.println("hello from a lambda");
+		hello.run();
+
+		Runnable capturing = () -> System.out.println("captured " + base + " and " + big);
+		capturing.run();
+
+		IntOperation twice = Lambdas::twice;
+		IntOperation plusBase = a -> a + base;
+		System.out.println(twice.apply(21));
+		System.out.println(twice.andThen(plusBase).apply(5));
+		System.out.println(new Lambdas(100).adder().andThen(twice).apply(1));
+
+		Combiner combiner = (s, l, x) -> s + l + x;
+		System.out.println(combiner.combine("combined ", 3L, 0.5));
+
+		Factory factory = Point::new;
+		Describer describer = Point::describe;
+		System.out.println(describer.describe(factory.create(3, 4)));
+
+		Object lambda = plusBase;
+		System.out.println(lambda instanceof IntOperation ? 1 : 0);
+		System.out.println(lambda instanceof Runnable ? 1 : 0);
+	}
+}
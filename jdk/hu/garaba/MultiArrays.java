@@ -0,0 +1,74 @@
+package hu.garaba;
+
+public class MultiArrays {
+	static int[][] multiply(int[][] a, int[][] b) {
+		int n = a.length;
+		int m = b[0].length;
+		int[][] result = new int[n][m];
+		for (int i = 0; i < n; i++) {
+			for (int j = 0; j < m; j++) {
+				int sum = 0;
+				for (int k = 0; k < b.length; k++) {
+					sum += a[i][k] * b[k][j];
+				}
+				result[i][j] = sum;
+			}
+		}
+
+		return result;
+	}
+
+	public static void main(String[] args) {
+		int[][] a = { { 1, 2, 3 }, { 4, 5, 6 } };
+		int[][] b = { { 7, 8 }, { 9, 10 }, { 11, 12 } };
+		int[][] c = multiply(a, b);
+		for (int[] row : c) {
+			System.out.println(row[0] + " " + row[1]);
+		}
+
+		long[][][] cube = new long[3][4][5];
+		cube[2][3][4] = 1L << 40;
+		System.out.println(cube.length + " " + cube[0].length + " " + cube[1][2].length);
+		System.out.println(cube[2][3][4] + cube[0][0][0]);
+
+		double[][] partial = new double[2][];
+		System.out.println(partial[1] == null ? "null component" : "allocated component");
+		partial[0] = new double[] { 0.5, 1.5 };
+		System.out.println(partial[0][0] + partial[0][1]);
+
+		String[][] names = new String[2][2];
+		names[1][0] = "matrix";
+		System.out.println(names[1][0] + " " + names[0][1]);
+
+		byte[] bytes = new byte[2];
+		bytes[0] = (byte) 200;
+		bytes[1] = 127;
+		bytes[1] = (byte) (bytes[1] + 1);
+		System.out.println(bytes[0] + " " + bytes[1]);
+
+		short[] shorts = { (short) 40000, -1 };
+		System.out.println(shorts[0] + " " + shorts[1]);
+
+		char[] chars = { 'a', (char) 65535 };
+		chars[0] = (char) (chars[0] + 2);
+		System.out.print(chars[0]);
+		System.out.println(" " + (int) chars[1]);
+
+		boolean[][] flags = new boolean[2][3];
+		flags[1][2] = true;
+		System.out.println((flags[1][2] ? 1 : 0) + " " + (flags[0][0] ? 1 : 0));
+
+		float[] floats = { 1.25f, 2.5f };
+		System.out.println(floats[0] * floats[1]);
+
+		long[] longs = { 9223372036854775807L, -9223372036854775808L };
+		System.out.println(longs[0] + " " + longs[1]);
+
+		try {
+			int[][] negative = new int[2][-3];
+			System.out.println(negative.length);
+		} catch (NegativeArraySizeException e) {
+			System.out.println("NegativeArraySizeException");
+		}
+	}
+}
@@ -0,0 +1,46 @@
+package hu.garaba;
+
+public class StackTraces {
+	static class Failure extends RuntimeException {
+		Failure(String message) {
+			super(message);
+		}
+	}
+
+	static void fail(int depth) {
+		if (depth == 0) {
+			throw new Failure("deep");
+		}
+		fail(depth - 1);
+	}
+
+	static int get(int[] array, int i) {
+		return array[i];
+	}
+
+	static void print(Throwable t) {
+		StackTraceElement[] trace = t.getStackTrace();
+		for (int i = 0; i < trace.length; i++) {
+			StackTraceElement e = trace[i];
+			System.out.println(e.getClassName() + "." + e.getMethodName()
+					+ "(" + e.getFileName() + ":" + e.getLineNumber() + ")");
+		}
+	}
+
+	public static void main(String[] args) {
+		try {
+			fail(2);
+		} catch (Failure e) {
+			System.out.println(e.getMessage());
+			print(e);
+		}
+
+		try {
+			get(new int[1], 3);
+		} catch (ArrayIndexOutOfBoundsException e) {
+			print(e);
+		}
+
+		print(new Throwable());
+	}
+}
@@ -0,0 +1,88 @@
+package hu.garaba;
+
+public class StringEquality {
+	// Open addressing table keyed by strings, relying only on hashCode and equals
+	static String[] keys = new String[16];
+	static int[] values = new int[16];
+
+	static int slot(String key) {
+		int h = key.hashCode();
+		int i = h - h / 16 * 16;
+		if (i < 0) {
+			i = i + 16;
+		}
+		while (keys[i] != null && !keys[i].equals(key)) {
+			i = i + 1;
+			if (i - 16 == 0) {
+				i = 0;
+			}
+		}
+		return i;
+	}
+
+	static void put(String key, int value) {
+		int i = slot(key);
+		keys[i] = key;
+		values[i] = value;
+	}
+
+	static int get(String key) {
+		int i = slot(key);
+		return keys[i] == null ? -1 : values[i];
+	}
+
+	static int bit(boolean b) {
+		return b ? 1 : 0;
+	}
+
+	public static void main(String[] args) {
+		String hello = "hello";
+		String built = "hel".concat("lo");
+		System.out.println(bit(hello == built));
+		System.out.println(bit(hello.equals(built)));
+		System.out.println(bit(hello.equals(null)));
+		System.out.println(bit(hello.equals(new Object())));
+		System.out.println(bit(hello == built.intern()));
+		System.out.println(bit("a".concat("bc").intern() == "abc"));
+
+		String fresh = "un".concat("interned");
+		System.out.println(bit(fresh.intern() == fresh));
+		System.out.println(bit("unin".concat("terned").intern() == fresh));
+
+		System.out.println(hello.hashCode());
+		System.out.println(built.hashCode());
+		System.out.println("polygenelubricants".hashCode());
+		System.out.println("\u00e9t\u00e9".hashCode());
+
+		System.out.println("apple".compareTo("banana"));
+		System.out.println("banana".compareTo("apple"));
+		System.out.println("app".compareTo("apple"));
+		System.out.println("apple".compareTo("apple"));
+		System.out.println("\u00e9".compareTo("e"));
+
+		System.out.println(bit("Hello".equalsIgnoreCase("hELLO")));
+		System.out.println(bit("Hello".equalsIgnoreCase("Help!")));
+		System.out.println(bit("Hello".equalsIgnoreCase(null)));
+		System.out.println(bit("\u00c1RV\u00cdZ".equalsIgnoreCase("\u00e1rv\u00edz")));
+		System.out.println(bit("stra\u00dfe".equalsIgnoreCase("STRASSE")));
+
+		put("one", 1);
+		put("two", 2);
+		put("three", 3);
+		put("Aa", 4);
+		put("BB", 5);
+		put("o".concat("ne"), 11);
+		System.out.println(get("one"));
+		System.out.println(get("tw".concat("o")));
+		System.out.println(get("three"));
+		System.out.println(get("Aa"));
+		System.out.println(get("BB"));
+		System.out.println(get("four"));
+
+		try {
+			hello.compareTo(null);
+		} catch (NullPointerException e) {
+			System.out.println("NPE");
+		}
+	}
+}
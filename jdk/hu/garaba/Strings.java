@@ -0,0 +1,56 @@
+package hu.garaba;
+
+public class Strings {
+	public static void main(String[] args) {
+		String s = "Hello, world";
+		System.out.println(s.length());
+		System.out.print(s.charAt(7));
+		System.out.println("");
+		System.out.println(s.substring(7));
+		System.out.println(s.substring(0, 5));
+		System.out.println(s.indexOf('o'));
+		System.out.println(s.indexOf('o', 5));
+		System.out.println(s.indexOf('z'));
+		System.out.println(s.indexOf("world"));
+		System.out.println(s.indexOf("worlds"));
+		System.out.println(s.hashCode());
+		System.out.println("".hashCode());
+
+		String accented = "\u00e1rv\u00edzt\u0171r\u0151";
+		System.out.println(accented.length());
+		System.out.println((int) accented.charAt(6));
+		System.out.println((int) accented.substring(2, 5).charAt(1));
+
+		String emoji = "a\uD83D\uDE00b";
+		System.out.println(emoji.length());
+		System.out.println(emoji.indexOf(0x1F600));
+		System.out.println(emoji.indexOf('b'));
+		System.out.println(emoji.hashCode());
+
+		String surrogate = "\uD800x";
+		System.out.println((int) surrogate.charAt(0));
+		System.out.println((int) ("\uDC00" + emoji.length()).charAt(0));
+
+		String concatenated = s.concat("!").concat("");
+		System.out.println(concatenated);
+
+		String repeated = "0123456789";
+		for (int i = 0; i < 8; i++) {
+			repeated = repeated.concat(repeated);
+		}
+		System.out.println(repeated.length());
+		System.out.println(repeated.substring(2555));
+		System.out.println(repeated.hashCode());
+
+		try {
+			s.charAt(12);
+		} catch (StringIndexOutOfBoundsException e) {
+			System.out.println(e.getMessage());
+		}
+		try {
+			s.substring(5, 2);
+		} catch (IndexOutOfBoundsException e) {
+			System.out.println(e.getMessage());
+		}
+	}
+}
@@ -0,0 +1,53 @@
+package hu.garaba;
+
+public class Switch {
+	public static void main(String[] args) {
+		for (int i = -2; i < 8; i++) {
+			System.out.println(dense(i));
+		}
+
+		int[] sparse = { -1000, -1, 0, 7, 100, 4096, 1 << 20, 65535 };
+		for (int i = 0; i < sparse.length; i++) {
+			System.out.println(sparse(sparse[i]));
+		}
+
+		String word = "switch";
+		for (int i = 0; i < word.length(); i++) {
+			System.out.print(vowel(word.charAt(i)));
+		}
+		System.out.print('\n');
+	}
+
+	private static int dense(int n) {
+		switch (n) {
+			case 0: return 10;
+			case 1: return 11;
+			case 2:
+			case 3: return 23;
+			case 5: return 15;
+			default: return -1;
+		}
+	}
+
+	private static int sparse(int n) {
+		int result = 0;
+		switch (n) {
+			case -1000: result = 1; break;
+			case 7: result = 2; break;
+			case 4096: result = 3; break;
+			case 1 << 20: result = 4;
+			case 65535: result += 5; break;
+			default: result = -7;
+		}
+		return result;
+	}
+
+	private static char vowel(char c) {
+		switch (c) {
+			case 'a': case 'e': case 'i': case 'o': case 'u':
+				return 'V';
+			default:
+				return 'c';
+		}
+	}
+}
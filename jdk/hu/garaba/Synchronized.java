@@ -0,0 +1,65 @@
+package hu.garaba;
+
+public class Synchronized {
+	private static int staticCounter = 0;
+	private int counter = 0;
+
+	public static void main(String[] args) {
+		Synchronized s = new Synchronized();
+		for (int i = 0; i < 10; i++) {
+			s.increment();
+			incrementStatic();
+		}
+		System.out.println(s.counter);
+		System.out.println(staticCounter);
+
+		synchronized (s) {
+			synchronized (s) {
+				s.increment();
+			}
+		}
+		System.out.println(s.counter);
+
+		try {
+			s.fail();
+		} catch (RuntimeException e) {
+			System.out.println("caught");
+		}
+		// The monitor must have been released by the exceptional exit
+		System.out.println(s.blockThrow());
+		System.out.println(s.counter);
+
+		Object nothing = null;
+		try {
+			synchronized (nothing) {
+				System.out.println("unreachable");
+			}
+		} catch (NullPointerException e) {
+			System.out.println("npe");
+		}
+	}
+
+	private synchronized void increment() {
+		counter++;
+	}
+
+	private static synchronized void incrementStatic() {
+		staticCounter++;
+	}
+
+	private synchronized void fail() {
+		counter++;
+		throw new RuntimeException();
+	}
+
+	private int blockThrow() {
+		try {
+			synchronized (this) {
+				counter++;
+				throw new RuntimeException();
+			}
+		} catch (RuntimeException e) {
+			return 1;
+		}
+	}
+}
@@ -0,0 +1,74 @@
+package hu.garaba;
+
+public class Threads {
+	private int counter = 0;
+
+	public static void main(String[] args) throws InterruptedException {
+		System.out.println(Thread.currentThread().getName());
+
+		Threads shared = new Threads();
+		Thread[] workers = new Thread[4];
+		for (int i = 0; i < workers.length; i++) {
+			workers[i] = new Thread(new Worker(shared));
+		}
+		for (int i = 0; i < workers.length; i++) {
+			workers[i].start();
+		}
+		for (int i = 0; i < workers.length; i++) {
+			workers[i].join();
+			System.out.println(workers[i].isAlive() ? 1 : 0);
+		}
+		System.out.println(shared.counter);
+		System.out.println(workers[3].getName());
+
+		Sleeper sleeper = new Sleeper();
+		System.out.println(sleeper.isAlive() ? 1 : 0);
+		sleeper.start();
+		System.out.println(sleeper.isAlive() ? 1 : 0);
+		sleeper.join();
+		System.out.println(sleeper.done);
+
+		try {
+			sleeper.start();
+		} catch (IllegalThreadStateException e) {
+			System.out.println("already started");
+		}
+
+		// The VM must not exit until this thread finishes
+		Thread last = new Sleeper();
+		last.start();
+		System.out.println("main done");
+	}
+
+	private synchronized void increment() {
+		counter++;
+	}
+
+	static class Worker implements Runnable {
+		private final Threads shared;
+
+		Worker(Threads shared) {
+			this.shared = shared;
+		}
+
+		public void run() {
+			for (int i = 0; i < 10000; i++) {
+				shared.increment();
+			}
+		}
+	}
+
+	static class Sleeper extends Thread {
+		int done = 0;
+
+		public void run() {
+			try {
+				Thread.sleep(20);
+			} catch (InterruptedException e) {
+				return;
+			}
+			done = 1;
+			System.out.println(Thread.currentThread().getName());
+		}
+	}
+}
@@ -0,0 +1,158 @@
+package hu.garaba;
+
+public class WaitNotify {
+	public static void main(String[] args) throws InterruptedException {
+		Buffer buffer = new Buffer();
+		Producer producer = new Producer(buffer);
+		Consumer consumer = new Consumer(buffer);
+		producer.start();
+		consumer.start();
+		producer.join();
+		consumer.join();
+		System.out.println(consumer.sum);
+
+		Object lock = new Object();
+		try {
+			lock.notify();
+		} catch (IllegalMonitorStateException e) {
+			System.out.println("not owner");
+		}
+		try {
+			lock.wait();
+		} catch (IllegalMonitorStateException e) {
+			System.out.println("not owner");
+		}
+
+		synchronized (lock) {
+			lock.wait(10);
+			lock.notifyAll();
+		}
+		System.out.println("timed out");
+
+		Thread sleeper = new Thread(new Sleeper());
+		sleeper.start();
+		sleeper.interrupt();
+		sleeper.join();
+
+		Waiter waiter = new Waiter(lock);
+		waiter.start();
+		synchronized (lock) {
+			while (!waiter.waiting) {
+				lock.wait();
+			}
+		}
+		waiter.interrupt();
+		waiter.join();
+
+		Thread.currentThread().interrupt();
+		System.out.println(Thread.currentThread().isInterrupted() ? 1 : 0);
+		System.out.println(Thread.interrupted() ? 1 : 0);
+		System.out.println(Thread.interrupted() ? 1 : 0);
+	}
+
+	static class Buffer {
+		private final int[] items = new int[4];
+		private int count = 0;
+		private int head = 0;
+
+		synchronized void put(int item) throws InterruptedException {
+			while (items.length <= count) {
+				wait();
+			}
+			int tail = head + count;
+			if (items.length <= tail) {
+				tail -= items.length;
+			}
+			items[tail] = item;
+			count++;
+			notifyAll();
+		}
+
+		synchronized int take() throws InterruptedException {
+			while (count == 0) {
+				wait();
+			}
+			int item = items[head];
+			head++;
+			if (items.length <= head) {
+				head = 0;
+			}
+			count--;
+			notifyAll();
+			return item;
+		}
+	}
+
+	static class Producer extends Thread {
+		private final Buffer buffer;
+
+		Producer(Buffer buffer) {
+			this.buffer = buffer;
+		}
+
+		public void run() {
+			try {
+				for (int i = 1; i <= 1000; i++) {
+					buffer.put(i);
+				}
+				buffer.put(-1);
+			} catch (InterruptedException e) {
+				System.out.println("producer interrupted");
+			}
+		}
+	}
+
+	static class Consumer extends Thread {
+		private final Buffer buffer;
+		long sum = 0;
+
+		Consumer(Buffer buffer) {
+			this.buffer = buffer;
+		}
+
+		public void run() {
+			try {
+				int item = buffer.take();
+				while (item >= 0) {
+					sum += item;
+					item = buffer.take();
+				}
+			} catch (InterruptedException e) {
+				System.out.println("consumer interrupted");
+			}
+		}
+	}
+
+	static class Sleeper implements Runnable {
+		public void run() {
+			try {
+				Thread.sleep(10000);
+				System.out.println("slept");
+			} catch (InterruptedException e) {
+				System.out.println("sleep interrupted");
+			}
+		}
+	}
+
+	static class Waiter extends Thread {
+		private final Object lock;
+		boolean waiting = false;
+
+		Waiter(Object lock) {
+			this.lock = lock;
+		}
+
+		public void run() {
+			synchronized (lock) {
+				waiting = true;
+				lock.notifyAll();
+				try {
+					lock.wait();
+					System.out.println("woken up");
+				} catch (InterruptedException e) {
+					System.out.println("wait interrupted");
+				}
+			}
+		}
+	}
+}
@@ -6,31 +6,52 @@ public class PrintStream {
 	public native void print(long x);
 	public native void print(double x);
 	public native void print(String x);
+	public void print(boolean x) {
+		print(x ? "true" : "false");
+	}
+	public void print(float x) {
+		print(Float.toString(x));
+	}
 	public void print(Object x) {
 		print(x.toString());
 	}
 
-	public void println(int x) {
+	public synchronized void println(boolean x) {
+		print(x);
+		print('\n');
+	}
+
+	public synchronized void println(char x) {
+		print(x);
+		print('\n');
+	}
+
+	public synchronized void println(int x) {
+		print(x);
+		print('\n');
+	}
+
+	public synchronized void println(long x) {
 		print(x);
 		print('\n');
 	}
 
-	public void println(long x) {
+	public synchronized void println(float x) {
 		print(x);
 		print('\n');
 	}
 
-	public void println(double x) {
+	public synchronized void println(double x) {
 		print(x);
 		print('\n');
 	}
 
-	public void println(String x) {
+	public synchronized void println(String x) {
 		print(x);
 		print('\n');
 	}
 
-	public void println(Object x) {
+	public synchronized void println(Object x) {
 		print(x);
 		print('\n');
 	}
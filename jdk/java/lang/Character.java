@@ -6,4 +6,6 @@ public class Character {
 	public Character(char value) {
 		this.value = value;
 	}
+
+	public static native String toString(char c);
 }
\ No newline at end of file
@@ -1,11 +1,55 @@
 package java.lang;
 
 public abstract class ClassLoader {
-	native Class<?> loadClass(String name);
+	private final ClassLoader parent;
 
-	protected Class<?> findClass(String name) throws Exception {
-		throw new Exception(); // TODO: ClassNotFoundException
+	// There is no application class loader, classes on the classpath are loaded by the bootstrap
+	// class loader, which is represented by null
+	protected ClassLoader() {
+		this(null);
+	}
+
+	protected ClassLoader(ClassLoader parent) {
+		this.parent = parent;
+	}
+
+	public final ClassLoader getParent() {
+		return parent;
+	}
+
+	public Class<?> loadClass(String name) throws ClassNotFoundException {
+		return loadClass(name, false);
+	}
+
+	protected Class<?> loadClass(String name, boolean resolve) throws ClassNotFoundException {
+		synchronized (this) {
+			Class<?> c = findLoadedClass(name);
+			if (c == null) {
+				if (parent != null) {
+					try {
+						c = parent.loadClass(name, false);
+					} catch (ClassNotFoundException e) {
+						// Not found by the parent, try to find it here
+					}
+				} else {
+					c = findBootstrapClass(name);
+				}
+
+				if (c == null) {
+					c = findClass(name);
+				}
+			}
+			return c;
+		}
+	}
+
+	protected Class<?> findClass(String name) throws ClassNotFoundException {
+		throw new ClassNotFoundException(name);
 	}
 
 	protected final native Class<?> defineClass(String name, byte[] b, int off, int len);
+
+	protected final native Class<?> findLoadedClass(String name);
+
+	private static native Class<?> findBootstrapClass(String name);
 }
@@ -0,0 +1,11 @@
+package java.lang;
+
+public final class Double {
+	private final double value;
+
+	public Double(double value) {
+		this.value = value;
+	}
+
+	public static native String toString(double d);
+}
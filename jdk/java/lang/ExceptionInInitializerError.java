@@ -0,0 +1,8 @@
+package java.lang;
+
+public class ExceptionInInitializerError extends LinkageError {
+
+	public Throwable getException() {
+		return getCause();
+	}
+}
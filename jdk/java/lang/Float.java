@@ -0,0 +1,11 @@
+package java.lang;
+
+public final class Float {
+	private final float value;
+
+	public Float(float value) {
+		this.value = value;
+	}
+
+	public static native String toString(float f);
+}
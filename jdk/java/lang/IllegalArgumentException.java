@@ -0,0 +1,11 @@
+package java.lang;
+
+public class IllegalArgumentException extends RuntimeException {
+	public IllegalArgumentException() {
+		super();
+	}
+
+	public IllegalArgumentException(String s) {
+		super(s);
+	}
+}
@@ -8,4 +8,6 @@ public final class Long {
 	}
 
 	public static native long parseLong(String num);
+
+	public static native String toString(long l);
 }
\ No newline at end of file
@@ -9,4 +9,9 @@ public class Object {
 	}
 
 	public native String toString();
+
+	public final native void wait() throws InterruptedException;
+	public final native void wait(long timeoutMillis) throws InterruptedException;
+	public final native void notify();
+	public final native void notifyAll();
 }
@@ -23,4 +23,12 @@ public class StackTraceElement {
 	public String getMethodName() {
 		return methodName;
 	}
+
+	public String getFileName() {
+		return fileName;
+	}
+
+	public int getLineNumber() {
+		return lineNumber;
+	}
 }
\ No newline at end of file
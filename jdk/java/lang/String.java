@@ -1,17 +1,43 @@
 package java.lang;
 public final class String {
-	long length;
-	long index;
+	private final char[] value;
+	private int hash;
+
+	private String() {
+		value = new char[0];
+	}
 
 	public native String concat(String other);
 
-	public long length() {
-		return length;
+	public int length() {
+		return value.length;
 	}
 
 	public native char charAt(int index);
 
-	public boolean equals(Object o) {
-		return StringUtil.stringEquals(this, o);
+	public native String substring(int beginIndex);
+
+	public native String substring(int beginIndex, int endIndex);
+
+	public native int indexOf(int ch);
+
+	public native int indexOf(int ch, int fromIndex);
+
+	public native int indexOf(String str);
+
+	public native int hashCode();
+
+	public static String valueOf(Object obj) {
+		return StringUtil.valueOf(obj);
 	}
-}
\ No newline at end of file
+
+	public native boolean equals(Object o);
+
+	public native int compareTo(String other);
+
+	public native boolean equalsIgnoreCase(String other);
+
+	public native String intern();
+
+	public native String toString();
+}
@@ -0,0 +1,51 @@
+package java.lang;
+
+public final class StringBuilder {
+	private char[] value;
+	private int count;
+
+	public StringBuilder() {
+		value = new char[16];
+	}
+
+	public StringBuilder(int capacity) {
+		value = new char[capacity];
+	}
+
+	public StringBuilder(String str) {
+		value = new char[str.length() + 16];
+		append(str);
+	}
+
+	public native StringBuilder append(String str);
+
+	public StringBuilder append(Object obj) {
+		return append(String.valueOf(obj));
+	}
+
+	public StringBuilder append(boolean b) {
+		return append(b ? "true" : "false");
+	}
+
+	public native StringBuilder append(char c);
+
+	public native StringBuilder append(int i);
+
+	public native StringBuilder append(long l);
+
+	public native StringBuilder append(float f);
+
+	public native StringBuilder append(double d);
+
+	public int length() {
+		return count;
+	}
+
+	public native char charAt(int index);
+
+	public native void setLength(int newLength);
+
+	public native StringBuilder reverse();
+
+	public native String toString();
+}
@@ -0,0 +1,87 @@
+package java.lang;
+
+public class Thread implements Runnable {
+	private String name;
+	private boolean daemon;
+	private boolean started;
+	private Runnable target;
+
+	private static int threadNumber;
+
+	public Thread() {
+		this(null, nextThreadName());
+	}
+
+	public Thread(Runnable target) {
+		this(target, nextThreadName());
+	}
+
+	public Thread(String name) {
+		this(null, name);
+	}
+
+	public Thread(Runnable target, String name) {
+		this.target = target;
+		this.name = name;
+		this.daemon = currentThread().isDaemon();
+	}
+
+	private static synchronized String nextThreadName() {
+		return "Thread-".concat(Integer.toString(threadNumber++));
+	}
+
+	public static native Thread currentThread();
+
+	public static native void sleep(long millis) throws InterruptedException;
+
+	public synchronized void start() {
+		if (started) {
+			throw new IllegalThreadStateException();
+		}
+
+		started = true;
+		start0();
+	}
+
+	private native void start0();
+
+	public void run() {
+		if (target != null) {
+			target.run();
+		}
+	}
+
+	public final native boolean isAlive();
+
+	public native void interrupt();
+
+	public native boolean isInterrupted();
+
+	public static native boolean interrupted();
+
+	public final void join() throws InterruptedException {
+		join(0);
+	}
+
+	public final native void join(long millis) throws InterruptedException;
+
+	public final void setDaemon(boolean on) {
+		if (started) {
+			throw new IllegalThreadStateException();
+		}
+
+		daemon = on;
+	}
+
+	public final boolean isDaemon() {
+		return daemon;
+	}
+
+	public final String getName() {
+		return name;
+	}
+
+	public final synchronized void setName(String name) {
+		this.name = name;
+	}
+}
@@ -5,8 +5,10 @@ public class Throwable {
 	private StackTraceElement[] stackTrace;
 
 	public Throwable() {
+		fillInStackTrace();
 	}
 	public Throwable(String message) {
+		fillInStackTrace();
 		detailMessage = message;
 	}
 
@@ -15,6 +17,14 @@ public class Throwable {
 	}
 
 	private String detailMessage;
+
+	private Throwable cause;
+
+	public Throwable getCause() {
+		return cause;
+	}
+
+	public native Throwable fillInStackTrace();
 	
 	public StackTraceElement[] getStackTrace() {
 		return stackTrace; // TODO: Should be cloned
@@ -0,0 +1,5 @@
+package java.lang;
+
+public class VirtualMachineError extends Error {
+
+}
@@ -0,0 +1,12 @@
+package java.lang.invoke;
+
+// Only present so that javac can compile lambdas, the VM links these call sites itself
+public final class LambdaMetafactory {
+	private LambdaMetafactory() { }
+
+	public static CallSite metafactory(MethodHandles.Lookup caller, String interfaceMethodName,
+			MethodType factoryType, MethodType interfaceMethodType, MethodHandle implementation,
+			MethodType dynamicMethodType) {
+		throw new Error();
+	}
+}
@@ -0,0 +1,17 @@
+package java.lang.invoke;
+
+// Only present so that javac can compile string concatenation, the VM links these call sites
+// itself
+public final class StringConcatFactory {
+	private StringConcatFactory() { }
+
+	public static CallSite makeConcat(MethodHandles.Lookup lookup, String name,
+			MethodType concatType) {
+		throw new Error();
+	}
+
+	public static CallSite makeConcatWithConstants(MethodHandles.Lookup lookup, String name,
+			MethodType concatType, String recipe, Object... constants) {
+		throw new Error();
+	}
+}
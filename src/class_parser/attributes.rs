@@ -0,0 +1,387 @@
+use std::io::{Cursor, Read};
+use crate::class_parser::be_reader::BEReader;
+use crate::class_parser::constants::CPInfo;
+use crate::class_parser::{ParseError, ParseErrorType};
+use crate::class_parser::types::{U1, U2, U4};
+
+/// Attribute of a class, field, method, Code attribute or record component (JVMS §4.7).
+/// Attributes the VM has no use for are still checked for well-formedness and valid constant
+/// pool references, but only their presence is kept. Attributes unknown to the VM are skipped.
+#[derive(Debug)]
+pub enum Attribute {
+    Code(CodeAttribute),
+    ConstantValue(U2),
+    Exceptions,
+    InnerClasses,
+    EnclosingMethod,
+    Signature,
+    SourceFile(U2),
+    LineNumberTable(Vec<LineNumber>),
+    LocalVariableTable,
+    LocalVariableTypeTable,
+    StackMapTable(Vec<StackMapFrame>),
+    BootstrapMethods(Vec<BootstrapMethodInfo>),
+    NestHost(U2),
+    NestMembers(Vec<U2>),
+    Record,
+    PermittedSubclasses,
+    Unknown
+}
+
+#[derive(Debug)]
+pub struct CodeAttribute {
+    pub max_stack: U2,
+    pub max_locals: U2,
+    pub code: Vec<u8>,
+    pub exception_table: Vec<ExceptionTableEntry>,
+    pub attributes: Vec<Attribute>
+}
+
+#[derive(Debug)]
+pub struct ExceptionTableEntry {
+    pub start_pc: U2,
+    pub end_pc: U2,
+    pub handler_pc: U2,
+    pub catch_type: U2 // 0 for handlers catching every exception
+}
+
+#[derive(Debug)]
+pub struct LineNumber {
+    pub start_pc: U2,
+    pub line_number: U2
+}
+
+/// Entry of a StackMapTable, the `_extended` variants are folded into their short forms
+#[derive(Debug, PartialEq)]
+pub enum StackMapFrame {
+    Same { offset_delta: U2 },
+    SameLocals1StackItem { offset_delta: U2, stack: VerificationTypeInfo },
+    Chop { offset_delta: U2, k: U1 },
+    Append { offset_delta: U2, locals: Vec<VerificationTypeInfo> },
+    Full { offset_delta: U2, locals: Vec<VerificationTypeInfo>, stack: Vec<VerificationTypeInfo> }
+}
+
+impl StackMapFrame {
+    pub fn offset_delta(&self) -> U2 {
+        match self {
+            StackMapFrame::Same { offset_delta }
+            | StackMapFrame::SameLocals1StackItem { offset_delta, .. }
+            | StackMapFrame::Chop { offset_delta, .. }
+            | StackMapFrame::Append { offset_delta, .. }
+            | StackMapFrame::Full { offset_delta, .. } => *offset_delta
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VerificationTypeInfo {
+    Top,
+    Integer,
+    Float,
+    Double,
+    Long,
+    Null,
+    UninitializedThis,
+    Object(U2), // Class constant
+    Uninitialized(U2) // Offset of the new instruction
+}
+
+#[derive(Debug)]
+pub struct BootstrapMethodInfo {
+    pub bootstrap_method_ref: U2,
+    pub bootstrap_arguments: Vec<U2>
+}
+
+fn format_error(message: String) -> ParseError {
+    ParseError { kind: ParseErrorType::ParseErrorType, message }
+}
+
+pub fn parse_attribute(reader: &mut impl Read, constant_pool: &[CPInfo]) -> Result<Attribute,
+    ParseError> {
+    let attribute_name_index = U2::read(reader)?;
+    let attribute_length = U4::read(reader)?;
+
+    // The buffer only grows as far as the input goes, so a bogus length can not make it allocate
+    // more than the class file has
+    let mut info = Vec::new();
+    reader.take(attribute_length as u64).read_to_end(&mut info)?;
+    if info.len() != attribute_length as usize {
+        return Err(ParseError {
+            kind: ParseErrorType::Truncated,
+            message: "Truncated attribute".to_string()
+        });
+    }
+
+    let name = match (attribute_name_index as usize).checked_sub(1)
+        .and_then(|i| constant_pool.get(i)) {
+        Some(CPInfo::Utf8(name)) => name.as_str(),
+        _ => return Err(format_error(format!("Invalid attribute name index {}",
+                                             attribute_name_index)))
+    };
+
+    let mut cursor = Cursor::new(info.as_slice());
+    let attribute = match parse_known_attribute(name, &mut cursor, constant_pool) {
+        Ok(Some(attribute)) => attribute,
+        Ok(None) => return Ok(Attribute::Unknown),
+        Err(e) if e.kind == ParseErrorType::Truncated => return Err(ParseError {
+            kind: ParseErrorType::Truncated,
+            message: format!("Truncated {} attribute", name)
+        }),
+        Err(e) => return Err(e)
+    };
+
+    if cursor.position() != info.len() as u64 {
+        return Err(format_error(format!("{} attribute is longer than its contents", name)));
+    }
+
+    Ok(attribute)
+}
+
+fn parse_known_attribute<R: Read>(name: &str, reader: &mut R, constant_pool: &[CPInfo]) ->
+                                                            Result<Option<Attribute>, ParseError> {
+    let reference = |reader: &mut R, optional: bool, expected: fn(&CPInfo) -> bool| {
+        let index = U2::read(reader)?;
+        match (index as usize).checked_sub(1).and_then(|i| constant_pool.get(i)) {
+            Some(entry) if expected(entry) => Ok(index),
+            None if optional && index == 0 => Ok(index),
+            _ => Err(format_error(format!("Invalid constant pool index {} in {} attribute", index,
+                                          name)))
+        }
+    };
+    let class = |reader: &mut R| reference(reader, false, |e| matches!(e, CPInfo::Class(_)));
+    let utf8 = |reader: &mut R| reference(reader, false, |e| matches!(e, CPInfo::Utf8(_)));
+
+    Ok(Some(match name {
+        "Code" => {
+            let max_stack = U2::read(reader)?;
+            let max_locals = U2::read(reader)?;
+
+            let code_length = U4::read(reader)?;
+            if code_length == 0 || code_length >= 65536 {
+                return Err(format_error(format!("Invalid code length {}", code_length)));
+            }
+            let mut code = vec![0; code_length as usize];
+            reader.read_exact(&mut code)?;
+
+            let exception_table = parse_table(reader, |reader| Ok(ExceptionTableEntry {
+                start_pc: U2::read(reader)?,
+                end_pc: U2::read(reader)?,
+                handler_pc: U2::read(reader)?,
+                catch_type: U2::read(reader)?
+            }))?;
+            let attributes = parse_table(reader,
+                                         |reader| parse_attribute(reader, constant_pool))?;
+
+            Attribute::Code(CodeAttribute { max_stack, max_locals, code, exception_table,
+                attributes })
+        }
+        "ConstantValue" => Attribute::ConstantValue(U2::read(reader)?),
+        "Exceptions" => {
+            parse_table(reader, class)?;
+            Attribute::Exceptions
+        }
+        "InnerClasses" => {
+            parse_table(reader, |reader| {
+                class(reader)?;
+                reference(reader, true, |e| matches!(e, CPInfo::Class(_)))?;
+                reference(reader, true, |e| matches!(e, CPInfo::Utf8(_)))?;
+                U2::read(reader) // inner_class_access_flags
+            })?;
+            Attribute::InnerClasses
+        }
+        "EnclosingMethod" => {
+            class(reader)?;
+            reference(reader, true, |e| matches!(e, CPInfo::NameAndType(..)))?;
+            Attribute::EnclosingMethod
+        }
+        "Signature" => {
+            utf8(reader)?;
+            Attribute::Signature
+        }
+        "SourceFile" => Attribute::SourceFile(U2::read(reader)?),
+        "LineNumberTable" => Attribute::LineNumberTable(parse_table(reader, |reader| Ok(LineNumber {
+            start_pc: U2::read(reader)?,
+            line_number: U2::read(reader)?
+        }))?),
+        "LocalVariableTable" | "LocalVariableTypeTable" => {
+            // start_pc, length, name_index, descriptor_index or signature_index, index
+            parse_table(reader, |reader| {
+                U2::read(reader)?;
+                U2::read(reader)?;
+                utf8(reader)?;
+                utf8(reader)?;
+                U2::read(reader)
+            })?;
+            if name == "LocalVariableTable" {
+                Attribute::LocalVariableTable
+            } else {
+                Attribute::LocalVariableTypeTable
+            }
+        }
+        "StackMapTable" => Attribute::StackMapTable(parse_table(reader, parse_stack_map_frame)?),
+        "BootstrapMethods" => Attribute::BootstrapMethods(parse_table(reader,
+            |reader| Ok(BootstrapMethodInfo {
+                bootstrap_method_ref: U2::read(reader)?,
+                bootstrap_arguments: parse_table(reader, U2::read)?
+            }))?),
+        "NestHost" => Attribute::NestHost(U2::read(reader)?),
+        "NestMembers" => Attribute::NestMembers(parse_table(reader, U2::read)?),
+        "Record" => {
+            parse_table(reader, |reader| {
+                utf8(reader)?;
+                utf8(reader)?;
+                parse_table(reader, |reader| parse_attribute(reader, constant_pool))
+            })?;
+            Attribute::Record
+        }
+        "PermittedSubclasses" => {
+            parse_table(reader, class)?;
+            Attribute::PermittedSubclasses
+        }
+        _ => return Ok(None)
+    }))
+}
+
+/// Parses a table preceded by its u2 length
+fn parse_table<R: Read, T>(reader: &mut R, mut parse: impl FnMut(&mut R) -> Result<T, ParseError>)
+    -> Result<Vec<T>, ParseError> {
+    let length = U2::read(reader)?;
+
+    let mut vec = Vec::with_capacity(length as usize);
+    for _ in 0..length {
+        vec.push(parse(reader)?);
+    }
+
+    Ok(vec)
+}
+
+fn parse_stack_map_frame(reader: &mut impl Read) -> Result<StackMapFrame, ParseError> {
+    let frame_type = U1::read(reader)?;
+
+    Ok(match frame_type {
+        0..=63 => StackMapFrame::Same { offset_delta: frame_type as U2 },
+        64..=127 => StackMapFrame::SameLocals1StackItem {
+            offset_delta: frame_type as U2 - 64,
+            stack: parse_verification_type(reader)?
+        },
+        247 => StackMapFrame::SameLocals1StackItem {
+            offset_delta: U2::read(reader)?,
+            stack: parse_verification_type(reader)?
+        },
+        248..=250 => StackMapFrame::Chop { offset_delta: U2::read(reader)?, k: 251 - frame_type },
+        251 => StackMapFrame::Same { offset_delta: U2::read(reader)? },
+        252..=254 => {
+            let offset_delta = U2::read(reader)?;
+
+            let mut locals = Vec::with_capacity((frame_type - 251) as usize);
+            for _ in 251..frame_type {
+                locals.push(parse_verification_type(reader)?);
+            }
+
+            StackMapFrame::Append { offset_delta, locals }
+        }
+        255 => StackMapFrame::Full {
+            offset_delta: U2::read(reader)?,
+            locals: parse_table(reader, parse_verification_type)?,
+            stack: parse_table(reader, parse_verification_type)?
+        },
+        _ => return Err(format_error(format!("Invalid stack map frame type {}", frame_type)))
+    })
+}
+
+fn parse_verification_type(reader: &mut impl Read) -> Result<VerificationTypeInfo, ParseError> {
+    use VerificationTypeInfo::*;
+
+    Ok(match U1::read(reader)? {
+        0 => Top,
+        1 => Integer,
+        2 => Float,
+        3 => Double,
+        4 => Long,
+        5 => Null,
+        6 => UninitializedThis,
+        7 => Object(U2::read(reader)?),
+        8 => Uninitialized(U2::read(reader)?),
+        tag => return Err(format_error(format!("Invalid verification type {}", tag)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::class_parser::attributes::{Attribute, parse_attribute, StackMapFrame,
+                                          VerificationTypeInfo};
+    use crate::class_parser::constants::CPInfo;
+
+    fn constant_pool() -> Vec<CPInfo> {
+        ["Code", "LineNumberTable", "StackMapTable", "Custom", "Signature"].iter()
+            .map(|s| CPInfo::Utf8(s.to_string())).collect()
+    }
+
+    #[test]
+    fn parses_code() {
+        let buf = [
+            0, 1, 0, 0, 0, 53, // Code, length
+            0, 2, 0, 1, 0, 0, 0, 3, 0x03, 0x3b, 0xb1, // max_stack, max_locals, iconst_0 istore_0 return
+            0, 0, // exception_table_length
+            0, 2, // attributes_count
+            0, 2, 0, 0, 0, 6, 0, 1, 0, 0, 0, 42, // LineNumberTable
+            0, 3, 0, 0, 0, 20, 0, 3, // StackMapTable with 3 frames
+            2, // same
+            252, 0, 1, 1, // append int
+            255, 0, 0, 0, 1, 7, 0, 1, 0, 1, 8, 0, 0 // full
+        ];
+
+        let code = match parse_attribute(&mut Cursor::new(&buf[..]), &constant_pool()).unwrap() {
+            Attribute::Code(code) => code,
+            a => panic!("Unexpected attribute {:?}", a)
+        };
+        assert_eq!((code.max_stack, code.max_locals), (2, 1));
+        assert_eq!(code.code, vec![0x03, 0x3b, 0xb1]);
+
+        match &code.attributes[0] {
+            Attribute::LineNumberTable(lines) =>
+                assert_eq!((lines[0].start_pc, lines[0].line_number), (0, 42)),
+            a => panic!("Unexpected attribute {:?}", a)
+        }
+        match &code.attributes[1] {
+            Attribute::StackMapTable(frames) => assert_eq!(frames, &vec![
+                StackMapFrame::Same { offset_delta: 2 },
+                StackMapFrame::Append { offset_delta: 1, locals: vec![VerificationTypeInfo::Integer] },
+                StackMapFrame::Full {
+                    offset_delta: 0,
+                    locals: vec![VerificationTypeInfo::Object(1)],
+                    stack: vec![VerificationTypeInfo::Uninitialized(0)]
+                }
+            ]),
+            a => panic!("Unexpected attribute {:?}", a)
+        }
+    }
+
+    #[test]
+    fn skips_unknown_attributes() {
+        let buf = [0, 4, 0, 0, 0, 3, 1, 2, 3, 0xff];
+
+        let mut reader = Cursor::new(&buf[..]);
+        assert!(matches!(parse_attribute(&mut reader, &constant_pool()).unwrap(),
+                         Attribute::Unknown));
+        assert_eq!(reader.position(), 9);
+    }
+
+    #[test]
+    fn rejects_malformed_attributes() {
+        let error = |buf: &[u8]| parse_attribute(&mut Cursor::new(buf), &constant_pool())
+            .unwrap_err().message;
+
+        assert_eq!(error(&[0, 2, 0, 0, 0, 4, 0, 1, 0, 0]), "Truncated LineNumberTable attribute");
+        assert_eq!(error(&[0, 2, 0, 0, 0, 8, 0, 1, 0, 0, 0, 1, 0, 0]),
+                   "LineNumberTable attribute is longer than its contents");
+        assert_eq!(error(&[0, 3, 0, 0, 0, 3, 0, 1, 200]), "Invalid stack map frame type 200");
+        assert_eq!(error(&[0, 9, 0, 0, 0, 0]), "Invalid attribute name index 9");
+        assert_eq!(error(&[0, 2, 0, 0, 0, 4, 0, 1]), "Truncated attribute");
+        // A length far beyond the input is reported without allocating that much
+        assert_eq!(error(&[0, 2, 0xff, 0xff, 0xff, 0xff, 0, 1]), "Truncated attribute");
+        assert_eq!(error(&[0, 5, 0, 0, 0, 2, 0, 0]),
+                   "Invalid constant pool index 0 in Signature attribute");
+    }
+}
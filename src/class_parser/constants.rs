@@ -90,6 +90,7 @@ pub const fn cp_info_to_tag(cp_info: &CPInfo) -> Option<CPTag> {
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub enum CPInfo {
     Class(U2),
     String(U2),
@@ -1,17 +1,21 @@
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-use std::io::{Cursor, Read};
-use std::string::FromUtf8Error;
-use crate::class_parser::types::{AttributeInfo, FieldInfo, MethodInfo, ParsedClass, U1, U2, U4};
+use std::io::{Cursor, ErrorKind, Read};
+use crate::class_parser::types::{FieldInfo, MethodInfo, ParsedClass, U1, U2, U4};
+use crate::class_parser::attributes::parse_attribute;
 use crate::class_parser::be_reader::BEReader;
 use crate::class_parser::constants::{CPInfo, CPTag, tag_to_2U2_constructor, tag_to_2U4_constructor, tag_to_U2_constructor, tag_to_U4_constructor};
 
 pub mod types;
+pub mod attributes;
 pub mod constants;
+pub mod mutf8;
 mod be_reader;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ParseErrorType {
-    ParseErrorType
+    ParseErrorType,
+    Truncated
 }
 
 #[derive(Debug)]
@@ -28,9 +32,6 @@ impl Display for ParseError {
 
 pub trait ToParseError {} // Required because generic would conflict with blanket impl of From
 
-impl ToParseError for std::io::Error {}
-impl ToParseError for FromUtf8Error {}
-
 impl<T: Display + ToParseError> From<T> for ParseError {
     fn from(e: T) -> Self {
         ParseError {
@@ -40,7 +41,22 @@ impl<T: Display + ToParseError> From<T> for ParseError {
     }
 }
 
-fn parse_cp_info(reader: &mut impl Read, constant_pool: &mut Vec<CPInfo>) -> Result<(), ParseError> {
+impl From<std::io::Error> for ParseError {
+    fn from(e: std::io::Error) -> Self {
+        let kind = match e.kind() {
+            ErrorKind::UnexpectedEof => ParseErrorType::Truncated,
+            _ => ParseErrorType::ParseErrorType
+        };
+
+        ParseError {
+            kind,
+            message: e.to_string()
+        }
+    }
+}
+
+fn parse_cp_info(reader: &mut impl Read, constant_pool: &mut Vec<CPInfo>,
+                 utf16: &mut HashMap<U2, Vec<u16>>) -> Result<(), ParseError> {
     use CPTag::*;
 
     let tag = U1::read(reader)?;
@@ -52,7 +68,9 @@ fn parse_cp_info(reader: &mut impl Read, constant_pool: &mut Vec<CPInfo>) -> Res
             let length = U2::read(reader)? as usize;
             let mut buf = vec![0; length];
             reader.read_exact(&mut buf)?;
-            constant_pool.push(CPInfo::Utf8(std::string::String::from_utf8(buf)?));
+            let units = mutf8::decode_utf16(&buf)?;
+            constant_pool.push(CPInfo::Utf8(mutf8::decode_units(&units)));
+            utf16.insert(constant_pool.len() as U2, units);
         }
         Class | String | MethodType | Module | Package => {
             let u2 = U2::read(reader)?;
@@ -84,7 +102,8 @@ fn parse_cp_info(reader: &mut impl Read, constant_pool: &mut Vec<CPInfo>) -> Res
     Ok(())
 }
 
-fn parse_field_info(reader: &mut impl Read) -> Result<FieldInfo, ParseError> {
+fn parse_field_info(reader: &mut impl Read, constant_pool: &[CPInfo]) -> Result<FieldInfo,
+    ParseError> {
     let access_flags = U2::read(reader)?;
     let name_index = U2::read(reader)?;
     let descriptor_index = U2::read(reader)?;
@@ -92,7 +111,7 @@ fn parse_field_info(reader: &mut impl Read) -> Result<FieldInfo, ParseError> {
 
     let mut attributes = Vec::with_capacity(attributes_count as usize);
     for _ in 0..attributes_count {
-        attributes.push(parse_attributes_info(reader)?);
+        attributes.push(parse_attribute(reader, constant_pool)?);
     }
 
     Ok(FieldInfo {
@@ -104,7 +123,8 @@ fn parse_field_info(reader: &mut impl Read) -> Result<FieldInfo, ParseError> {
     })
 }
 
-fn parse_method_info(reader: &mut impl Read) -> Result<MethodInfo, ParseError> {
+fn parse_method_info(reader: &mut impl Read, constant_pool: &[CPInfo]) -> Result<MethodInfo,
+    ParseError> {
     let access_flags = U2::read(reader)?;
     let name_index = U2::read(reader)?;
     let descriptor_index = U2::read(reader)?;
@@ -112,7 +132,7 @@ fn parse_method_info(reader: &mut impl Read) -> Result<MethodInfo, ParseError> {
 
     let mut attributes = Vec::with_capacity(attributes_count as usize);
     for _ in 0..attributes_count {
-        attributes.push(parse_attributes_info(reader)?);
+        attributes.push(parse_attribute(reader, constant_pool)?);
     }
 
     Ok(MethodInfo {
@@ -124,20 +144,6 @@ fn parse_method_info(reader: &mut impl Read) -> Result<MethodInfo, ParseError> {
     })
 }
 
-fn parse_attributes_info(reader: &mut impl Read) -> Result<AttributeInfo, ParseError> {
-    let attribute_name_index = U2::read(reader)?;
-    let attribute_length = U4::read(reader)?;
-
-    let mut vec = vec![0; attribute_length as usize];
-    reader.read_exact(&mut vec).unwrap();
-
-    Ok(AttributeInfo {
-        attribute_name_index,
-        attribute_length,
-        info: vec,
-    })
-}
-
 // the method with the same name is unstable for cursor
 trait ReadStatus {
     fn check_if_empty(&mut self) -> bool;
@@ -167,9 +173,16 @@ pub fn parse_class(buf: &[u8]) -> Result<ParsedClass, ParseError> {
 
     let constant_pool_count = U2::read(&mut cursor)? - 1;
     let mut constant_pool = Vec::with_capacity(constant_pool_count as usize);
+    let mut utf16 = HashMap::new();
     while constant_pool.len() < constant_pool_count as usize {
-        parse_cp_info(&mut cursor, &mut constant_pool)?;
+        parse_cp_info(&mut cursor, &mut constant_pool, &mut utf16)?;
     }
+    let string_constants = constant_pool.iter()
+        .filter_map(|info| match info {
+            CPInfo::String(index) => utf16.get(index).map(|units| (*index, units.clone())),
+            _ => None
+        })
+        .collect();
 
     let access_flags = U2::read(&mut cursor)?;
     let this_class = U2::read(&mut cursor)?;
@@ -184,19 +197,19 @@ pub fn parse_class(buf: &[u8]) -> Result<ParsedClass, ParseError> {
     let fields_count = U2::read(&mut cursor)?;
     let mut fields = Vec::with_capacity(fields_count as usize);
     for _ in 0..fields_count {
-        fields.push(parse_field_info(&mut cursor)?);
+        fields.push(parse_field_info(&mut cursor, &constant_pool)?);
     }
 
     let methods_count = U2::read(&mut cursor)?;
     let mut methods = Vec::with_capacity(methods_count as usize);
     for _ in 0..methods_count {
-        methods.push(parse_method_info(&mut cursor)?);
+        methods.push(parse_method_info(&mut cursor, &constant_pool)?);
     }
 
     let attributes_count = U2::read(&mut cursor)?;
     let mut attributes = Vec::with_capacity(attributes_count as usize);
     for _ in 0..attributes_count {
-        attributes.push(parse_attributes_info(&mut cursor)?);
+        attributes.push(parse_attribute(&mut cursor, &constant_pool)?);
     }
 
 
@@ -212,7 +225,8 @@ pub fn parse_class(buf: &[u8]) -> Result<ParsedClass, ParseError> {
             interfaces,
             fields,
             methods,
-            attributes
+            attributes,
+            string_constants
         };
 
         // println!("{:#?}", parsed_class);
@@ -0,0 +1,138 @@
+//! Conversion of the modified UTF-8 encoding used by class files (JVMS §4.4.7). It differs from
+//! standard UTF-8 in that NUL is encoded in two bytes (C0 80) and supplementary characters are
+//! encoded as a surrogate pair of two three-byte sequences, without any four-byte forms.
+
+use std::char::REPLACEMENT_CHARACTER;
+use std::fmt::{Display, Formatter};
+use crate::class_parser::ToParseError;
+
+#[derive(Debug, PartialEq)]
+pub struct ModifiedUtf8Error {
+    pub position: usize
+}
+
+impl Display for ModifiedUtf8Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid modified UTF-8 sequence at byte {}", self.position)
+    }
+}
+
+impl ToParseError for ModifiedUtf8Error {}
+
+/// Decodes the bytes into UTF-16 code units, keeping unpaired surrogates
+pub fn decode_utf16(bytes: &[u8]) -> Result<Vec<u16>, ModifiedUtf8Error> {
+    let mut units = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let continuation = |j: usize| match bytes.get(j) {
+            Some(b) if b & 0xC0 == 0x80 => Ok((b & 0x3F) as u16),
+            _ => Err(ModifiedUtf8Error { position: j })
+        };
+
+        let b = bytes[i];
+        match b {
+            0x01..=0x7F => {
+                units.push(b as u16);
+                i += 1;
+            }
+            0xC0..=0xDF => {
+                units.push(((b & 0x1F) as u16) << 6 | continuation(i + 1)?);
+                i += 2;
+            }
+            0xE0..=0xEF => {
+                units.push(((b & 0x0F) as u16) << 12 | continuation(i + 1)? << 6
+                    | continuation(i + 2)?);
+                i += 3;
+            }
+            // NUL has no single byte form and there are no four-byte forms
+            _ => return Err(ModifiedUtf8Error { position: i })
+        }
+    }
+
+    Ok(units)
+}
+
+/// Converts the decoded code units into a string. Surrogates that are not part of a pair can not
+/// be represented and are replaced by U+FFFD.
+pub fn decode_units(units: &[u16]) -> String {
+    char::decode_utf16(units.iter().copied())
+        .map(|c| c.unwrap_or(REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// Encodes the string the way it would appear in a class file
+#[cfg(test)]
+pub fn encode(str: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(str.len());
+
+    for unit in str.encode_utf16() {
+        match unit {
+            0x0001..=0x007F => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::class_parser::mutf8::{decode_units, decode_utf16, encode, ModifiedUtf8Error};
+
+    fn decode(bytes: &[u8]) -> Result<String, ModifiedUtf8Error> {
+        decode_utf16(bytes).map(|units| decode_units(&units))
+    }
+
+    #[test]
+    fn embedded_nul() {
+        assert_eq!(encode("a\0b"), [b'a', 0xC0, 0x80, b'b']);
+        assert_eq!(decode(&[b'a', 0xC0, 0x80, b'b']).unwrap(), "a\0b");
+
+        // The single byte form of NUL is not allowed
+        assert_eq!(decode(&[b'a', 0, b'b']), Err(ModifiedUtf8Error { position: 1 }));
+    }
+
+    #[test]
+    fn supplementary_characters() {
+        // U+1F600 is the surrogate pair D83D DE00
+        let encoded = [0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80];
+        assert_eq!(encode("\u{1F600}"), encoded);
+        assert_eq!(decode(&encoded).unwrap(), "\u{1F600}");
+        assert_eq!(decode_utf16(&encoded).unwrap(), [0xD83D, 0xDE00]);
+
+        // Standard UTF-8 four-byte forms are not allowed
+        assert_eq!(decode("\u{1F600}".as_bytes()), Err(ModifiedUtf8Error { position: 0 }));
+
+        // An unpaired surrogate is kept as a code unit, but can not be part of a string
+        assert_eq!(decode_utf16(&[0xED, 0xA0, 0xBD, b'x']).unwrap(), [0xD83D, b'x' as u16]);
+        assert_eq!(decode(&[0xED, 0xA0, 0xBD, b'x']).unwrap(), "\u{FFFD}x");
+    }
+
+    #[test]
+    fn round_trip() {
+        for str in ["", "java/lang/Object", "<init>", "árvíztűrő tükörfúrógép", "日本語",
+            "\u{7F}\u{80}\u{7FF}\u{800}\u{FFFF}\u{10000}\u{10FFFF}"] {
+            assert_eq!(decode(&encode(str)).unwrap(), str);
+        }
+
+        // Characters below U+10000 are encoded the same way as in standard UTF-8
+        assert_eq!(encode("árvíztűrő"), "árvíztűrő".as_bytes());
+    }
+
+    #[test]
+    fn truncated_sequences() {
+        assert_eq!(decode(&[b'a', 0xC3]), Err(ModifiedUtf8Error { position: 2 }));
+        assert_eq!(decode(&[0xE6, 0x97]), Err(ModifiedUtf8Error { position: 2 }));
+        assert_eq!(decode(&[0xE6, b'a', 0xA5]), Err(ModifiedUtf8Error { position: 1 }));
+        assert_eq!(decode(&[0x80]), Err(ModifiedUtf8Error { position: 0 }));
+    }
+}
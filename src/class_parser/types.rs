@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+use crate::class_parser::attributes::Attribute;
 use crate::class_parser::constants::{cp_info_to_tag, CPInfo, CPTag};
 
 pub type U1 = u8;
@@ -5,31 +7,27 @@ pub type U2 = u16;
 pub type U4 = u32;
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub struct FieldInfo {
     pub access_flags: U2,
     pub name_index: U2,
     pub descriptor_index: U2,
     pub attributes_count: U2,
-    pub attributes: Vec<AttributeInfo>, // of attributes_count length
+    pub attributes: Vec<Attribute>, // of attributes_count length
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub struct MethodInfo {
     pub access_flags: U2,
     pub name_index: U2,
     pub descriptor_index: U2,
     pub attributes_count: U2,
-    pub attributes: Vec<AttributeInfo>, // of attributes_count length
-}
-
-#[derive(Debug)]
-pub struct AttributeInfo {
-    pub attribute_name_index: U2,
-    pub attribute_length: U4,
-    pub info: Vec<u8>,
+    pub attributes: Vec<Attribute>, // of attributes_count length
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub struct ParsedClass {
     pub minor_version: U2,
     pub major_version: U2,
@@ -40,7 +38,10 @@ pub struct ParsedClass {
     pub interfaces: Vec<U2>, // of length interfaces_count
     pub fields: Vec<FieldInfo>, // of length fields_count
     pub methods: Vec<MethodInfo>, // of length methods_count
-    pub attributes: Vec<AttributeInfo>, // of length attributes_count
+    pub attributes: Vec<Attribute>, // of length attributes_count
+    /// UTF-16 code units of the Utf8 entries referenced by String entries, by index. Unlike the
+    /// decoded Utf8 strings, they keep unpaired surrogates.
+    pub string_constants: HashMap<U2, Vec<u16>>,
 }
 
 impl ParsedClass {
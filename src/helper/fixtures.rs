@@ -0,0 +1,81 @@
+//! Fixtures for tests, built at test time with the JDK tools. Java sources of the fixtures are
+//! checked in under test/fixtures.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// An empty directory for the fixtures of one test, unique to the test and process
+pub fn temp_dir(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("rust-jvm3-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+
+    directory
+}
+
+/// Compiles every Java source under the directory, given relative to the root of the crate, into
+/// the output directory
+pub fn javac(sources: &str, class_path: &[&Path], output: &Path) {
+    let mut files = vec![];
+    find_sources(&Path::new(env!("CARGO_MANIFEST_DIR")).join(sources), &mut files);
+
+    let mut command = Command::new("javac");
+    command.arg("-d").arg(output)
+        .arg("-cp").arg(std::env::join_paths(class_path).unwrap())
+        .args(files);
+    run(command);
+}
+
+/// Compiles the sources under the directory, like `javac`, and returns the class file of the class
+/// with the given binary name
+pub fn class_file(sources: &str, class: &str) -> Vec<u8> {
+    let directory = temp_dir(&sources.replace('/', "-"));
+    javac(sources, &[], &directory);
+
+    let class_file = std::fs::read(directory.join(class.replace('.', "/") + ".class")).unwrap();
+    std::fs::remove_dir_all(directory).unwrap();
+    class_file
+}
+
+fn find_sources(directory: &Path, files: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            find_sources(&path, files);
+        } else if path.extension().is_some_and(|e| e == "java") {
+            files.push(path);
+        }
+    }
+}
+
+/// Adds the files of the directory to the archive, creating it with the given manifest if it does
+/// not exist yet. Compressed entries are deflated, the others are stored.
+pub fn jar(archive: &Path, manifest: Option<&str>, directory: &Path, files: &[&str],
+           compress: bool) {
+    let mut command = Command::new("jar");
+    command.arg(if archive.exists() { "--update" } else { "--create" }).arg("--file").arg(archive);
+    match manifest {
+        Some(manifest) => {
+            let path = archive.with_extension("MF");
+            std::fs::write(&path, manifest).unwrap();
+            command.arg("--manifest").arg(path);
+        }
+        None => {
+            command.arg("--no-manifest");
+        }
+    }
+    if !compress {
+        command.arg("--no-compress");
+    }
+    for file in files {
+        command.arg("-C").arg(directory).arg(file);
+    }
+    run(command);
+}
+
+fn run(mut command: Command) {
+    let output = command.output()
+        .unwrap_or_else(|e| panic!("Could not run {:?}: {}", command, e));
+    assert!(output.status.success(), "{:?} failed:\n{}", command,
+            String::from_utf8_lossy(&output.stderr));
+}
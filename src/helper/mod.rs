@@ -1,3 +1,7 @@
+pub mod number;
+#[cfg(test)]
+pub mod fixtures;
+
 use std::ops::BitAnd;
 
 pub fn has_flag<U, T: Into<U>>(value: U, flag: T) -> bool
@@ -33,14 +37,22 @@ pub fn ftou2(f: f64) -> u64 {
 
 #[cfg(test)]
 mod test {
-    use crate::helper::{ftou2, utof2};
+    use crate::helper::{ftou, ftou2, utof, utof2};
 
     #[test]
     pub fn float_converter() {
-        let a: f64 = -3.141592654;
+        let a: f64 = -1234.5678;
         let b = ftou2(a);
         let a2 = utof2(b);
 
         assert_eq!(a, a2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn single_float_converter() {
+        let a: f32 = -1234.5678;
+        assert_eq!(utof(ftou(a)), a);
+        assert_eq!(ftou(1.0), 0x3f80_0000);
+        assert!(utof(0x7fc0_0000).is_nan());
+    }
+}
@@ -0,0 +1,483 @@
+//! Conversion of floating point numbers to strings the way Double.toString and Float.toString do
+//! up to JDK 18. The digits are developed by a port of FloatingDecimal.dtoa from JDK 17, which
+//! usually yields the shortest decimal that rounds to the value, at least two digits long, written
+//! in plain notation if the magnitude is in [10^-3, 10^7), otherwise in computerized scientific
+//! notation (e.g. 1.0E-5).
+//!
+//! Its deviations from the shortest decimal are reproduced as well: integral values below 2^63 are
+//! printed from their exact value, only rounding off the digits that are insignificant for the
+//! precision of the type (2^60 is 1.15292150460684698E18), and the digit generation may go on
+//! longer than needed (1.0E23 is 9.999999999999999E22, 2^-96 is 1.2621774483536189E-29) or end in
+//! a digit that is not the closest one (2.1235808073258287E25 is 2.1235808073258286E25).
+
+use std::cmp::{max, min, Ordering};
+
+pub fn double_to_string(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else if value == 0.0 {
+        if value.is_sign_negative() { "-0.0" } else { "0.0" }.to_string()
+    } else {
+        let bits = value.to_bits();
+        let mut fract_bits = bits & ((1 << EXP_SHIFT) - 1);
+        let biased_exp = (bits >> EXP_SHIFT) as i32 & 0x7FF;
+        let (bin_exp, significant_bits) = if biased_exp == 0 {
+            // Subnormal, normalized so that the highest bit is at EXP_SHIFT
+            let leading_zeros = fract_bits.leading_zeros();
+            let shift = leading_zeros - (63 - EXP_SHIFT);
+            fract_bits <<= shift;
+            (1 - shift as i32, 64 - leading_zeros as i32)
+        } else {
+            fract_bits |= 1 << EXP_SHIFT;
+            (biased_exp, EXP_SHIFT as i32 + 1)
+        };
+
+        let (digits, exponent) = dtoa(bin_exp - 1023, fract_bits, significant_bits);
+        format_decimal(value < 0.0, &digits, exponent)
+    }
+}
+
+pub fn float_to_string(value: f32) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else if value == 0.0 {
+        if value.is_sign_negative() { "-0.0" } else { "0.0" }.to_string()
+    } else {
+        const SINGLE_EXP_SHIFT: u32 = f32::MANTISSA_DIGITS - 1;
+
+        let bits = value.to_bits();
+        let mut fract_bits = bits & ((1 << SINGLE_EXP_SHIFT) - 1);
+        let biased_exp = (bits >> SINGLE_EXP_SHIFT) as i32 & 0xFF;
+        let (bin_exp, significant_bits) = if biased_exp == 0 {
+            let leading_zeros = fract_bits.leading_zeros();
+            let shift = leading_zeros - (31 - SINGLE_EXP_SHIFT);
+            fract_bits <<= shift;
+            (1 - shift as i32, 32 - leading_zeros as i32)
+        } else {
+            fract_bits |= 1 << SINGLE_EXP_SHIFT;
+            (biased_exp, SINGLE_EXP_SHIFT as i32 + 1)
+        };
+
+        let (digits, exponent) = dtoa(bin_exp - 127, (fract_bits as u64)
+            << (EXP_SHIFT - SINGLE_EXP_SHIFT), significant_bits);
+        format_decimal(value < 0.0, &digits, exponent)
+    }
+}
+
+/// Position of the highest bit of the normalized fraction passed to dtoa
+const EXP_SHIFT: u32 = f64::MANTISSA_DIGITS - 1;
+
+/// Number of decimal digits that can be dropped from an integer, indexed by how many more bits it
+/// has than the precision of its type (FloatingDecimal.insignificantDigitsNumber)
+const INSIGNIFICANT_DIGITS: [u32; 64] = [
+    0, 0, 0, 0, 1, 1, 1, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 5, 5, 5, 6, 6, 6, 6, 7, 7, 7, 8, 8, 8, 9, 9,
+    9, 9, 10, 10, 10, 11, 11, 11, 12, 12, 12, 12, 13, 13, 13, 14, 14, 14, 15, 15, 15, 15, 16, 16,
+    16, 17, 17, 17, 18, 18, 18, 19
+];
+
+/// Bit length of 5^i (FloatingDecimal.N_5_BITS)
+const N_5_BITS: [i32; 27] = [
+    0, 3, 5, 7, 10, 12, 14, 17, 19, 21, 24, 26, 28, 31, 33, 35, 38, 40, 42, 45, 47, 49, 52, 54, 56,
+    59, 61
+];
+
+fn five_bits(power: i32) -> i32 {
+    N_5_BITS.get(power as usize).copied().unwrap_or(power * 3)
+}
+
+/// The digits and the exponent of the decimal representing the value fract_bits * 2^(bin_exp -
+/// EXP_SHIFT), the value being d.ddd * 10^exponent. The highest bit of fract_bits must be at
+/// EXP_SHIFT, significant_bits is the precision of the value.
+fn dtoa(bin_exp: i32, fract_bits: u64, significant_bits: i32) -> (Vec<u8>, i32) {
+    let tail_zeros = fract_bits.trailing_zeros() as i32;
+    // Number of bits from the highest set one to the lowest
+    let fract_len = EXP_SHIFT as i32 + 1 - tail_zeros;
+    // Number of bits to the right of the binary point
+    let tiny_bits = max(0, fract_len - bin_exp - 1);
+
+    if (-21..=62).contains(&bin_exp) && tiny_bits == 0 {
+        // An integer that fits in a long
+        let insignificant = match bin_exp - significant_bits - 1 {
+            excess @ 2..=63 => INSIGNIFICANT_DIGITS[excess as usize],
+            _ => 0
+        };
+        let long = if bin_exp >= EXP_SHIFT as i32 {
+            fract_bits << (bin_exp - EXP_SHIFT as i32)
+        } else {
+            fract_bits >> (EXP_SHIFT as i32 - bin_exp)
+        };
+        return long_digits(long, insignificant);
+    }
+
+    // The value is B / S, and M is half the distance to the neighbouring values, all of them
+    // kept as 5^x * 2^y
+    let mut dec_exp = estimate_dec_exp(fract_bits, bin_exp);
+    let b5 = max(0, -dec_exp);
+    let mut b2 = b5 + tiny_bits + bin_exp;
+    let s5 = max(0, dec_exp);
+    let mut s2 = s5 + tiny_bits;
+    let m5 = b5;
+    let mut m2 = b2 - significant_bits;
+
+    let fract = fract_bits >> tail_zeros;
+    b2 -= fract_len - 1;
+    let common2 = min(b2, s2);
+    b2 -= common2;
+    s2 -= common2;
+    m2 -= common2;
+    // The neighbouring value below a power of two is closer
+    if fract_len == 1 {
+        m2 -= 1;
+    }
+    if m2 < 0 {
+        b2 -= m2;
+        s2 -= m2;
+        m2 = 0;
+    }
+
+    let b_bits = fract_len + b2 + five_bits(b5);
+    let ten_s_bits = s2 + 1 + five_bits(s5 + 1);
+    let (mut digits, low, high, low_digit_difference) = if b_bits < 64 && ten_s_bits < 64 {
+        // Java uses ints or longs, whose overflows have to be reproduced
+        let bits = if b_bits < 32 && ten_s_bits < 32 { 32 } else { 64 };
+        small_digits(fract, [b5, b2, s5, s2, m5, m2], &mut dec_exp, bits)
+    } else {
+        big_digits(fract, [b5, b2, s5, s2, m5, m2], &mut dec_exp)
+    };
+
+    let mut exponent = dec_exp;
+    // Round up if the remainder is closer to the next decimal
+    if high && (!low || low_digit_difference > 0
+        || low_digit_difference == 0 && digits.last().unwrap() % 2 == 1) {
+        match digits.iter().rposition(|&digit| digit != b'9') {
+            Some(i) => {
+                digits[i] += 1;
+                digits[i + 1..].fill(b'0');
+            }
+            None => {
+                // Carried into a new digit, the rest of the digits stay as zeros
+                exponent += 1;
+                digits.fill(b'0');
+                digits[0] = b'1';
+            }
+        }
+    }
+
+    (digits, exponent)
+}
+
+/// Digits of a long, rounding off the given number of insignificant digits
+fn long_digits(mut long: u64, insignificant: u32) -> (Vec<u8>, i32) {
+    let mut exponent = 0;
+    if insignificant != 0 {
+        let pow10 = 10u64.pow(insignificant);
+        let residue = long % pow10;
+        long /= pow10;
+        exponent += insignificant as i32;
+        if residue >= pow10 / 2 {
+            long += 1;
+        }
+    }
+
+    let digits = long.to_string();
+    exponent += digits.len() as i32 - 1;
+    (digits.trim_end_matches('0').as_bytes().to_vec(), exponent)
+}
+
+/// Estimates floor(log10(fract_bits * 2^(bin_exp - EXP_SHIFT))), which may be one too large
+// The constants are Java's, log10(2) among them is not exactly LOG10_2
+#[allow(clippy::approx_constant)]
+fn estimate_dec_exp(fract_bits: u64, bin_exp: i32) -> i32 {
+    let d2 = f64::from_bits(1f64.to_bits() | (fract_bits & ((1 << EXP_SHIFT) - 1)));
+    let d = (d2 - 1.5) * 0.289529654 + 0.176091259 + bin_exp as f64 * 0.301029995663981;
+    d.floor() as i32
+}
+
+/// Develops the digits of B / S until they identify the value, using integers of the given
+/// number of bits. Returns whether the remainder is within M of zero (low) and of the next digit
+/// (high), and how the remainder compares to half a digit.
+fn small_digits(fract: u64, [b5, b2, s5, s2, m5, m2]: [i32; 6], dec_exp: &mut i32, bits: u32)
+    -> (Vec<u8>, bool, bool, i128) {
+    let wrap = |value: i128| if bits == 32 { value as i32 as i128 } else { value as i64 as i128 };
+
+    let mut b = wrap((fract as i128 * 5i128.pow(b5 as u32)) << b2);
+    let s = 5i128.pow(s5 as u32) << s2;
+    let mut m = wrap(5i128.pow(m5 as u32) << m2);
+    let tens = s * 10;
+
+    let mut digits = Vec::with_capacity(20);
+    let q = b / s;
+    b = 10 * (b % s);
+    m = wrap(m * 10);
+    let mut low = b < m;
+    let mut high = wrap(b + m) > tens;
+    if q == 0 && !high {
+        // The estimated exponent was one too large
+        *dec_exp -= 1;
+    } else {
+        digits.push(b'0' + q as u8);
+    }
+    if *dec_exp < -3 || *dec_exp >= 8 {
+        // At least two digits in scientific notation
+        low = false;
+        high = false;
+    }
+    while !low && !high {
+        let q = b / s;
+        b = 10 * (b % s);
+        m = wrap(m * 10);
+        if m > 0 {
+            low = b < m;
+            high = wrap(b + m) > tens;
+        } else {
+            low = true;
+            high = true;
+        }
+        digits.push(b'0' + q as u8);
+    }
+
+    (digits, low, high, wrap((b << 1) - tens))
+}
+
+/// Like small_digits, for values that need arbitrary precision
+fn big_digits(fract: u64, [b5, b2, s5, s2, m5, m2]: [i32; 6], dec_exp: &mut i32)
+    -> (Vec<u8>, bool, bool, i128) {
+    let s = Natural::pow52(s5, s2);
+    let mut b = Natural::from(fract).mul_pow52(b5, b2);
+    let mut m = Natural::pow52(m5 + 1, m2 + 1);
+    let ten_s = Natural::pow52(s5 + 1, s2 + 1);
+
+    let mut digits = Vec::with_capacity(20);
+    let q = b.quo_rem_iteration(&s);
+    let mut low = b < m;
+    let mut high = b.add(&m) >= ten_s;
+    if q == 0 && !high {
+        *dec_exp -= 1;
+    } else {
+        digits.push(b'0' + q);
+    }
+    if *dec_exp < -3 || *dec_exp >= 8 {
+        low = false;
+        high = false;
+    }
+    while !low && !high {
+        let q = b.quo_rem_iteration(&s);
+        m = m.mul_pow52(1, 1);
+        low = b < m;
+        high = b.add(&m) >= ten_s;
+        digits.push(b'0' + q);
+    }
+
+    let low_digit_difference = if high && low {
+        match b.mul_pow52(0, 1).cmp(&ten_s) {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1
+        }
+    } else {
+        0
+    };
+    (digits, low, high, low_digit_difference)
+}
+
+/// Unsigned integer of arbitrary size for the cases FloatingDecimal uses FDBigInteger for, with
+/// little-endian 32-bit words and no leading zero words
+#[derive(PartialEq, Eq)]
+struct Natural(Vec<u32>);
+
+impl From<u64> for Natural {
+    fn from(value: u64) -> Self {
+        let mut natural = Natural(vec![value as u32, (value >> 32) as u32]);
+        natural.trim();
+        natural
+    }
+}
+
+impl Natural {
+    fn pow52(p5: i32, p2: i32) -> Natural {
+        Natural::from(1).mul_pow52(p5, p2)
+    }
+
+    /// Multiplies by 5^p5 * 2^p2
+    fn mul_pow52(mut self, p5: i32, p2: i32) -> Natural {
+        for _ in 0..p5 {
+            let mut carry = 0;
+            for word in &mut self.0 {
+                let product = *word as u64 * 5 + carry;
+                *word = product as u32;
+                carry = product >> 32;
+            }
+            if carry != 0 {
+                self.0.push(carry as u32);
+            }
+        }
+
+        let mut shifted = vec![0; p2 as usize / 32];
+        let bits = p2 as u32 % 32;
+        let mut carry = 0;
+        for &word in &self.0 {
+            shifted.push(word << bits | carry);
+            carry = if bits == 0 { 0 } else { word >> (32 - bits) };
+        }
+        shifted.push(carry);
+        let mut natural = Natural(shifted);
+        natural.trim();
+        natural
+    }
+
+    fn add(&self, other: &Natural) -> Natural {
+        let mut sum = Vec::with_capacity(max(self.0.len(), other.0.len()) + 1);
+        let mut carry = 0;
+        for i in 0..max(self.0.len(), other.0.len()) {
+            let word = *self.0.get(i).unwrap_or(&0) as u64 + *other.0.get(i).unwrap_or(&0) as u64
+                + carry;
+            sum.push(word as u32);
+            carry = word >> 32;
+        }
+        sum.push(carry as u32);
+        let mut natural = Natural(sum);
+        natural.trim();
+        natural
+    }
+
+    /// Subtracts a value that is not larger
+    fn sub_assign(&mut self, other: &Natural) {
+        let mut borrow = 0;
+        for i in 0..self.0.len() {
+            let difference = self.0[i] as i64 - *other.0.get(i).unwrap_or(&0) as i64 - borrow;
+            self.0[i] = difference as u32;
+            borrow = (difference < 0) as i64;
+        }
+        self.trim();
+    }
+
+    /// Returns the quotient of dividing by s, which must be below 10, and replaces the value by
+    /// ten times the remainder
+    fn quo_rem_iteration(&mut self, s: &Natural) -> u8 {
+        let mut q = 0;
+        while *self >= *s {
+            self.sub_assign(s);
+            q += 1;
+        }
+        *self = std::mem::replace(self, Natural(vec![])).mul_pow52(1, 1);
+        q
+    }
+
+    fn trim(&mut self) {
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+    }
+}
+
+impl PartialOrd for Natural {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Natural {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.len().cmp(&other.0.len())
+            .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+}
+
+fn format_decimal(negative: bool, digits: &[u8], exponent: i32) -> String {
+    let digits = std::str::from_utf8(digits).unwrap();
+    let mut string = String::with_capacity(digits.len() + 8);
+    if negative {
+        string.push('-');
+    }
+
+    if (-3..7).contains(&exponent) {
+        if exponent < 0 {
+            string.push_str("0.");
+            string.extend(std::iter::repeat_n('0', (-exponent - 1) as usize));
+            string.push_str(digits);
+        } else {
+            let integer_digits = exponent as usize + 1;
+            if digits.len() > integer_digits {
+                string.push_str(&digits[..integer_digits]);
+                string.push('.');
+                string.push_str(&digits[integer_digits..]);
+            } else {
+                string.push_str(digits);
+                string.extend(std::iter::repeat_n('0', integer_digits - digits.len()));
+                string.push_str(".0");
+            }
+        }
+    } else {
+        string.push_str(&digits[..1]);
+        string.push('.');
+        string.push_str(if digits.len() > 1 { &digits[1..] } else { "0" });
+        string.push('E');
+        string.push_str(&exponent.to_string());
+    }
+
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helper::number::{double_to_string, float_to_string};
+
+    #[test]
+    fn plain_notation() {
+        assert_eq!(double_to_string(1.0), "1.0");
+        assert_eq!(double_to_string(-12.5), "-12.5");
+        assert_eq!(double_to_string(0.1), "0.1");
+        assert_eq!(double_to_string(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(double_to_string(100.0), "100.0");
+        assert_eq!(double_to_string(0.001), "0.001");
+        assert_eq!(double_to_string(9999999.0), "9999999.0");
+        assert_eq!(double_to_string(1.0 / 3.0), "0.3333333333333333");
+        assert_eq!(double_to_string(-0.0), "-0.0");
+    }
+
+    #[test]
+    fn scientific_notation() {
+        assert_eq!(double_to_string(1.0e7), "1.0E7");
+        assert_eq!(double_to_string(1.0e-4), "1.0E-4");
+        assert_eq!(double_to_string(-1.25e-5), "-1.25E-5");
+        assert_eq!(double_to_string(6.02214076e23), "6.02214076E23");
+        assert_eq!(double_to_string(f64::MAX), "1.7976931348623157E308");
+        assert_eq!(double_to_string(2f64.powi(60)), "1.15292150460684698E18");
+        assert_eq!(double_to_string(f64::MIN_POSITIVE), "2.2250738585072014E-308");
+        assert_eq!(double_to_string(f64::from_bits(1)), "4.9E-324");
+        assert_eq!(double_to_string(1.0e-322), "1.0E-322");
+        assert_eq!(double_to_string(1.0e-323), "1.0E-323");
+        assert_eq!(double_to_string(f64::NEG_INFINITY), "-Infinity");
+        assert_eq!(double_to_string(f64::NAN), "NaN");
+    }
+
+    #[test]
+    fn not_shortest() {
+        assert_eq!(double_to_string(1.0e23), "9.999999999999999E22");
+        assert_eq!(double_to_string(2f64.powi(-96)), "1.2621774483536189E-29");
+        assert_eq!(double_to_string(-2.1235808073258287e25), "-2.1235808073258286E25");
+        // Java decides on rounding up the last digit with an int that overflows
+        assert_eq!(float_to_string(f32::from_bits(1030017045)), "0.055862505");
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(float_to_string(0.1), "0.1");
+        assert_eq!(float_to_string(1.0 / 3.0), "0.33333334");
+        assert_eq!(float_to_string(1.0e10), "1.0E10");
+        assert_eq!(float_to_string(4.7939306e8), "4.79393056E8");
+        assert_eq!(float_to_string(16777216.0), "1.6777216E7");
+        // Halfway between two shortest decimals, the one with the even last digit wins
+        assert_eq!(float_to_string(8341217.0 / 4.0), "2085304.2");
+        assert_eq!(float_to_string(-2088789.0 / 8.0), "-261098.62");
+        assert_eq!(float_to_string(5563835.0 / 4.0), "1390958.8");
+        assert_eq!(float_to_string(f32::MAX), "3.4028235E38");
+        assert_eq!(float_to_string(f32::from_bits(1)), "1.4E-45");
+        assert_eq!(float_to_string(f32::INFINITY), "Infinity");
+    }
+}
+
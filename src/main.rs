@@ -1,3 +1,5 @@
+#![allow(clippy::module_inception, clippy::upper_case_acronyms, clippy::enum_variant_names)]
+
 use std::fs::File;
 use std::io::Write;
 use std::sync::atomic::Ordering;
@@ -27,12 +29,9 @@ fn main() {
     let now = Instant::now();
     let vm = VM_HANDLER.get_or_init(VM::init);
 
-    let stat_thread_handle;
-    if cfg!(feature = "statistics") {
+    let stat_thread_handle = if cfg!(feature = "statistics") {
         // Start statistic thread
-        stat_thread_handle = Some(std::thread::spawn(|| {
-            use num_enum::FromPrimitive;
-
+        Some(std::thread::spawn(|| {
             let vm = VM_HANDLER.get().unwrap();
 
             let mut arr = [0; 256];
@@ -55,28 +54,29 @@ fn main() {
 
             let mut buf = Vec::with_capacity(64);
 
-            for i in 1..256 {
-                if arr[i] == 0 {
+            for (i, count) in arr.iter().enumerate().skip(1) {
+                if *count == 0 {
                     continue;
                 }
 
                 let b = vm.instr_map[i].load(Ordering::Relaxed) as f64;
-                let _ = write!(&mut buf, "{:?}, {}\n",
+                let _ = writeln!(&mut buf, "{:?}, {}",
                                unsafe { Instruction::from_unchecked(i as u8) },
-                               (arr[i] as f64) / b);
+                               (*count as f64) / b);
                 let _ = file.write(&buf);
                 buf.truncate(0);
             }
-        }));
+        }))
     } else {
-        stat_thread_handle = None;
-    }
+        None
+    };
 
     let handle = std::thread::spawn(move || {
         start_main_class();
     });
 
     let _ = handle.join();
+    vm.threads.wait_for_non_daemon();
     vm.last_instruction.store(Instruction::impdep1 as u8, Ordering::Release);
     match stat_thread_handle {
         None => {},
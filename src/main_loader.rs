@@ -1,13 +1,16 @@
 use smallvec::smallvec;
 use crate::{Class, ClassRef, FieldType, initialize_class, ThreadStatus, VM_HANDLER, VMThread};
 use crate::vm::object::ObjectPtr;
+use crate::vm::thread::thread::{allocate, preallocate_out_of_memory_error};
 
 pub fn start_main_class() {
     let vm = VM_HANDLER.get().unwrap();
 
     let arg = vm.args.read().unwrap();
 
-    let main_class_name = vm.string_pool.intern_string(arg.main_class.as_str());
+    let main_class_name = vm.string_pool.intern_string(
+        None, arg.main_class.as_deref().expect("The main class is known after VM initialization"))
+        .unwrap_or_else(|e| exit_with_error(&e));
 
     let class_loader = vm.classloader;
     let mut loader_thread = VMThread::new();
@@ -16,6 +19,7 @@ pub fn start_main_class() {
         ThreadStatus::FINISHED(Some(res)) => {
             let main_class = ClassRef::new(res as *const Class);
 
+            preallocate_out_of_memory_error();
             init_main_class(main_class);
 
             let main_method = main_class.data.methods.iter().enumerate().find(|(_i, m)| {
@@ -26,33 +30,55 @@ pub fn start_main_class() {
             });
 
             let main_method = main_method.unwrap_or_else(|| panic!("No main method found"));
-            let java_args: Vec<ObjectPtr> = arg.java_args.iter()
-                .map(|s| vm.string_pool.add_string(s))
-                .collect();
-
-            let class = vm.load_class("[java/lang/String").unwrap();
-            let array = vm.object_arena.new_array(class, java_args.len());
-            for (i, ptr) in java_args.iter().enumerate() {
-                array.store_to_array(i, ptr.to_val());
-            }
+            let array = main_args(&arg.java_args).unwrap_or_else(|e| exit_with_error(&e));
 
             let mut main_thread = VMThread::new();
             main_thread.start((main_class, main_method.0), smallvec![array.to_val()]);
-            match main_thread.status {
-                ThreadStatus::FAILED(err) => println!("{}", err),
-                _ => {}
+            if let ThreadStatus::FAILED(err) = main_thread.status {
+                vm.threads.report_uncaught(&err);
             }
+
+            vm.threads.exit_current();
+        }
+        ThreadStatus::FAILED(err) => {
+            eprintln!("Error: Could not find or load main class {}",
+                      arg.main_class.as_deref().unwrap_or_default());
+            eprintln!("Caused by: {}", err);
+            std::process::exit(1);
         }
-        ThreadStatus::FAILED(err) => panic!("Could not load main class: {}", err),
         _ => panic!("Can't happen")
     }
 }
 
 fn init_main_class(class: ClassRef) {
-    match initialize_class(class) {
+    match initialize_class(class, None) {
         Ok(_) => {}
         Err(exc) => {
-            panic!("Exception occurred while initializing main class: {}", exc)
+            eprintln!("Error: Unable to initialize main class {}", class.data.name.replace('/', "."));
+            eprintln!("Caused by: {}", exc);
+            std::process::exit(1);
         }
     }
-}
\ No newline at end of file
+}
+
+/// Creates the String[] passed to the main method
+fn main_args(args: &[String]) -> Result<ObjectPtr, String> {
+    let vm = VM_HANDLER.get().unwrap();
+
+    let java_args = args.iter()
+        .map(|s| vm.string_pool.add_string(None, s))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let class = vm.load_class("[java/lang/String")?;
+    let array = allocate(None, |arena| arena.try_new_array(class, java_args.len()))?;
+    for (i, ptr) in java_args.iter().enumerate() {
+        array.store_to_array(i, ptr.to_val());
+    }
+
+    Ok(array)
+}
+
+fn exit_with_error(error: &str) -> ! {
+    eprintln!("Error: {}", error);
+    std::process::exit(1);
+}
@@ -2,12 +2,14 @@ use std::cell::UnsafeCell;
 use std::fmt::{Debug, Formatter};
 use num_enum::{FromPrimitive};
 use std::ops::Deref;
+use std::sync::{Condvar, Mutex};
 use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::thread::ThreadId;
 use smallvec::SmallVec;
 use crate::class_parser::constants::AccessFlagClass;
 use crate::helper::has_flag;
-use crate::vm::class::constant_pool::CPEntry;
-use crate::vm::class::field::Field;
+use crate::vm::class::constant_pool::{BootstrapMethod, CPEntry};
+use crate::vm::class::field::{Field, FieldType};
 use crate::vm::class::method::{Method, MethodDescriptor};
 use crate::vm::object::{ObjectHeader, ObjectPtr};
 use crate::vm::thread::thread::MethodRef;
@@ -21,6 +23,8 @@ pub enum ClassState {
     Initializing = 2,
     #[default]
     Ready = 3,
+    /// Initialization failed, the class can not be used
+    Erroneous = 4,
 }
 
 pub struct AtomicClassState {
@@ -37,17 +41,12 @@ impl AtomicClassState {
     pub fn get(&self) -> ClassState {
         let state = self.state.load(Ordering::Acquire);
 
-        ClassState::from_primitive(state as u8)
+        ClassState::from_primitive(state)
     }
 
     pub fn set(&self, state: ClassState) {
         self.state.store(state as u8, Ordering::Release);
     }
-
-    pub fn set_from(&self, from: ClassState, to: ClassState) -> Result<(), ()> {
-        self.state.compare_exchange(from as u8, to as u8, Ordering::Release, Ordering::Relaxed)
-            .map(|_| ()).map_err(|_| ())
-    }
 }
 
 /// Runtime representation of a class in the method area, which is simultaneously has a correct
@@ -56,9 +55,21 @@ impl AtomicClassState {
 pub struct Class {
     pub header: ObjectHeader,
     pub state: AtomicClassState,
+    /// Serializes constant pool updates, separately from the monitor visible to Java code
+    pub cp_lock: Mutex<()>,
+    pub init_lock: InitLock,
     pub data: ClassRepr,
 }
 
+/// Lock of the class initialization procedure (JVMS §5.5)
+#[derive(Default)]
+pub struct InitLock {
+    /// The thread running the initialization while the class is `Initializing`
+    pub initializer: Mutex<Option<ThreadId>>,
+    /// Notified when an initialization completes
+    pub completed: Condvar
+}
+
 impl Debug for Class {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "[{:?}]{:?}", self.state.get(), self.data)
@@ -70,12 +81,37 @@ impl Class {
         has_flag(self.data.flag, AccessFlagClass::ACC_INTERFACE)
     }
 
+    /// Whether a value of this class can be assigned to a variable of type `other`, considering
+    /// superclasses, implemented interfaces and array covariance
     pub fn is_subclass(&self, other: ClassRef) -> bool {
         let vm = VM_HANDLER.get().unwrap();
         if ClassRef::new(self) == other || other == vm.object_class {
             return true;
         }
 
+        if self.is_array() {
+            if other.data.name == "java/lang/Cloneable" || other.data.name == "java/io/Serializable" {
+                return true;
+            }
+            if !other.is_array() {
+                return false;
+            }
+
+            let component = &self.data.name[1..];
+            let other_component = &other.data.name[1..];
+            // Primitive components have no class of their own, they have to match exactly
+            // Array classes are defined by the loader of their component
+            return match (vm.find_loaded_class(self.data.class_loader, component),
+                          vm.find_loaded_class(other.data.class_loader, other_component)) {
+                (Some(component), Some(other_component)) => component.is_subclass(other_component),
+                _ => component == other_component
+            };
+        }
+
+        if self.data.interfaces.iter().any(|i| i.is_subclass(other)) {
+            return true;
+        }
+
         if !self.data.superclass.0.is_null() {
             return self.data.superclass.is_subclass(other);
         }
@@ -88,15 +124,43 @@ impl Class {
             && &m.descriptor == descriptor).map(|(i, _)| (ClassRef::new(self), i))
     }
 
+    /// The field denoted by a resolved field reference to this class
+    pub fn resolved_field(&self, instance: bool, index: usize) -> &Field {
+        if !instance {
+            return self.data.fields.iter().filter(|f| f.is_static()).nth(index).unwrap();
+        }
+
+        let inherited = if self.data.superclass.ptr().is_null() { 0 } else {
+            self.data.superclass.data.instance_field_count
+        };
+        if index < inherited {
+            return self.data.superclass.resolved_field(true, index);
+        }
+
+        self.data.fields.iter().filter(|f| !f.is_static()).nth(index - inherited).unwrap()
+    }
+
     pub fn is_array(&self) -> bool {
         self.data.name.starts_with('[')
     }
 
+    /// Size in bytes of the elements of an array class. Array classes of references are named
+    /// after their component class, primitive ones after the descriptor of their component type.
+    pub fn element_size(&self) -> usize {
+        debug_assert!(self.is_array());
+
+        match &self.data.name[1..] {
+            component if component.len() == 1 => FieldType::parse(component)
+                .map_or(8, |t| t.size()),
+            _ => 8
+        }
+    }
+
     pub fn get_package(&self) -> (ObjectPtr, String) {
         let rightmost_slash = self.data.name.rfind('/');
         match rightmost_slash {
-            None => (ObjectPtr::null(), "".to_string()),
-            Some(i) => (ObjectPtr::null(), String::from(&self.data.name[0..i]))
+            None => (self.data.class_loader, "".to_string()),
+            Some(i) => (self.data.class_loader, String::from(&self.data.name[0..i]))
         }
     }
 }
@@ -110,7 +174,7 @@ impl Class {
     }
 
     pub fn set_cp_entry(&self, index: usize, value: CPEntry) {
-        let _ = self.header.lock.lock().unwrap();
+        let _guard = self.cp_lock.lock().unwrap();
 
         unsafe { *self.data.constant_pool[index - 1].entry.get() = value; }
     }
@@ -133,19 +197,26 @@ impl CPEntryWrapper {
 pub struct ClassRepr {
     pub name: String,
     pub flag: u16,
-    // TODO: class_loader: ObjectRef,
+    /// Defining loader of the class, null for the bootstrap class loader
+    pub class_loader: ObjectPtr,
     pub superclass: ClassRef,
     pub interfaces: SmallVec<[ClassRef; 32]>,
     pub constant_pool: Vec<CPEntryWrapper>,
     pub fields: Vec<Field>,
     pub methods: Vec<Method>,
+    pub bootstrap_methods: Vec<BootstrapMethod>,
+    pub source_file: Option<String>,
+    /// Host of the nest the class claims to belong to (NestHost attribute)
+    pub nest_host: Option<String>,
+    /// Classes claimed to belong to the nest hosted by this class (NestMembers attribute)
+    pub nest_members: Vec<String>,
     // TODO: attributes
     pub static_fields: SmallVec<[AtomicU64; 32]>,
     pub instance_field_count: usize // Cumulative size of all instance fields in the hierarchy
 }
 
 /// Concrete type used as "pointer" to a Class instance
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(transparent)]
 pub struct ClassRef(*const Class);
 
@@ -190,4 +261,19 @@ mod tests {
 
         assert!(string.is_subclass(object));
     }
+
+    #[test]
+    fn test_array_subclass() {
+        let _vm = VM_HANDLER.get_or_init(| | VM::vm_init(false));
+
+        let strings = _vm.load_class("[Ljava/lang/String;").unwrap();
+        let objects = _vm.load_class("[java/lang/Object").unwrap();
+        let ints = _vm.load_class("[I").unwrap();
+
+        assert_eq!("[java/lang/String", strings.data.name);
+        assert!(strings.is_subclass(objects));
+        assert!(!objects.is_subclass(strings));
+        assert!(!ints.is_subclass(objects));
+        assert!(ints.is_subclass(_vm.object_class));
+    }
 }
\ No newline at end of file
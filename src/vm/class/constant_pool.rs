@@ -18,13 +18,43 @@ pub enum UnresolvedReference {
     ClassReference(String),
     MethodReference(u16, String, MethodDescriptor), // class reference index, method name, method descriptor
     FieldReference(u16, String, FieldType),
-    InterfaceMethodReference(u16, String, MethodDescriptor)
+    InterfaceMethodReference(u16, String, MethodDescriptor),
+    MethodHandleReference(u8, u16), // reference kind, index of the referenced member
+    MethodTypeReference(MethodDescriptor),
+    InvokeDynamicReference(u16, String, MethodDescriptor), // bootstrap method index, name,
+                                                           // descriptor
+    DynamicReference(u16, String, FieldType)
 }
 
 #[derive(Debug, Clone)]
 pub enum SymbolicReference {
     ClassReference(ClassRef),
     MethodReference(ClassRef, usize),
-    FieldReference(ClassRef, bool, usize) // Class containing field, whether it is an instance
-                                          // field, and index
+    FieldReference(ClassRef, bool, usize), // Class containing field, whether it is an instance
+                                           // field, and index
+    CallSite(CallSite)
+}
+
+/// Target of a linked invokedynamic instruction. Only the bootstrap methods of the JDK used by
+/// javac are supported, and they are implemented by the VM instead of running them.
+#[derive(Debug, Clone)]
+pub enum CallSite {
+    /// Concatenates the arguments of the given types into a new String
+    StringConcat(Vec<ConcatElement>, Vec<FieldType>),
+    /// Creates an instance of the lambda class, the arguments being its captured values
+    Lambda(ClassRef)
+}
+
+#[derive(Debug, Clone)]
+pub enum ConcatElement {
+    /// UTF-16 code units copied into the result
+    Constant(Vec<u16>),
+    Argument(usize)
+}
+
+/// Entry of the BootstrapMethods attribute
+#[derive(Debug, Clone)]
+pub struct BootstrapMethod {
+    pub method_handle: u16,
+    pub arguments: Vec<u16>
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+use std::fmt::{Display, Formatter};
 use crate::class_parser::constants::AccessFlagField;
 use crate::helper::has_flag;
 
@@ -30,13 +31,50 @@ impl FieldType {
             _ => panic!()
         }
     }
+
+    /// Size in bytes of a value of this type as an array element
+    pub fn size(&self) -> usize {
+        match self {
+            FieldType::B | FieldType::Z => 1,
+            FieldType::C | FieldType::S => 2,
+            FieldType::I | FieldType::F => 4,
+            FieldType::J | FieldType::D | FieldType::L(_) | FieldType::A(_) => 8,
+            FieldType::V => panic!("void has no size")
+        }
+    }
+
+    /// Whether values of this type are references to objects or arrays
+    pub fn is_reference(&self) -> bool {
+        matches!(self, FieldType::L(_) | FieldType::A(_))
+    }
+}
+
+/// Formats the type the way it is written in Java source, e.g. int[] or java.lang.String
+impl Display for FieldType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldType::B => write!(f, "byte"),
+            FieldType::C => write!(f, "char"),
+            FieldType::D => write!(f, "double"),
+            FieldType::F => write!(f, "float"),
+            FieldType::I => write!(f, "int"),
+            FieldType::J => write!(f, "long"),
+            FieldType::L(name) => write!(f, "{}", name.replace('/', ".")),
+            FieldType::S => write!(f, "short"),
+            FieldType::Z => write!(f, "boolean"),
+            FieldType::A(component) => write!(f, "{}[]", component),
+            FieldType::V => write!(f, "void")
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Field {
     pub flag: u16,
     pub name: String,
-    pub descriptor: FieldType
+    pub descriptor: FieldType,
+    /// Constant pool index of the initial value of a static field (ConstantValue attribute)
+    pub constant_value: Option<usize>
 }
 
 impl Field {
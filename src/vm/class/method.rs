@@ -12,6 +12,16 @@ pub struct MethodDescriptor {
     pub ret: FieldType
 }
 
+impl MethodDescriptor {
+    /// Formats a method the way the JDK does in error messages, e.g.
+    /// 'void java.lang.Object.wait(long)'
+    pub fn describe(&self, class_name: &str, name: &str) -> String {
+        let parameters: Vec<String> = self.parameters.iter().map(|p| p.to_string()).collect();
+        format!("'{} {}.{}({})'", self.ret, class_name.replace('/', "."), name,
+                parameters.join(", "))
+    }
+}
+
 #[derive(Debug)]
 pub struct Method {
     pub flag: u16,
@@ -37,20 +47,48 @@ impl Method {
         has_flag(self.flag, AccessFlagMethod::ACC_PRIVATE)
     }
 
+    pub fn is_abstract(&self) -> bool {
+        has_flag(self.flag, AccessFlagMethod::ACC_ABSTRACT)
+    }
+
+    pub fn is_synchronized(&self) -> bool {
+        has_flag(self.flag, AccessFlagMethod::ACC_SYNCHRONIZED)
+    }
+
+    #[allow(dead_code)]
     pub fn is_instance_init(&self, defining_class: ClassRef) -> bool {
         !defining_class.is_interface() && self.name == "<init>" && self.descriptor.ret == FieldType::V
     }
 
+    /// Whether this method, declared in `self_class`, overrides `other_method` declared in its
+    /// superclass `other_class` (JVMS §5.4.5)
     pub fn can_override(&self, self_class: ClassRef, other_method: &Method, other_class: ClassRef)
         -> bool {
-        self.name == other_method.name && self.descriptor == other_method.descriptor
-        && !self.is_private()
-        && (other_method.is_public() || other_method.is_protected() || (
-            !other_method.is_public() && !other_method.is_protected() && !other_method.is_private()
-            && (
-                self_class.get_package() == other_class.get_package() // TODO: transitive access
-                )
-            ))
+        if self.name != other_method.name || self.descriptor != other_method.descriptor
+            || self.is_private() || other_method.is_private() {
+            return false;
+        }
+
+        if other_method.is_public() || other_method.is_protected()
+            || self_class.get_package() == other_class.get_package() {
+            return true;
+        }
+
+        // A package-private method may also be overridden transitively, through a method of an
+        // intermediate class that overrides it
+        let mut class = self_class.data.superclass;
+        while !class.ptr().is_null() && class != other_class {
+            let overrides = class.data.methods.iter().any(|m| !m.is_static()
+                && m.can_override(class, other_method, other_class)
+                && self.can_override(self_class, m, class));
+            if overrides {
+                return true;
+            }
+
+            class = class.data.superclass;
+        }
+
+        false
     }
 }
 
@@ -88,11 +126,49 @@ pub struct ExceptionHandler {
     pub catch_type: Option<ClassRef>
 }
 
+#[derive(Debug)]
+pub struct LineNumber {
+    pub start_pc: usize,
+    pub line_number: u16
+}
+
 #[derive(Debug)]
 pub struct Code {
     pub max_stack: usize,
     pub max_locals: usize,
     pub code: Vec<u8>,
-    pub exception_handlers: Vec<ExceptionHandler>
+    pub exception_handlers: Vec<ExceptionHandler>,
+    pub line_numbers: Vec<LineNumber> // Sorted by start_pc
     // TODO: attributes
-}
\ No newline at end of file
+}
+
+impl Code {
+    /// Source line of the instruction at `pc`, if the class file has line number information
+    pub fn line_number(&self, pc: usize) -> Option<u16> {
+        let i = self.line_numbers.partition_point(|l| l.start_pc <= pc);
+        i.checked_sub(1).map(|i| self.line_numbers[i].line_number)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::vm::class::method::{Code, LineNumber};
+
+    #[test]
+    fn maps_pc_to_line_number() {
+        let code = Code {
+            max_stack: 0,
+            max_locals: 0,
+            code: vec![0; 10],
+            exception_handlers: vec![],
+            line_numbers: vec![
+                LineNumber { start_pc: 2, line_number: 7 },
+                LineNumber { start_pc: 5, line_number: 9 }
+            ]
+        };
+
+        assert_eq!(code.line_number(0), None);
+        assert_eq!(code.line_number(2), Some(7));
+        assert_eq!(code.line_number(4), Some(7));
+        assert_eq!(code.line_number(9), Some(9));
+    }
+}
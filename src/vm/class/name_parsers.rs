@@ -26,7 +26,7 @@ impl MethodDescriptor {
                     (Some(FieldType::L(str[1..end].to_string())),
                      &str[end+1..])
                 } else {
-                    (None, &str)
+                    (None, str)
                 }
             },
             "S" => (Some(FieldType::S), &str[1..]),
@@ -35,15 +35,15 @@ impl MethodDescriptor {
                 match Self::parse_field_type(&str[1..], true) {
                     (Some(component), rest) => (Some(FieldType::A(Box::new(component)))
                                                 , rest),
-                    _ => (None, &str)
+                    _ => (None, str)
                 }
             },
             "V" =>  if is_parameter {
-                        (None, &str)
+                        (None, str)
                     } else {
                         (Some(FieldType::V), &str[1..])
                     }
-            _ => (None, &str)
+            _ => (None, str)
         }
     }
 
@@ -75,7 +75,7 @@ impl MethodDescriptor {
         let res = Self::parse_field_type(str, false);
 
         match res {
-            (Some(ret), rest) if rest.is_empty() => Some(MethodDescriptor {
+            (Some(ret), "") => Some(MethodDescriptor {
                 parameters,
                 ret,
             }),
@@ -3,6 +3,7 @@ use crate::{Class, ClassRef, ClassRepr, ObjectHeader};
 use crate::vm::class::class::AtomicClassState;
 use crate::vm::class::class::ClassState::Ready;
 use crate::vm::class::field::FieldType;
+use crate::vm::object::ObjectPtr;
 
 pub fn create_primitive_array_class(component: FieldType) -> Option<Class> {
     match component {
@@ -13,14 +14,21 @@ pub fn create_primitive_array_class(component: FieldType) -> Option<Class> {
     Some(Class {
         header: ObjectHeader::default(),
         state: AtomicClassState::new(Ready),
+        cp_lock: Default::default(),
+        init_lock: Default::default(),
         data: ClassRepr {
             name: format!("[{:?}", component),
             flag: 0,
+            class_loader: ObjectPtr::null(),
             superclass: ClassRef::new(null()),
             interfaces: Default::default(),
             constant_pool: vec![],
             fields: vec![],
             methods: vec![],
+            bootstrap_methods: vec![],
+            source_file: None,
+            nest_host: None,
+            nest_members: vec![],
             static_fields: Default::default(),
             instance_field_count: 0
         }
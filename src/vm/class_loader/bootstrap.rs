@@ -1,35 +1,31 @@
-use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
 use std::ptr::{null};
 use std::sync::atomic::AtomicU64;
 use smallvec::{smallvec, SmallVec};
-use crate::{Class, ClassRepr, get_cp_info, Instruction, Method, ObjectHeader, VM, VM_HANDLER, VMThread};
-use crate::class_parser::constants::{AccessFlagMethod, CPInfo};
+use crate::{Class, ClassRepr, get_cp_info, Method, ObjectHeader, VM, VM_HANDLER, VMThread};
+use crate::class_parser::constants::{AccessFlagClass, AccessFlagField, AccessFlagMethod, CPInfo};
+use crate::class_parser::attributes::Attribute;
 use crate::class_parser::parse_class;
-use crate::class_parser::types::ParsedClass;
+use crate::class_parser::types::{ParsedClass, U2};
 use crate::class_parser::constants::CPTag;
 use crate::helper::{ftou2, has_flag};
 use crate::vm::class::class::{AtomicClassState, ClassRef, CPEntryWrapper};
-use crate::vm::class::class::ClassState::{Ready, Verified};
-use crate::vm::class::constant_pool::{CPEntry, UnresolvedReference};
+use crate::vm::class::class::ClassState::{Loaded, Ready, Verified};
+use crate::vm::class::constant_pool::{BootstrapMethod, CPEntry, UnresolvedReference};
 use crate::vm::class::constant_pool::CPEntry::{ConstantString, ConstantValue, UnresolvedSymbolicReference};
 use crate::vm::class::field::{Field, FieldType};
-use crate::vm::class::method::{Code, ExceptionHandler, JvmMethod, MethodDescriptor, MethodRepr, NativeMethod};
+use crate::vm::class::method::{Code, ExceptionHandler, JvmMethod, LineNumber, MethodDescriptor, MethodRepr, NativeFnPtr, NativeMethod};
 use crate::vm::class::method::MethodRepr::Native;
 use crate::vm::class_loader::array::create_primitive_array_class;
-use crate::vm::class_loader::native::{init_native_store, NATIVE_FN_STORE, NativeMethodRef};
-use crate::vm::instructions::instruction_length;
+use crate::vm::class_loader::native::{allocation_result, init_native_store, lang, NATIVE_FN_STORE, NativeMethodRef};
+use crate::vm::class_loader::verifier::verify;
 use crate::vm::object::ObjectPtr;
-use crate::vm::pool::string::StrArena;
+use crate::vm::pool::string::get_string;
 
-use crate::vm::thread::thread::{create_throwable, create_throwable_message, ThreadStatus};
+use crate::vm::thread::thread::{create_throwable_from_error, error_from_throwable, ThreadStatus};
 use crate::vm::thread::thread::ThreadStatus::FINISHED;
 
-const INITIAL_CLASS_BUFFER_SIZE: usize = 1024;
-
 impl VM {
-    fn add_class(&self, class: Class) -> ClassRef {
+    pub fn add_class(&self, class: Class) -> ClassRef {
         let mut classes = self.classes.lock().unwrap();
 
         let pin = Box::pin(class);
@@ -48,16 +44,19 @@ impl VM {
         let object_class_data = Class {
             header: ObjectHeader::default(),
             state: AtomicClassState::new(Ready),
+            cp_lock: Default::default(),
+            init_lock: Default::default(),
             data: ClassRepr {
                 name: object_name.clone(),
-                flag: 0,
+                flag: AccessFlagClass::ACC_PUBLIC as u16,
+                class_loader: ObjectPtr::null(),
                 superclass: ClassRef::new(zero_ptr),
                 interfaces: Default::default(),
                 constant_pool: vec![],
                 fields: vec![],
                 methods: vec![
                     Method {
-                        flag: 0,
+                        flag: AccessFlagMethod::ACC_PUBLIC as u16,
                         name: "<init>".to_string(),
                         descriptor: MethodDescriptor { parameters: vec![], ret: FieldType::V },
                         repr: MethodRepr::Jvm(JvmMethod {
@@ -67,7 +66,8 @@ impl VM {
                                 code: vec![
                                     177
                                 ],
-                                exception_handlers: vec![]
+                                exception_handlers: vec![],
+                                line_numbers: vec![]
                             })
                         })
                     },
@@ -89,7 +89,8 @@ impl VM {
                                     3, // iconst_0
                                     172 // ireturn
                                 ],
-                                exception_handlers: vec![]
+                                exception_handlers: vec![],
+                                line_numbers: vec![]
                             })
                         })
                     },
@@ -99,21 +100,54 @@ impl VM {
                         descriptor: MethodDescriptor { parameters: vec![],
                             ret: FieldType::L("java/lang/String".to_string()) },
                         repr: MethodRepr::Native(NativeMethod {
-                            fn_ptr: |_, args, _| {
+                            fn_ptr: |thread, args, exception| {
                                 let this = ObjectPtr::from_val(args[0]).unwrap();
                                 let class_name = &this.get_class().data.name;
 
                                 let mut buf = Vec::with_capacity(class_name.len() + 16);
                                 let _ = write!(&mut buf, "{}@{}", class_name, args[0]);
 
-                                let ptr = VM_HANDLER.get().unwrap()
-                                    .string_pool.add_string(std::str::from_utf8(&buf).unwrap());
+                                let ptr = VM_HANDLER.get().unwrap().string_pool
+                                    .add_string(Some(thread), std::str::from_utf8(&buf).unwrap());
 
-                                Some(ptr.to_val())
+                                allocation_result(ptr, thread, exception)
                         }
                         })
+                    },
+                    Method {
+                        flag: AccessFlagMethod::ACC_PUBLIC as u16
+                            | AccessFlagMethod::ACC_FINAL as u16,
+                        name: "wait".to_string(),
+                        descriptor: MethodDescriptor { parameters: vec![], ret: FieldType::V },
+                        repr: MethodRepr::Native(NativeMethod { fn_ptr: lang::object::wait })
+                    },
+                    Method {
+                        flag: AccessFlagMethod::ACC_PUBLIC as u16
+                            | AccessFlagMethod::ACC_FINAL as u16,
+                        name: "wait".to_string(),
+                        descriptor: MethodDescriptor { parameters: vec![FieldType::J],
+                            ret: FieldType::V },
+                        repr: MethodRepr::Native(NativeMethod { fn_ptr: lang::object::wait })
+                    },
+                    Method {
+                        flag: AccessFlagMethod::ACC_PUBLIC as u16
+                            | AccessFlagMethod::ACC_FINAL as u16,
+                        name: "notify".to_string(),
+                        descriptor: MethodDescriptor { parameters: vec![], ret: FieldType::V },
+                        repr: MethodRepr::Native(NativeMethod { fn_ptr: lang::object::notify })
+                    },
+                    Method {
+                        flag: AccessFlagMethod::ACC_PUBLIC as u16
+                            | AccessFlagMethod::ACC_FINAL as u16,
+                        name: "notifyAll".to_string(),
+                        descriptor: MethodDescriptor { parameters: vec![], ret: FieldType::V },
+                        repr: MethodRepr::Native(NativeMethod { fn_ptr: lang::object::notifyAll })
                     }
                 ],
+                bootstrap_methods: vec![],
+                source_file: None,
+                nest_host: None,
+                nest_members: vec![],
                 static_fields: Default::default(),
                 instance_field_count: 0
             }
@@ -121,18 +155,19 @@ impl VM {
 
         let object_class = self.add_class(object_class_data);
 
-        {
-            let mut class_list = self.bootstrap_cl_class_list.lock().unwrap();
-            class_list.insert(object_name, object_class);
-        }
+        self.record_loaded_class(ObjectPtr::null(), &object_name, object_class);
 
-        let classloader_name = "java/lang/ClassLoader".to_string();
+        // Entry point of the VM for loading classes, with any initiating loader
+        let classloader_name = "jdk/internal/loader/BootLoader".to_string();
         let classloader_class_data = Class {
             header: ObjectHeader::default(),
             state: AtomicClassState::new(Ready),
+            cp_lock: Default::default(),
+            init_lock: Default::default(),
             data: ClassRepr {
                 name: classloader_name.clone(),
                 flag: 0,
+                class_loader: ObjectPtr::null(),
                 superclass: object_class,
                 interfaces: Default::default(),
                 constant_pool: vec![],
@@ -147,20 +182,22 @@ impl VM {
                                 FieldType::L("java/lang/String".to_string())],
                             ret: FieldType::L("java/lang/Class".to_string()) },
                         repr: MethodRepr::Native(NativeMethod { fn_ptr: |thread, args, exc| {
-                            // first argument is the bootstrap class loader (null), second is a
-                            // String object which denotes the name of the class that should be loaded
+                            // first argument is the initiating class loader (null for the
+                            // bootstrap class loader), second is a String object which denotes the
+                            // name of the class that should be loaded
 
+                            let class_loader = ObjectPtr { ptr: args[0] as *const AtomicU64 };
                             let string = args[1] as *const AtomicU64;
                             let obj = ObjectPtr { ptr: string };
 
                             let vm = VM_HANDLER.get().unwrap();
-                            let res = vm.load_class(StrArena::get_string(obj).as_str());
+                            let res = vm.load_class_with(class_loader,
+                                                         get_string(obj).as_str());
 
                             match res {
                                 Ok(val) => Some(val.ptr() as u64),
                                 Err(e) => {
-                                    *exc = Some(create_throwable_message("java/lang/Exception",
-                                                                         thread, &e));
+                                    *exc = Some(create_throwable_from_error(&e, thread));
                                     None
                                 }
                             }
@@ -168,6 +205,10 @@ impl VM {
                     }
 
                 ],
+                bootstrap_methods: vec![],
+                source_file: None,
+                nest_host: None,
+                nest_members: vec![],
                 static_fields: Default::default(),
                 instance_field_count: 0
             }
@@ -175,18 +216,25 @@ impl VM {
 
         let classloader_class = self.add_class(classloader_class_data);
 
-        {
-            let mut class_list = self.bootstrap_cl_class_list.lock().unwrap();
-            class_list.insert(classloader_name, classloader_class);
-        }
+        self.record_loaded_class(ObjectPtr::null(), &classloader_name, classloader_class);
 
         let string_name = "java/lang/String".to_string();
+        let string_native = |name: &str, parameters: Vec<FieldType>, ret: FieldType,
+                             fn_ptr: NativeFnPtr| Method {
+            flag: AccessFlagMethod::ACC_PUBLIC as u16 | AccessFlagMethod::ACC_NATIVE as u16,
+            name: name.to_string(),
+            descriptor: MethodDescriptor { parameters, ret },
+            repr: Native(NativeMethod { fn_ptr })
+        };
         let string_class_data = Class {
             header: ObjectHeader::default(),
             state: AtomicClassState::new(Ready),
+            cp_lock: Default::default(),
+            init_lock: Default::default(),
             data: ClassRepr {
                 name: string_name.clone(),
-                flag: 0,
+                flag: AccessFlagClass::ACC_PUBLIC as u16 | AccessFlagClass::ACC_FINAL as u16,
+                class_loader: ObjectPtr::null(),
                 superclass: object_class,
                 interfaces: Default::default(),
                 constant_pool: vec![
@@ -194,134 +242,107 @@ impl VM {
                             UnresolvedReference::ClassReference("java/lang/String".to_string())
                         )),
                     CPEntryWrapper::new(&UnresolvedSymbolicReference(
-                            UnresolvedReference::FieldReference(1, "length".to_string(),
-                                                                FieldType::J)
+                            UnresolvedReference::FieldReference(1, "value".to_string(),
+                                                                FieldType::A(Box::new(FieldType::C)))
                         )),
                     CPEntryWrapper::new(&UnresolvedSymbolicReference(
                         UnresolvedReference::ClassReference("java/lang/StringUtil".to_string())
                     )),
                     CPEntryWrapper::new(&UnresolvedSymbolicReference(
-                        UnresolvedReference::MethodReference(3,
-                                                             "stringEquals".to_string(),
-                                                            MethodDescriptor {
-                                                                parameters: vec![
-                                                                    FieldType::L("java/lang/String".to_string()),
-                                                                    FieldType::L("java/lang/Object".to_string()),
-                                                                ],
-                                                                ret: FieldType::Z
-                                                            }
-                        )
+                        UnresolvedReference::MethodReference(3, "valueOf".to_string(),
+                            MethodDescriptor {
+                                parameters: vec![FieldType::L("java/lang/Object".to_string())],
+                                ret: FieldType::L("java/lang/String".to_string())
+                            })
                     )),
                 ],
                 fields: vec![
                     Field {
-                        flag: 0,
-                        name: "length".to_string(),
-                        descriptor: FieldType::J
+                        flag: AccessFlagField::ACC_PRIVATE as u16
+                            | AccessFlagField::ACC_FINAL as u16,
+                        name: "value".to_string(),
+                        descriptor: FieldType::A(Box::new(FieldType::C)),
+                        constant_value: None
                     },
                     Field {
-                        flag: 0,
-                        name: "index".to_string(),
-                        descriptor: FieldType::J
+                        flag: AccessFlagField::ACC_PRIVATE as u16,
+                        name: "hash".to_string(),
+                        descriptor: FieldType::I,
+                        constant_value: None
                     }
                 ],
                 methods: vec![
-                    Method {
-                        flag: AccessFlagMethod::ACC_PUBLIC as u16,
-                        name: "concat".to_string(),
-                        descriptor: MethodDescriptor {
-                            parameters: vec![FieldType::L("java/lang/String".to_string())],
-                            ret: FieldType::L("java/lang/String".to_string()) },
-                        repr: Native(NativeMethod {
-                            fn_ptr: |thread, args, exc| {
-                                let a = match ObjectPtr::from_val(args[0]) {
-                                    None => {
-                                        *exc = Some(
-                                            create_throwable("java/lang/NullPointerException",
-                                                           thread));
-                                        return None;
-                                    }
-                                    Some(val) => val
-                                };
-                                let b = match ObjectPtr::from_val(args[1]) {
-                                    None => {
-                                        *exc = Some(
-                                            create_throwable("java/lang/NullPointerException",
-                                                             thread));
-                                        return None;
-                                    }
-                                    Some(val) => val
-                                };
-
-                                let b_length = b.get_field(0);
-                                if b_length == 0 {
-                                    return Some(a.to_val());
-                                }
-
-                                let vm = VM_HANDLER.get().unwrap();
-                                let res = StrArena::get_string(a) + &StrArena::get_string(b);
-                                let res = vm.string_pool.add_string(&res);
-                                Some(res.to_val())
-                            }
-                        })
-                    },
+                    string_native("concat", vec![FieldType::L("java/lang/String".to_string())],
+                                  FieldType::L("java/lang/String".to_string()),
+                                  lang::string::concat),
                     Method {
                         flag: AccessFlagMethod::ACC_PUBLIC as u16,
                         name: "length".to_string(),
-                        descriptor: MethodDescriptor { parameters: vec![], ret: FieldType::J },
+                        descriptor: MethodDescriptor { parameters: vec![], ret: FieldType::I },
                         repr: MethodRepr::Jvm(JvmMethod { code: Some(Code {
                             max_stack: 1,
                             max_locals: 1,
                             code: vec![
                                 42, // aload_0
                                 180, 0, 2, // getfield #2
-                                173 // lreturn
+                                190, // arraylength
+                                172 // ireturn
                             ],
-                            exception_handlers: vec![]
+                            exception_handlers: vec![],
+                            line_numbers: vec![]
                         }) })
                     },
+                    string_native("charAt", vec![FieldType::I], FieldType::C,
+                                  lang::string::charAt),
+                    string_native("substring", vec![FieldType::I],
+                                  FieldType::L("java/lang/String".to_string()),
+                                  lang::string::substring),
+                    string_native("substring", vec![FieldType::I, FieldType::I],
+                                  FieldType::L("java/lang/String".to_string()),
+                                  lang::string::substring_range),
+                    string_native("indexOf", vec![FieldType::I], FieldType::I,
+                                  lang::string::indexOf),
+                    string_native("indexOf", vec![FieldType::I, FieldType::I], FieldType::I,
+                                  lang::string::indexOf_from),
+                    string_native("indexOf", vec![FieldType::L("java/lang/String".to_string())],
+                                  FieldType::I, lang::string::indexOf_string),
+                    string_native("hashCode", vec![], FieldType::I, lang::string::hashCode),
+                    string_native("equals", vec![FieldType::L("java/lang/Object".to_string())],
+                                  FieldType::Z, lang::string::equals),
+                    string_native("compareTo", vec![FieldType::L("java/lang/String".to_string())],
+                                  FieldType::I, lang::string::compareTo),
+                    string_native("equalsIgnoreCase",
+                                  vec![FieldType::L("java/lang/String".to_string())],
+                                  FieldType::Z, lang::string::equalsIgnoreCase),
+                    string_native("intern", vec![], FieldType::L("java/lang/String".to_string()),
+                                  lang::string::intern),
+                    string_native("toString", vec![],
+                                  FieldType::L("java/lang/String".to_string()),
+                                  lang::string::toString),
                     Method {
-                        flag: AccessFlagMethod::ACC_PUBLIC as u16,
-                        name: "charAt".to_string(),
-                        descriptor: MethodDescriptor { parameters: vec![FieldType::I],
-                            ret: FieldType::C },
-                        repr: Native(NativeMethod {
-                            fn_ptr: |thread, args, exc| {
-                                let string = ObjectPtr::from_val(args[0]).unwrap();
-                                let index = args[1] as i32;
-
-                                let str = StrArena::get_string(string);
-
-                                match str.encode_utf16().nth(index as usize) {
-                                    None => {
-                                        *exc = Some(
-                                            create_throwable("java/lang/ArrayIndexOutOfBoundsException", thread));
-
-                                        None
-                                    }
-                                    Some(val) => Some(val as u64)
-                                }
-                            }
-                        })
-                    },
-                    Method {
-                        flag: AccessFlagMethod::ACC_PUBLIC as u16,
-                        name: "equals".to_string(),
+                        flag: AccessFlagMethod::ACC_PUBLIC as u16
+                            | AccessFlagMethod::ACC_STATIC as u16,
+                        name: "valueOf".to_string(),
                         descriptor: MethodDescriptor { parameters: vec![FieldType::L
-                            ("java/lang/Object".to_string())], ret: FieldType::Z },
+                            ("java/lang/Object".to_string())],
+                            ret: FieldType::L("java/lang/String".to_string()) },
                         repr: MethodRepr::Jvm(JvmMethod { code: Some(Code {
-                            max_stack: 2,
-                            max_locals: 2,
+                            max_stack: 1,
+                            max_locals: 1,
                             code: vec![
                                 42, // aload_0
-                                43, // aload_1
-                                184, 0, 4, // invokestatic #2
-                                172 // ireturn
+                                184, 0, 4, // invokestatic #4
+                                176 // areturn
                             ],
-                            exception_handlers: vec![]
+                            exception_handlers: vec![],
+                            line_numbers: vec![]
                         }) })
                     }
                 ],
+                bootstrap_methods: vec![],
+                source_file: None,
+                nest_host: None,
+                nest_members: vec![],
                 static_fields: Default::default(),
                 instance_field_count: 2
             }
@@ -329,11 +350,7 @@ impl VM {
 
         let string_class = self.add_class(string_class_data);
 
-        {
-            let mut class_list = self.bootstrap_cl_class_list.lock().unwrap();
-            class_list.insert(string_name,
-                              string_class);
-        }
+        self.record_loaded_class(ObjectPtr::null(), &string_name, string_class);
 
         {
             use FieldType::*;
@@ -341,80 +358,159 @@ impl VM {
             for t in [B, C, F, D, Z, S, I, J] {
                 let class_data = create_primitive_array_class(t).unwrap();
                 let class = self.add_class(class_data);
-
-                {
-                    let mut class_list = self.bootstrap_cl_class_list.lock().unwrap();
-                    class_list.insert(class.data.name.clone(), class);
-                }
+                self.record_loaded_class(ObjectPtr::null(), &class.data.name.clone(), class);
             }
         }
     }
 
-    pub fn find_loaded_class(&self, name: &str) -> Option<ClassRef> {   // TODO: To support
-                                                                        // user-defined class loaders as well,
-                                                                        // it should take a class_loader object as well
-        self.bootstrap_cl_class_list.lock().unwrap().get(name).map(|s| *s)
+    /// Returns the class loaded under the given name with `class_loader` as its initiating loader
+    /// (null for the bootstrap class loader)
+    pub fn find_loaded_class(&self, class_loader: ObjectPtr, name: &str) -> Option<ClassRef> {
+        self.loaded_classes.lock().unwrap().get(&class_loader)
+            .and_then(|classes| classes.get(name)).copied()
+    }
+
+    /// Records `class_loader` as an initiating loader of the class. If another class has been
+    /// recorded under the same name in the meantime, that one is kept and returned.
+    fn record_loaded_class(&self, class_loader: ObjectPtr, name: &str, class: ClassRef) -> ClassRef {
+        *self.loaded_classes.lock().unwrap().entry(class_loader).or_default()
+            .entry(name.to_string()).or_insert(class)
     }
 
     pub fn load_class(&self, name: &str) -> Result<ClassRef, Exception> {
-        if let Some(class) = self.find_loaded_class(name) {
+        self.load_class_with(ObjectPtr::null(), name)
+    }
+
+    /// Loads a class with the given initiating loader. The bootstrap class loader (null) searches
+    /// the classpath, user-defined loaders are asked through their `loadClass` method.
+    pub fn load_class_with(&self, class_loader: ObjectPtr, name: &str) -> Result<ClassRef, Exception> {
+        let name = &internal_class_name(name);
+
+        if let Some(class) = self.find_loaded_class(class_loader, name) {
             return Ok(class);
         }
 
         eprintln!("Started loading class: {}", name);
 
-        if name.starts_with("[") {
-            return self.load_array_class(name);
-        }
+        let class = if name.starts_with('[') {
+            self.load_array_class(class_loader, name)?
+        } else if class_loader.ptr.is_null() {
+            let buf = self.class_path.find_class(name)
+                .map_err(|e| format!("ClassNotFoundException: {}", e))?;
 
-        let classpath = self.args.read().unwrap();
-        let classpath = classpath.classpath.as_ref().map(|s| s.as_str())
-            .unwrap_or(".");
-        let mut file = find_class_file(name, classpath)?;
-        let mut buf = Vec::with_capacity(INITIAL_CLASS_BUFFER_SIZE);
-        file.read_to_end(&mut buf).map_err(|e| e.to_string())?;
+            eprintln!("Loaded class file {:?}", name);
 
-        eprintln!("Loaded class file {:?}", name);
+            self.derive_class(ObjectPtr::null(), name, &buf)?
+        } else {
+            self.delegate_load_class(class_loader, name)?
+        };
 
-        let class = self.derive_class(ObjectPtr::null(), name, &mut buf)?;
+        Ok(self.record_loaded_class(class_loader, name, class))
+    }
 
-        {
-            let mut class_list = self.bootstrap_cl_class_list.lock().unwrap();
-            class_list.insert(class.data.name.clone(),
-                              class);
+    /// Defines a class from the contents of a class file, with `class_loader` as its defining
+    /// loader (ClassLoader.defineClass)
+    pub fn define_class(&self, class_loader: ObjectPtr, name: Option<&str>, buf: &[u8]) ->
+                                                                    Result<ClassRef, Exception> {
+        let name = name.map(internal_class_name);
+        let duplicate = |name: &str| format!("LinkageError: attempted duplicate class definition \
+                                              for {}", name.replace('/', "."));
+
+        if let Some(name) = &name {
+            if self.find_loaded_class(class_loader, name).is_some() {
+                return Err(duplicate(name));
+            }
+        }
+
+        let class = self.derive_class(class_loader, name.as_deref().unwrap_or(""), buf)?;
+        if self.record_loaded_class(class_loader, &class.data.name, class) != class {
+            return Err(duplicate(&class.data.name));
         }
 
         Ok(class)
     }
 
-    fn load_array_class(&self, name: &str) -> Result<ClassRef, Exception> {
-        let component_class = self.load_class(&name[1..])?;
+    /// Calls `loadClass(String)` of a user-defined class loader
+    fn delegate_load_class(&self, class_loader: ObjectPtr, name: &str) -> Result<ClassRef, Exception> {
+        let binary_name = name.replace('/', ".");
+        let descriptor = MethodDescriptor {
+            parameters: vec![FieldType::L("java/lang/String".to_string())],
+            ret: FieldType::L("java/lang/Class".to_string())
+        };
+
+        let mut class = class_loader.get_class();
+        let method = loop {
+            if let Some(method) = class.find_method("loadClass", &descriptor) {
+                break method;
+            }
+
+            class = class.data.superclass;
+            if class.ptr().is_null() {
+                return Err(format!("{} is not a class loader", class_loader.get_class().data.name));
+            }
+        };
+
+        let ptr = self.string_pool.intern_string(None, &binary_name)?;
+
+        let mut thread = VMThread::new();
+        thread.start(method, smallvec![class_loader.to_val(), ptr.to_val()]);
+
+        match thread.status {
+            FINISHED(Some(class)) if class != 0 => {
+                let class = ClassRef::new(class as *const Class);
+                if class.data.name != name {
+                    return Err(format!("NoClassDefFoundError: {} (wrong name: {})", name,
+                                       class.data.name));
+                }
+
+                Ok(class)
+            }
+            FINISHED(_) | ThreadStatus::RUNNING =>
+                Err(format!("ClassNotFoundException: {}", binary_name)),
+            ThreadStatus::FAILED(_) => Err(error_from_throwable(thread.exception.unwrap()))
+        }
+    }
+
+    /// Array classes are defined by the defining loader of their component type, arrays of
+    /// primitives by the bootstrap class loader
+    fn load_array_class(&self, class_loader: ObjectPtr, name: &str) -> Result<ClassRef, Exception> {
+        let component_name = &name[1..];
+        if component_name.len() == 1 {
+            return self.find_loaded_class(ObjectPtr::null(), name)
+                .ok_or_else(|| format!("Invalid array class {}", name));
+        }
+
+        let component_class = self.load_class_with(class_loader, component_name)?;
+        let defining_loader = component_class.data.class_loader;
+        if let Some(class) = self.find_loaded_class(defining_loader, name) {
+            return Ok(class);
+        }
 
         let class = Class {
             header: Default::default(),
             state: AtomicClassState::new(Verified),
+            cp_lock: Default::default(),
+            init_lock: Default::default(),
             data: ClassRepr {
                 name: name.to_string(),
                 flag: component_class.data.flag,
+                class_loader: defining_loader,
                 superclass: ClassRef::new(null()),
                 interfaces: Default::default(),
                 constant_pool: vec![],
                 fields: vec![],
                 methods: vec![],
+                bootstrap_methods: vec![],
+                source_file: None,
+                nest_host: None,
+                nest_members: vec![],
                 static_fields: Default::default(),
                 instance_field_count: 0
             }
         };
 
-
         let class = self.add_class(class);
-
-        {
-            let mut class_list = self.bootstrap_cl_class_list.lock().unwrap();
-            class_list.insert(class.data.name.clone(), class);
-        }
-
-        Ok(class)
+        Ok(self.record_loaded_class(defining_loader, name, class))
     }
 
     fn load_cp_entries(parsed_class: &ParsedClass, constant_pool: &mut Vec<CPEntry>) -> Result<()
@@ -497,13 +593,61 @@ impl VM {
                 }
 
                 CPInfo::String(ind) => {
-                    let string = get_cp_info!(parsed_class, ind, CPTag::Utf8,
-                        CPInfo::Utf8(ind), ind)?.clone();
+                    let string = parsed_class.string_constants.get(&ind)
+                        .ok_or(format!("Format error at constant pool item {}", ind))?;
 
-                    let ptr = VM_HANDLER.get().unwrap().string_pool.intern_string(string.as_str());
+                    let ptr = VM_HANDLER.get().unwrap().string_pool
+                        .intern_utf16(None, string)?;
 
                     constant_pool.push(ConstantString(ptr));
                 }
+                CPInfo::MethodHandle(kind, reference) => {
+                    constant_pool.push(UnresolvedSymbolicReference(
+                        UnresolvedReference::MethodHandleReference(kind, reference)));
+                }
+                CPInfo::MethodType(descriptor_ind) => {
+                    let descriptor = get_cp_info!(parsed_class, descriptor_ind, CPTag::Utf8,
+                        CPInfo::Utf8(descriptor), descriptor)?;
+
+                    let descriptor = MethodDescriptor::parse(descriptor)
+                        .ok_or(format!("Could not parse method descriptor {}", descriptor))?;
+
+                    constant_pool.push(UnresolvedSymbolicReference(
+                        UnresolvedReference::MethodTypeReference(descriptor)));
+                }
+                CPInfo::InvokeDynamic(bootstrap_method, name_and_type) => {
+                    let (name_ind, descriptor_ind) = get_cp_info!(parsed_class, name_and_type,
+                        CPTag::NameAndType, CPInfo::NameAndType(name_index, descriptor_index),
+                        (*name_index, *descriptor_index))?;
+
+                    let name = get_cp_info!(parsed_class, name_ind, CPTag::Utf8,
+                        CPInfo::Utf8(name), name)?.clone();
+                    let descriptor = get_cp_info!(parsed_class, descriptor_ind, CPTag::Utf8,
+                        CPInfo::Utf8(descriptor), descriptor)?;
+
+                    let descriptor = MethodDescriptor::parse(descriptor)
+                        .ok_or(format!("Could not parse method descriptor {}", descriptor))?;
+
+                    constant_pool.push(UnresolvedSymbolicReference(
+                        UnresolvedReference::InvokeDynamicReference(bootstrap_method, name,
+                                                                    descriptor)));
+                }
+                CPInfo::Dynamic(bootstrap_method, name_and_type) => {
+                    let (name_ind, descriptor_ind) = get_cp_info!(parsed_class, name_and_type,
+                        CPTag::NameAndType, CPInfo::NameAndType(name_index, descriptor_index),
+                        (*name_index, *descriptor_index))?;
+
+                    let name = get_cp_info!(parsed_class, name_ind, CPTag::Utf8,
+                        CPInfo::Utf8(name), name)?.clone();
+                    let descriptor = get_cp_info!(parsed_class, descriptor_ind, CPTag::Utf8,
+                        CPInfo::Utf8(descriptor), descriptor)?;
+
+                    let descriptor = FieldType::parse(descriptor)
+                        .ok_or(format!("Could not parse field descriptor {}", descriptor))?;
+
+                    constant_pool.push(UnresolvedSymbolicReference(
+                        UnresolvedReference::DynamicReference(bootstrap_method, name, descriptor)));
+                }
                 _ => constant_pool.push(CPEntry::Hole)
             }
         }
@@ -511,7 +655,8 @@ impl VM {
         Ok(())
     }
 
-    fn load_methods(parsed_class: &ParsedClass, class_name: &str, methods: &mut Vec<Method>) ->
+    fn load_methods(parsed_class: &ParsedClass, class_loader: ObjectPtr, class_name: &str,
+                    methods: &mut Vec<Method>) ->
                                                                                          Result<()
         , Exception> {
         for m in &parsed_class.methods {
@@ -524,76 +669,47 @@ impl VM {
 
             let mut code = None;
             for a in &m.attributes {
-                let attribute_type = get_cp_info!(parsed_class, a.attribute_name_index, CPTag::Utf8, CPInfo::Utf8(str),
-                        str)?;
-                if attribute_type == "Code" {
-                    let code_length = u32::from_be_bytes(a.info[4..8].try_into().unwrap()) as usize;
-                    let mut code_buf = vec![0; code_length];
-                    code_buf.clone_from_slice(&a.info[8..8+code_length]);
-
-                    let mut pc = 0;
-                    loop {
-                        if code_buf.len() < pc {
-                            panic!("Couldn't validate used instructions");
-                        } else if code_buf.len() == pc {
-                            break;
-                        }
-
-                        let instr = code_buf[pc];
-                        if Instruction::exists(instr) {
-                            let instruction = unsafe { Instruction::from_unchecked(instr) };
-                            pc += instruction_length(instruction);
-                        } else {
-                            panic!("Instruction {} is not yet implemented", code_buf[pc]);
-                        }
-                    }
-
-                    let exception_start = 8+code_length;
-                    let exception_table_length = u16::from_be_bytes(
-                        a.info[exception_start..exception_start+2].try_into().unwrap()) as usize;
-
-                    let mut exception_start = exception_start+2;
-                    let mut handler : Vec<(u16, u16, u16, u16)> = Vec::with_capacity(exception_table_length);
-                    for _ in 0..exception_table_length {
-                        handler.push((
-                            u16::from_be_bytes(
-                                a.info[exception_start..exception_start+2].try_into().unwrap()),
-                            u16::from_be_bytes(
-                                a.info[exception_start+2..exception_start+4].try_into().unwrap()),
-                            u16::from_be_bytes(
-                                a.info[exception_start+4..exception_start+6].try_into().unwrap()),
-                            u16::from_be_bytes(
-                                a.info[exception_start+6..exception_start+8].try_into().unwrap())
-                            ));
-                        exception_start += 8;
-                    }
-
-                    let exception_handlers = handler.iter().map(|(a,b,c,d)| {
-                        let catch_type = if *d == 0 { None } else {
-                            let index = get_cp_info!(parsed_class, *d,
-                                CPTag::Class, CPInfo::Class(num), *num).expect("Class_info \
-                                structure was expected");
+                if let Attribute::Code(code_attribute) = a {
+                    let mut exception_handlers =
+                        Vec::with_capacity(code_attribute.exception_table.len());
+                    for e in &code_attribute.exception_table {
+                        let catch_type = if e.catch_type == 0 { None } else {
+                            let index = get_cp_info!(parsed_class, e.catch_type, CPTag::Class,
+                                CPInfo::Class(num), *num)?;
                             let exception_name = get_cp_info!(parsed_class, index, CPTag::Utf8,
-                                CPInfo::Utf8(str), str).expect("Utf8_info structure was expected");
+                                CPInfo::Utf8(str), str)?;
 
-                            let vm = VM_HANDLER.get().unwrap().load_class(exception_name).unwrap();
-
-                            Some(vm)
+                            Some(VM_HANDLER.get().unwrap()
+                                .load_class_with(class_loader, exception_name)?)
                         };
 
-                        ExceptionHandler {
-                            start_pc: *a as usize,
-                            end_pc: *b as usize,
-                            handler_pc: *c as usize,
+                        exception_handlers.push(ExceptionHandler {
+                            start_pc: e.start_pc as usize,
+                            end_pc: e.end_pc as usize,
+                            handler_pc: e.handler_pc as usize,
                             catch_type
-                        }
-                    }).collect();
+                        });
+                    }
+
+                    let mut line_numbers: Vec<LineNumber> = code_attribute.attributes.iter()
+                        .filter_map(|a| match a {
+                            Attribute::LineNumberTable(table) => Some(table),
+                            _ => None
+                        })
+                        .flatten()
+                        .map(|l| LineNumber {
+                            start_pc: l.start_pc as usize,
+                            line_number: l.line_number
+                        })
+                        .collect();
+                    line_numbers.sort_by_key(|l| l.start_pc);
 
                     code = Some(Code {
-                        max_stack: u16::from_be_bytes(a.info[..2].try_into().unwrap()) as usize,
-                        max_locals: u16::from_be_bytes(a.info[2..4].try_into().unwrap()) as usize,
-                        code: code_buf,
-                        exception_handlers
+                        max_stack: code_attribute.max_stack as usize,
+                        max_locals: code_attribute.max_locals as usize,
+                        code: code_attribute.code.clone(),
+                        exception_handlers,
+                        line_numbers
                     });
                 }
             }
@@ -631,6 +747,20 @@ impl VM {
         Ok(())
     }
 
+    fn load_bootstrap_methods(parsed_class: &ParsedClass,
+                              bootstrap_methods: &mut Vec<BootstrapMethod>) -> Result<(), Exception> {
+        for a in &parsed_class.attributes {
+            if let Attribute::BootstrapMethods(methods) = a {
+                bootstrap_methods.extend(methods.iter().map(|m| BootstrapMethod {
+                    method_handle: m.bootstrap_method_ref,
+                    arguments: m.bootstrap_arguments.clone()
+                }));
+            }
+        }
+
+        Ok(())
+    }
+
     fn load_fields(parsed_class: &ParsedClass, fields: &mut Vec<Field>) -> Result<(), Exception> {
         for f in &parsed_class.fields {
             let name = get_cp_info!(parsed_class, f.name_index, CPTag::Utf8, CPInfo::Utf8(str),
@@ -640,19 +770,48 @@ impl VM {
             let descriptor = FieldType::parse(descriptor)
                 .ok_or(format!("Could not parse field descriptor {}", descriptor))?;
 
+            let mut constant_value = None;
+            if has_flag(f.access_flags, AccessFlagField::ACC_STATIC) {
+                for a in &f.attributes {
+                    if let Attribute::ConstantValue(index) = a {
+                        let tag = match &descriptor {
+                            FieldType::B | FieldType::C | FieldType::I | FieldType::S
+                            | FieldType::Z => CPTag::Integer,
+                            FieldType::F => CPTag::Float,
+                            FieldType::J => CPTag::Long,
+                            FieldType::D => CPTag::Double,
+                            FieldType::L(class) if class == "java/lang/String" => CPTag::String,
+                            _ => return Err(format!("ClassFormatError: Illegal ConstantValue \
+                                attribute for field {}", name))
+                        };
+                        parsed_class.get_cp_info_raw(*index, tag).ok_or(format!(
+                            "ClassFormatError: Inconsistent constant value type for field {}",
+                            name))?;
+
+                        constant_value = Some(*index as usize);
+                    }
+                }
+            }
+
             fields.push(Field {
                 flag: f.access_flags,
                 name,
-                descriptor
+                descriptor,
+                constant_value
             })
         }
 
         Ok(())
     }
 
-    pub fn derive_class(&self, _class_loader: ObjectPtr, _name: &str, buf: &[u8]) ->
+    /// Creates a class from the contents of a class file. Unless `name` is empty, the class file
+    /// has to define a class with that name.
+    pub fn derive_class(&self, class_loader: ObjectPtr, name: &str, buf: &[u8]) ->
                                                                                  Result<ClassRef, Exception> {
-        let parsed_class = parse_class(buf).map_err(|e| e.to_string())?;
+        let parsed_class = parse_class(buf)
+            .map_err(|e| format!("ClassFormatError: {}", e.message))?;
+        let verification = verify(&parsed_class)
+            .map_err(|e| format!("ClassFormatError: {}", e))?;
 
         let mut constant_pool = vec![];
         VM::load_cp_entries(&parsed_class, &mut constant_pool)?;
@@ -664,31 +823,65 @@ impl VM {
 
         let class_name = get_cp_info!(parsed_class, this_class, CPTag::Utf8, CPInfo::Utf8(str), str)?;
 
+        if !name.is_empty() && class_name != name {
+            return Err(format!("NoClassDefFoundError: {} (wrong name: {})", name, class_name));
+        }
+
         let superclass = get_cp_info!(parsed_class, parsed_class.super_class, CPTag::Class,
             CPInfo::Class(num), *num)?;
 
         let superclass_name = get_cp_info!(parsed_class, superclass, CPTag::Utf8, CPInfo::Utf8
             (str), str)?;
 
-        let ptr = self.string_pool.intern_string(superclass_name);
-
-        let mut thread = VMThread::new();
-        thread.start((self.classloader, 0), smallvec![0, ptr.ptr as u64]);
+        let superclass = self.load_referenced_class(class_loader, superclass_name)?;
 
-        let superclass: ClassRef;
+        let mut interfaces = SmallVec::with_capacity(parsed_class.interfaces.len());
+        for interface in &parsed_class.interfaces {
+            let interface = get_cp_info!(parsed_class, *interface, CPTag::Class,
+                CPInfo::Class(num), *num)?;
+            let interface_name = get_cp_info!(parsed_class, interface, CPTag::Utf8,
+                CPInfo::Utf8(str), str)?;
 
-        match thread.status {
-            FINISHED(Some(class)) => superclass = ClassRef::new(class as *const Class),
-            ThreadStatus::FAILED(e) => return Err(e),
-            _ => panic!("Can't happen")
+            let interface = self.load_referenced_class(class_loader, interface_name)?;
+            if !interface.is_interface() {
+                return Err(format!("IncompatibleClassChangeError: {} implements class {}",
+                                   class_name, interface.data.name));
+            }
+            interfaces.push(interface);
         }
 
         let mut methods = Vec::with_capacity(parsed_class.methods.len());
-        VM::load_methods(&parsed_class, class_name, &mut methods)?;
+        VM::load_methods(&parsed_class, class_loader, class_name, &mut methods)?;
 
         let mut fields = Vec::with_capacity(parsed_class.fields.len());
         VM::load_fields(&parsed_class, &mut fields)?;
 
+        let mut bootstrap_methods = vec![];
+        VM::load_bootstrap_methods(&parsed_class, &mut bootstrap_methods)?;
+
+        let class_name_at = |index: U2| -> Result<String, Exception> {
+            let name = get_cp_info!(parsed_class, index, CPTag::Class, CPInfo::Class(num), *num)?;
+            Ok(get_cp_info!(parsed_class, name, CPTag::Utf8, CPInfo::Utf8(str), str)?.clone())
+        };
+
+        let mut source_file = None;
+        let mut nest_host = None;
+        let mut nest_members = vec![];
+        for a in &parsed_class.attributes {
+            match a {
+                Attribute::SourceFile(index) => {
+                    source_file = Some(get_cp_info!(parsed_class, *index, CPTag::Utf8,
+                        CPInfo::Utf8(str), str)?.clone());
+                }
+                Attribute::NestHost(index) => nest_host = Some(class_name_at(*index)?),
+                Attribute::NestMembers(indices) => {
+                    nest_members = indices.iter().map(|i| class_name_at(*i))
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => {}
+            }
+        }
+
         let static_field_count = fields.iter().filter(|f| f.is_static()).count();
         let mut static_fields = SmallVec::with_capacity(static_field_count);
         for _ in 0..static_field_count {
@@ -700,34 +893,122 @@ impl VM {
 
         let class = Class {
             header: ObjectHeader::default(),
-            state: AtomicClassState::new(Verified), // TODO: Verification before giving this state
+            state: AtomicClassState::new(Loaded),
+            cp_lock: Default::default(),
+            init_lock: Default::default(),
             data: ClassRepr {
                 name: class_name.clone(),
                 flag: parsed_class.access_flags,
+                class_loader,
                 superclass,
-                interfaces: Default::default(),
+                interfaces,
                 constant_pool,
                 fields,
                 methods,
+                bootstrap_methods,
+                source_file,
+                nest_host,
+                nest_members,
                 static_fields,
                 instance_field_count
             }
         };
 
         let class = self.add_class(class);
+        self.pending_verifications.lock().unwrap().insert(class, verification.into());
         Ok(class)
     }
+
+    /// Loads a class referenced by another one (e.g. its superclass) through the defining loader
+    /// of the referencing class. A class that can not be found is reported with a
+    /// NoClassDefFoundError.
+    pub fn load_referenced_class(&self, class_loader: ObjectPtr, name: &str) -> Result<ClassRef, Exception> {
+        let ptr = self.string_pool.intern_string(None, name)?;
+
+        let mut thread = VMThread::new();
+        thread.start((self.classloader, 0), smallvec![class_loader.to_val(), ptr.ptr as u64]);
+
+        match thread.status {
+            FINISHED(Some(class)) if class != 0 => Ok(ClassRef::new(class as *const Class)),
+            FINISHED(_) | ThreadStatus::RUNNING => Err(format!("NoClassDefFoundError: {}", name)),
+            ThreadStatus::FAILED(_) => {
+                let error = error_from_throwable(thread.exception.unwrap());
+                if error.starts_with("ClassNotFoundException: ") {
+                    Err(format!("NoClassDefFoundError: {}", name))
+                } else {
+                    Err(error)
+                }
+            }
+        }
+    }
 }
 
 type Exception = String;
 
-pub fn find_class_file(name: &str, class_path: &str) -> Result<File, Exception> {
-    let buf = PathBuf::from(class_path);
-    let name = name.replace('.', "/") + ".class";
+/// Converts a class name as used by Java code (e.g. java.lang.String or [Ljava.lang.String;) or a
+/// class file descriptor of an array to the name the VM uses internally (java/lang/String,
+/// [java/lang/String)
+pub fn internal_class_name(name: &str) -> String {
+    let name = name.replace('.', "/");
+    match name.trim_start_matches('[').strip_prefix('L') {
+        Some(component) if name.starts_with('[') && name.ends_with(';') =>
+            format!("{}{}", &name[..name.len() - component.len() - 1],
+                    &component[..component.len() - 1]),
+        _ => name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{VM, VM_HANDLER};
+    use std::sync::atomic::Ordering;
+    use crate::helper::fixtures;
+    use crate::vm::class_loader::resolve::initialize_class;
+    use crate::vm::object::ObjectPtr;
+
+    #[test]
+    fn classes_are_keyed_by_loader() {
+        let vm = VM_HANDLER.get_or_init(|| VM::vm_init(false));
+
+        // Stand-ins for class loader objects, they have no loadClass method, so Object is
+        // recorded for them up front
+        let loaders: Vec<ObjectPtr> = (0..3).map(|_| vm.object_arena.new_object(vm.object_class))
+            .collect();
+        for loader in &loaders {
+            vm.record_loaded_class(*loader, "java/lang/Object", vm.object_class);
+        }
 
-    File::open(buf.join(name.clone()).as_path()).map_err(|e| {
-        let mut str = e.to_string();
-        str.push_str(format!(" while loading {}", name).as_str());
-        str
-    })
-}
\ No newline at end of file
+        let plugin = fixtures::class_file("test/fixtures/plugin", "Plugin");
+        let a = vm.define_class(loaders[0], Some("Plugin"), &plugin).unwrap();
+        let b = vm.define_class(loaders[1], None, &plugin).unwrap();
+        assert_ne!(a, b);
+        assert_eq!(a.data.class_loader, loaders[0]);
+        assert_eq!(vm.find_loaded_class(loaders[0], "Plugin"), Some(a));
+        assert_eq!(vm.find_loaded_class(loaders[1], "Plugin"), Some(b));
+        assert_eq!(vm.find_loaded_class(ObjectPtr::null(), "Plugin"), None);
+
+        assert!(vm.define_class(loaders[0], None, &plugin).unwrap_err().contains("duplicate"));
+        assert!(vm.define_class(loaders[2], Some("Other"), &plugin).unwrap_err()
+            .contains("wrong name"));
+
+        let array = vm.load_class_with(loaders[1], "[LPlugin;").unwrap();
+        assert_eq!(array.data.class_loader, loaders[1]);
+        assert!(!array.is_subclass(vm.load_class_with(loaders[0], "[LPlugin;").unwrap()));
+    }
+
+    #[test]
+    fn static_fields_get_constant_values() {
+        let vm = VM_HANDLER.get_or_init(|| VM::vm_init(false));
+
+        let constants = fixtures::class_file("test/fixtures/constants", "Constants");
+        let class = vm.define_class(ObjectPtr::null(), Some("Constants"), &constants).unwrap();
+        initialize_class(class, None).unwrap();
+
+        let values: Vec<u64> = class.data.static_fields.iter()
+            .map(|f| f.load(Ordering::Relaxed)).collect();
+        assert_eq!(values[0] as i32, -7);
+        assert_eq!(values[1], 1 << 40);
+        assert_eq!(f64::from_bits(values[2]), 2.5);
+        assert_eq!(values[3], vm.string_pool.intern_string(None, "constant").unwrap().to_val());
+    }
+}
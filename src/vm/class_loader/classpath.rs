@@ -0,0 +1,243 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use crate::vm::class_loader::zip::ZipArchive;
+
+type Exception = String;
+
+/// Locations searched for class files, in order
+#[derive(Debug, Default)]
+pub struct ClassPath {
+    entries: Vec<ClassPathEntry>
+}
+
+#[derive(Debug)]
+enum ClassPathEntry {
+    Directory(PathBuf),
+    Archive(PathBuf, ZipArchive),
+    // Kept to be able to tell why nothing was found there
+    Unreadable(PathBuf, String)
+}
+
+const MANIFEST: &str = "META-INF/MANIFEST.MF";
+
+impl ClassPathEntry {
+    fn new(path: PathBuf) -> ClassPathEntry {
+        let is_archive = path.extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("jar") || e.eq_ignore_ascii_case("zip"));
+        if !is_archive {
+            return ClassPathEntry::Directory(path);
+        }
+
+        match ZipArchive::open(&path) {
+            Ok(archive) => ClassPathEntry::Archive(path, archive),
+            Err(e) => ClassPathEntry::Unreadable(path, e)
+        }
+    }
+}
+
+impl ClassPath {
+    /// Parses a `:`-separated list of directories and .jar/.zip archives. Archives are opened
+    /// right away.
+    pub fn new(class_path: &str) -> ClassPath {
+        let entries = class_path.split(':')
+            .map(|entry| ClassPathEntry::new(PathBuf::from(if entry.is_empty() { "." } else { entry })))
+            .collect();
+
+        ClassPath { entries }
+    }
+
+    /// Class path of an executable JAR: the archive itself followed by the `Class-Path` entries
+    /// of its manifest, relative URLs resolved against the archive's directory. Also returns the
+    /// `Main-Class` attribute of the manifest.
+    pub fn from_jar(jar: &Path) -> Result<(ClassPath, String), Exception> {
+        let archive = ZipArchive::open(jar)
+            .map_err(|e| format!("Invalid or corrupt jarfile {}: {}", jar.display(), e))?;
+
+        let manifest = match archive.read(MANIFEST) {
+            Some(manifest) => manifest
+                .map_err(|e| format!("{} while loading {} from {}", e, MANIFEST, jar.display()))?,
+            None => Vec::new()
+        };
+        let attributes = main_attributes(&String::from_utf8_lossy(&manifest));
+
+        let main_class = attributes.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Main-Class"))
+            .map(|(_, value)| value.clone())
+            .ok_or_else(|| format!("no main manifest attribute, in {}", jar.display()))?;
+
+        let directory = jar.parent().unwrap_or(Path::new(""));
+        let mut entries = vec![ClassPathEntry::Archive(jar.to_path_buf(), archive)];
+        if let Some((_, class_path)) = attributes.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Class-Path")) {
+            entries.extend(class_path.split_ascii_whitespace()
+                .map(|url| ClassPathEntry::new(directory.join(decode_url_path(url)))));
+        }
+
+        Ok((ClassPath { entries }, main_class))
+    }
+
+    /// Reads the class file of the class with the given binary name from the first entry
+    /// containing it
+    pub fn find_class(&self, name: &str) -> Result<Vec<u8>, Exception> {
+        let file_name = name.replace('.', "/") + ".class";
+
+        let mut searched = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            match entry {
+                ClassPathEntry::Directory(directory) => {
+                    let path = directory.join(&file_name);
+                    match std::fs::read(&path) {
+                        Ok(buf) => return Ok(buf),
+                        Err(e) if e.kind() == ErrorKind::NotFound =>
+                            searched.push(path.display().to_string()),
+                        Err(e) => searched.push(format!("{} ({})", path.display(), e))
+                    }
+                }
+                ClassPathEntry::Archive(path, archive) => match archive.read(&file_name) {
+                    Some(res) => return res.map_err(|e| format!("{} while loading {} from {}",
+                                                                e, file_name, path.display())),
+                    None => searched.push(format!("{}!/{}", path.display(), file_name))
+                }
+                ClassPathEntry::Unreadable(path, e) =>
+                    searched.push(format!("{} ({})", path.display(), e))
+            }
+        }
+
+        Err(format!("Could not find {} on the classpath, searched: {}", file_name,
+                    searched.join(", ")))
+    }
+}
+
+/// Parses the main section of a JAR manifest, i.e. the `Name: value` headers before the first
+/// empty line. Lines starting with a single space continue the previous value.
+fn main_attributes(manifest: &str) -> Vec<(String, String)> {
+    let mut attributes: Vec<(String, String)> = Vec::new();
+    for line in manifest.lines() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.is_empty() {
+            break;
+        }
+
+        match line.strip_prefix(' ') {
+            Some(continuation) => if let Some((_, value)) = attributes.last_mut() {
+                value.push_str(continuation);
+            }
+            None => if let Some((name, value)) = line.split_once(':') {
+                attributes.push((name.to_string(), value.trim_start().to_string()));
+            }
+        }
+    }
+
+    attributes
+}
+
+/// Decodes the `%XX` escapes of a URL path, like `%20` for a space. The decoded bytes are UTF-8,
+/// malformed escapes are kept as they are.
+fn decode_url_path(url: &str) -> String {
+    let bytes = url.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) if bytes[i] == b'%' => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::helper::fixtures;
+    use crate::vm::class_loader::classpath::{ClassPath, ClassPathEntry, decode_url_path, main_attributes};
+    use crate::vm::class_loader::zip::ZipArchive;
+    use crate::vm::class_loader::zip::tests::test_zip;
+
+    #[test]
+    fn searches_in_order() {
+        let directory = std::env::temp_dir().join(format!("rust-jvm3-cp-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("a")).unwrap();
+        std::fs::write(directory.join("a/Hello.class"), b"from directory").unwrap();
+
+        let class_path = ClassPath {
+            entries: vec![
+                ClassPathEntry::Unreadable(PathBuf::from("missing.jar"), "not found".to_string()),
+                ClassPathEntry::Archive(PathBuf::from("test.jar"),
+                                        ZipArchive::new(test_zip()).unwrap()),
+                ClassPathEntry::Directory(directory.clone())
+            ]
+        };
+
+        assert_eq!(class_path.find_class("stored").unwrap(), b"stored entry\n");
+        assert_eq!(class_path.find_class("a.Hello").unwrap(), b"from directory");
+
+        let error = class_path.find_class("b/Missing").unwrap_err();
+        assert!(error.contains("missing.jar (not found)"));
+        assert!(error.contains("test.jar!/b/Missing.class"));
+        assert!(error.contains(&directory.join("b/Missing.class").display().to_string()));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn parses_manifest() {
+        let manifest = "Manifest-Version: 1.0\r\nMain-Class: hu.garaba.Ma\r\n in\r\n\
+                        Class-Path: lib/a.jar  b.jar\r\n\r\nName: hu/garaba/\r\nSealed: true\r\n";
+
+        assert_eq!(main_attributes(manifest), vec![
+            ("Manifest-Version".to_string(), "1.0".to_string()),
+            ("Main-Class".to_string(), "hu.garaba.Main".to_string()),
+            ("Class-Path".to_string(), "lib/a.jar  b.jar".to_string())
+        ]);
+    }
+
+    #[test]
+    fn decodes_class_path_urls() {
+        assert_eq!(decode_url_path("lib/my%20app.jar"), "lib/my app.jar");
+        assert_eq!(decode_url_path("%C3%A1rv%c3%adz/"), "\u{e1}rv\u{ed}z/");
+        assert_eq!(decode_url_path("100%.jar"), "100%.jar");
+        assert_eq!(decode_url_path("a%2"), "a%2");
+    }
+
+    #[test]
+    fn follows_manifest_class_path() {
+        let directory = fixtures::temp_dir("manifest-class-path");
+        fixtures::javac("test/fixtures/jar/lib", &[], &directory.join("lib dir"));
+        fixtures::javac("test/fixtures/jar/app", &[&directory.join("lib dir")],
+                        &directory.join("app"));
+
+        let jar = directory.join("app.jar");
+        fixtures::jar(&jar, Some("Main-Class: hu.garaba.app.Main\nClass-Path: lib%20dir/\n"),
+                      &directory.join("app"), &["."], true);
+
+        let (class_path, main_class) = ClassPath::from_jar(&jar).unwrap();
+        assert_eq!(main_class, "hu.garaba.app.Main");
+        assert!(class_path.find_class("hu/garaba/app/Main").is_ok());
+        assert!(class_path.find_class("hu/garaba/lib/Greeter").is_ok());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn requires_main_class() {
+        let jar = std::env::temp_dir().join(format!("rust-jvm3-jar-{}.jar", std::process::id()));
+        std::fs::write(&jar, test_zip()).unwrap();
+
+        let error = ClassPath::from_jar(&jar).unwrap_err();
+        assert!(error.starts_with("no main manifest attribute"));
+
+        std::fs::remove_file(jar).unwrap();
+    }
+}
@@ -0,0 +1,310 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use smallvec::smallvec;
+use crate::{Class, ClassRef, ClassRepr, Method, ObjectHeader, VM_HANDLER};
+use crate::class_parser::constants::{AccessFlagClass, AccessFlagField, AccessFlagMethod};
+use crate::vm::class::class::{AtomicClassState, CPEntryWrapper};
+use crate::vm::class::class::ClassState::Ready;
+use crate::vm::class::constant_pool::{BootstrapMethod, CallSite, ConcatElement, CPEntry,
+                                      SymbolicReference, UnresolvedReference};
+use crate::vm::class::field::{Field, FieldType};
+use crate::vm::class::method::{Code, JvmMethod, MethodDescriptor, MethodRepr};
+use crate::vm::class_loader::resolve::resolve;
+use crate::vm::pool::string::string_value;
+use crate::vm::thread::thread::MethodRef;
+
+type Exception = String;
+
+// Method handle reference kinds (JVMS §5.4.3.5)
+const REF_INVOKE_VIRTUAL: u8 = 5;
+const REF_INVOKE_STATIC: u8 = 6;
+const REF_INVOKE_SPECIAL: u8 = 7;
+const REF_NEW_INVOKE_SPECIAL: u8 = 8;
+const REF_INVOKE_INTERFACE: u8 = 9;
+
+static LAMBDA_CLASS_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Links an invokedynamic call site of the class (JVMS §5.4.3.6). Instead of running the
+/// bootstrap method, the VM recognizes the bootstrap methods of StringConcatFactory and
+/// LambdaMetafactory and builds the call site itself.
+pub fn link_call_site(class: ClassRef, bootstrap_index: u16, name: &str,
+                      descriptor: &MethodDescriptor) -> Result<CallSite, Exception> {
+    let bootstrap = class.data.bootstrap_methods.get(bootstrap_index as usize)
+        .ok_or(format!("No bootstrap method at index {} in {}", bootstrap_index,
+                       class.data.name))?;
+
+    let reference = match class.get_cp_entry(bootstrap.method_handle as usize) {
+        CPEntry::UnresolvedSymbolicReference(
+            UnresolvedReference::MethodHandleReference(REF_INVOKE_STATIC, reference)) => *reference,
+        entry => return Err(format!("Bootstrap method should be a static method handle, got \
+            {:?}", entry))
+    };
+
+    match member_name(class, reference)? {
+        (class_name, method) if class_name == "java/lang/invoke/StringConcatFactory"
+            && method == "makeConcatWithConstants" =>
+            link_string_concat(class, bootstrap, descriptor),
+        (class_name, method) if class_name == "java/lang/invoke/StringConcatFactory"
+            && method == "makeConcat" => {
+            let elements = (0..descriptor.parameters.len()).map(ConcatElement::Argument)
+                .collect();
+            Ok(CallSite::StringConcat(elements, descriptor.parameters.clone()))
+        }
+        (class_name, method) if class_name == "java/lang/invoke/LambdaMetafactory"
+            && method == "metafactory" =>
+            link_lambda(class, bootstrap, name, descriptor),
+        (class_name, method) => Err(format!("Unsupported bootstrap method {}.{}", class_name,
+                                            method))
+    }
+}
+
+/// The class and method name of a method reference, without resolving it
+fn member_name(class: ClassRef, index: u16) -> Result<(String, String), Exception> {
+    use UnresolvedReference::*;
+
+    match class.get_cp_entry(index as usize) {
+        CPEntry::UnresolvedSymbolicReference(MethodReference(class_index, name, _)
+            | InterfaceMethodReference(class_index, name, _)) => {
+            let class_name = match class.get_cp_entry(*class_index as usize) {
+                CPEntry::UnresolvedSymbolicReference(ClassReference(class_name)) =>
+                    class_name.clone(),
+                CPEntry::ResolvedSymbolicReference(SymbolicReference::ClassReference(other)) =>
+                    other.data.name.clone(),
+                entry => return Err(format!("Unexpected class reference {:?}", entry))
+            };
+
+            Ok((class_name, name.clone()))
+        }
+        CPEntry::ResolvedSymbolicReference(SymbolicReference::MethodReference(other, i)) =>
+            Ok((other.data.name.clone(), other.data.methods[*i].name.clone())),
+        entry => Err(format!("Unexpected method reference {:?}", entry))
+    }
+}
+
+/// In the recipe of makeConcatWithConstants \1 stands for an argument and \2 for the next
+/// constant among the bootstrap arguments, everything else is copied as is
+fn link_string_concat(class: ClassRef, bootstrap: &BootstrapMethod,
+                      descriptor: &MethodDescriptor) -> Result<CallSite, Exception> {
+    let mut arguments = bootstrap.arguments.iter()
+        .map(|i| match class.get_cp_entry(*i as usize) {
+            CPEntry::ConstantString(ptr) => Ok(string_value(*ptr).char_array().to_vec()),
+            entry => Err(format!("Unsupported string concatenation constant {:?}", entry))
+        });
+
+    let recipe = arguments.next().ok_or("Missing string concatenation recipe")??;
+
+    let mut elements = vec![];
+    let mut constant = vec![];
+    let mut argument_count = 0;
+    for &c in &recipe {
+        match c {
+            1 => {
+                if !constant.is_empty() {
+                    elements.push(ConcatElement::Constant(std::mem::take(&mut constant)));
+                }
+                elements.push(ConcatElement::Argument(argument_count));
+                argument_count += 1;
+            }
+            2 => constant.extend_from_slice(&arguments.next()
+                .ok_or("Missing string concatenation constant")??),
+            c => constant.push(c)
+        }
+    }
+    if !constant.is_empty() {
+        elements.push(ConcatElement::Constant(constant));
+    }
+
+    if argument_count != descriptor.parameters.len() {
+        return Err(format!("String concatenation recipe has {} arguments instead of {}",
+                           argument_count, descriptor.parameters.len()));
+    }
+
+    Ok(CallSite::StringConcat(elements, descriptor.parameters.clone()))
+}
+
+/// The bootstrap arguments of metafactory are the erased type of the implemented interface
+/// method, the implementation method and its instantiated type
+fn link_lambda(class: ClassRef, bootstrap: &BootstrapMethod, name: &str,
+               descriptor: &MethodDescriptor) -> Result<CallSite, Exception> {
+    let [erased, implementation, _] = bootstrap.arguments.as_slice() else {
+        return Err(format!("Unexpected arguments for LambdaMetafactory.metafactory: {:?}",
+                           bootstrap.arguments));
+    };
+
+    let erased = match class.get_cp_entry(*erased as usize) {
+        CPEntry::UnresolvedSymbolicReference(UnresolvedReference::MethodTypeReference(erased)) =>
+            erased.clone(),
+        entry => return Err(format!("Expected a method type, got {:?}", entry))
+    };
+
+    let (kind, reference) = match class.get_cp_entry(*implementation as usize) {
+        CPEntry::UnresolvedSymbolicReference(
+            UnresolvedReference::MethodHandleReference(kind, reference)) => (*kind, *reference),
+        entry => return Err(format!("Expected a method handle, got {:?}", entry))
+    };
+
+    resolve(class, reference as usize)?;
+    let implementation = match class.get_cp_entry(reference as usize) {
+        CPEntry::ResolvedSymbolicReference(SymbolicReference::MethodReference(c, i)) => (*c, *i),
+        entry => return Err(format!("Expected a method reference, got {:?}", entry))
+    };
+
+    let interface = match &descriptor.ret {
+        FieldType::L(interface) => VM_HANDLER.get().unwrap()
+            .load_class_with(class.data.class_loader, interface)?,
+        ret => return Err(format!("Lambda should implement an interface, not {:?}", ret))
+    };
+
+    let lambda_class = create_lambda_class(class, interface, name, &descriptor.parameters,
+                                           erased, kind, implementation)?;
+
+    Ok(CallSite::Lambda(lambda_class))
+}
+
+/// Creates a class implementing the interface, whose fields hold the captured values and whose
+/// single method forwards to the implementation method
+fn create_lambda_class(caller: ClassRef, interface: ClassRef, name: &str, captured: &[FieldType],
+                       erased: MethodDescriptor, kind: u8, implementation: MethodRef)
+    -> Result<ClassRef, Exception> {
+    let vm = VM_HANDLER.get().unwrap();
+    let implementation_method = &implementation.0.data.methods[implementation.1];
+
+    // Values passed to the implementation: the captured ones followed by the arguments
+    let mut passed = captured.iter().chain(erased.parameters.iter());
+    if matches!(kind, REF_INVOKE_VIRTUAL | REF_INVOKE_SPECIAL | REF_INVOKE_INTERFACE) {
+        passed.next().ok_or("Missing receiver of the lambda implementation method")?;
+    }
+    let passed: Vec<FieldType> = passed.cloned().collect();
+
+    let returned = if kind == REF_NEW_INVOKE_SPECIAL {
+        FieldType::L(implementation.0.data.name.clone())
+    } else {
+        implementation_method.descriptor.ret.clone()
+    };
+
+    // Boxing and unboxing conversions are not supported
+    let compatible = passed.len() == implementation_method.descriptor.parameters.len()
+        && passed.iter().zip(&implementation_method.descriptor.parameters)
+            .all(|(a, b)| a.is_reference() == b.is_reference())
+        && (erased.ret == FieldType::V
+            || (returned != FieldType::V && returned.is_reference() == erased.ret.is_reference()));
+    if !compatible {
+        return Err(format!("Unsupported lambda conversion from {}.{} to {}.{}",
+                           implementation.0.data.name, implementation_method.name,
+                           interface.data.name, name));
+    }
+
+    // Constant pool: the captured fields, the implementation method, then its class
+    let method_index = captured.len() as u16 + 1;
+    let class_index = method_index + 1;
+
+    let mut code = vec![];
+    if kind == REF_NEW_INVOKE_SPECIAL {
+        code.push(187); // new
+        code.extend_from_slice(&class_index.to_be_bytes());
+        code.push(89); // dup
+    }
+    for i in 0..captured.len() as u16 {
+        code.push(42); // aload_0
+        code.push(180); // getfield
+        code.extend_from_slice(&(i + 1).to_be_bytes());
+    }
+
+    let mut local = 1;
+    for parameter in &erased.parameters {
+        code.push(match parameter {
+            FieldType::J => 22, // lload
+            FieldType::F => 23, // fload
+            FieldType::D => 24, // dload
+            FieldType::L(_) | FieldType::A(_) => 25, // aload
+            _ => 21 // iload
+        });
+        code.push(local as u8);
+        local += if matches!(parameter, FieldType::J | FieldType::D) { 2 } else { 1 };
+    }
+
+    code.push(match kind {
+        REF_INVOKE_VIRTUAL => 182,
+        REF_INVOKE_STATIC => 184,
+        REF_INVOKE_SPECIAL | REF_NEW_INVOKE_SPECIAL => 183,
+        REF_INVOKE_INTERFACE => 185,
+        _ => return Err(format!("Unsupported method handle kind {} for a lambda", kind))
+    });
+    code.extend_from_slice(&method_index.to_be_bytes());
+    if kind == REF_INVOKE_INTERFACE {
+        code.extend_from_slice(&[passed.len() as u8 + 1, 0]);
+    }
+
+    if erased.ret == FieldType::V && returned != FieldType::V {
+        code.push(87); // pop
+    }
+    code.push(match erased.ret {
+        FieldType::V => 177, // return
+        FieldType::J => 173, // lreturn
+        FieldType::F => 174, // freturn
+        FieldType::D => 175, // dreturn
+        FieldType::L(_) | FieldType::A(_) => 176, // areturn
+        _ => 172 // ireturn
+    });
+
+    let method = Method {
+        flag: AccessFlagMethod::ACC_PUBLIC as u16 | AccessFlagMethod::ACC_FINAL as u16
+            | AccessFlagMethod::ACC_SYNTHETIC as u16,
+        name: name.to_string(),
+        descriptor: erased,
+        repr: MethodRepr::Jvm(JvmMethod {
+            code: Some(Code {
+                max_stack: captured.len() + passed.len() + 3,
+                max_locals: local,
+                code,
+                exception_handlers: vec![],
+                line_numbers: vec![]
+            })
+        })
+    };
+
+    let fields: Vec<Field> = captured.iter().enumerate().map(|(i, descriptor)| Field {
+        flag: AccessFlagField::ACC_PRIVATE as u16 | AccessFlagField::ACC_FINAL as u16,
+        name: format!("arg${}", i + 1),
+        descriptor: descriptor.clone(),
+        constant_value: None
+    }).collect();
+
+    let constant_pool = (0..class_index).map(|_| CPEntryWrapper::new(&CPEntry::Hole)).collect();
+
+    let class = vm.add_class(Class {
+        header: ObjectHeader::default(),
+        state: AtomicClassState::new(Ready),
+        cp_lock: Default::default(),
+        init_lock: Default::default(),
+        data: ClassRepr {
+            name: format!("{}$$Lambda${}", caller.data.name,
+                          LAMBDA_CLASS_COUNT.fetch_add(1, Ordering::Relaxed) + 1),
+            flag: AccessFlagClass::ACC_FINAL as u16 | AccessFlagClass::ACC_SUPER as u16
+                | AccessFlagClass::ACC_SYNTHETIC as u16,
+            class_loader: caller.data.class_loader,
+            superclass: vm.object_class,
+            interfaces: smallvec![interface],
+            constant_pool,
+            instance_field_count: vm.object_class.data.instance_field_count + fields.len(),
+            fields,
+            methods: vec![method],
+            bootstrap_methods: vec![],
+            source_file: None,
+            nest_host: None,
+            nest_members: vec![],
+            static_fields: Default::default()
+        }
+    });
+
+    for i in 0..captured.len() {
+        class.set_cp_entry(i + 1, CPEntry::ResolvedSymbolicReference(
+            SymbolicReference::FieldReference(class, true,
+                                              vm.object_class.data.instance_field_count + i)));
+    }
+    class.set_cp_entry(method_index as usize, CPEntry::ResolvedSymbolicReference(
+        SymbolicReference::MethodReference(implementation.0, implementation.1)));
+    class.set_cp_entry(class_index as usize, CPEntry::ResolvedSymbolicReference(
+        SymbolicReference::ClassReference(implementation.0)));
+
+    Ok(class)
+}
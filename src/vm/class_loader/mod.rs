@@ -1,4 +1,8 @@
 mod bootstrap;
 pub mod resolve;
 mod array;
-mod native;
\ No newline at end of file
+pub mod invokedynamic;
+pub mod classpath;
+mod zip;
+mod native;
+pub mod verifier;
\ No newline at end of file
@@ -1,7 +1,10 @@
 use std::collections::HashMap;
 use once_cell::sync::OnceCell;
 use crate::vm::class::field::FieldType;
+use crate::VMThread;
 use crate::vm::class::method::{MethodDescriptor, NativeFnPtr};
+use crate::vm::object::ObjectPtr;
+use crate::vm::thread::thread::create_throwable_from_error;
 
 #[derive(Eq, Hash, PartialEq)]
 pub struct NativeMethodRef {
@@ -47,6 +50,118 @@ pub fn init_native_store() -> HashMap<NativeMethodRef, NativeFnPtr> {
             ret: FieldType::J
         }}, lang::parseLong);
 
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/Long".to_string(),
+        method_name: "toString".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::J],
+            ret: FieldType::L("java/lang/String".to_string())
+        }}, lang::toString_long);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/Double".to_string(),
+        method_name: "toString".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::D],
+            ret: FieldType::L("java/lang/String".to_string())
+        }}, lang::toString_double);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/Float".to_string(),
+        method_name: "toString".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::F],
+            ret: FieldType::L("java/lang/String".to_string())
+        }}, lang::toString_float);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/Character".to_string(),
+        method_name: "toString".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::C],
+            ret: FieldType::L("java/lang/String".to_string())
+        }}, lang::toString_char);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/StringBuilder".to_string(),
+        method_name: "append".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::L("java/lang/String".to_string())],
+            ret: FieldType::L("java/lang/StringBuilder".to_string())
+        }}, lang::string_builder::append_string);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/StringBuilder".to_string(),
+        method_name: "append".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::C],
+            ret: FieldType::L("java/lang/StringBuilder".to_string())
+        }}, lang::string_builder::append_char);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/StringBuilder".to_string(),
+        method_name: "append".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::I],
+            ret: FieldType::L("java/lang/StringBuilder".to_string())
+        }}, lang::string_builder::append_int);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/StringBuilder".to_string(),
+        method_name: "append".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::J],
+            ret: FieldType::L("java/lang/StringBuilder".to_string())
+        }}, lang::string_builder::append_long);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/StringBuilder".to_string(),
+        method_name: "append".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::F],
+            ret: FieldType::L("java/lang/StringBuilder".to_string())
+        }}, lang::string_builder::append_float);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/StringBuilder".to_string(),
+        method_name: "append".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::D],
+            ret: FieldType::L("java/lang/StringBuilder".to_string())
+        }}, lang::string_builder::append_double);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/StringBuilder".to_string(),
+        method_name: "charAt".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::I],
+            ret: FieldType::C
+        }}, lang::string_builder::charAt);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/StringBuilder".to_string(),
+        method_name: "setLength".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::I],
+            ret: FieldType::V
+        }}, lang::string_builder::setLength);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/StringBuilder".to_string(),
+        method_name: "reverse".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![],
+            ret: FieldType::L("java/lang/StringBuilder".to_string())
+        }}, lang::string_builder::reverse);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/StringBuilder".to_string(),
+        method_name: "toString".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![],
+            ret: FieldType::L("java/lang/String".to_string())
+        }}, lang::string_builder::toString);
+
     native_store.insert(NativeMethodRef {
         class_name: "java/lang/Math".to_string(),
         method_name: "sqrt".to_string(),
@@ -55,6 +170,103 @@ pub fn init_native_store() -> HashMap<NativeMethodRef, NativeFnPtr> {
             ret: FieldType::D
         }}, lang::math::sqrt);
 
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/Thread".to_string(),
+        method_name: "currentThread".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![],
+            ret: FieldType::L("java/lang/Thread".to_string())
+        }}, lang::thread::currentThread);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/Thread".to_string(),
+        method_name: "sleep".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::J],
+            ret: FieldType::V
+        }}, lang::thread::sleep);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/Thread".to_string(),
+        method_name: "start0".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![],
+            ret: FieldType::V
+        }}, lang::thread::start0);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/Thread".to_string(),
+        method_name: "isAlive".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![],
+            ret: FieldType::Z
+        }}, lang::thread::isAlive);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/Thread".to_string(),
+        method_name: "interrupt".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![],
+            ret: FieldType::V
+        }}, lang::thread::interrupt);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/Thread".to_string(),
+        method_name: "isInterrupted".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![],
+            ret: FieldType::Z
+        }}, lang::thread::isInterrupted);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/Thread".to_string(),
+        method_name: "interrupted".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![],
+            ret: FieldType::Z
+        }}, lang::thread::interrupted);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/Thread".to_string(),
+        method_name: "join".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::J],
+            ret: FieldType::V
+        }}, lang::thread::join);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/ClassLoader".to_string(),
+        method_name: "defineClass".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::L("java/lang/String".to_string()),
+                             FieldType::A(Box::new(FieldType::B)), FieldType::I, FieldType::I],
+            ret: FieldType::L("java/lang/Class".to_string())
+        }}, lang::class_loader::defineClass);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/ClassLoader".to_string(),
+        method_name: "findLoadedClass".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::L("java/lang/String".to_string())],
+            ret: FieldType::L("java/lang/Class".to_string())
+        }}, lang::class_loader::findLoadedClass);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/ClassLoader".to_string(),
+        method_name: "findBootstrapClass".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![FieldType::L("java/lang/String".to_string())],
+            ret: FieldType::L("java/lang/Class".to_string())
+        }}, lang::class_loader::findBootstrapClass);
+
+    native_store.insert(NativeMethodRef {
+        class_name: "java/lang/Throwable".to_string(),
+        method_name: "fillInStackTrace".to_string(),
+        descriptor: MethodDescriptor {
+            parameters: vec![],
+            ret: FieldType::L("java/lang/Throwable".to_string())
+        }}, lang::throwable::fillInStackTrace);
+
     native_store.insert(NativeMethodRef {
         class_name: "java/io/PrintStream".to_string(),
         method_name: "print".to_string(),
@@ -98,13 +310,29 @@ pub fn init_native_store() -> HashMap<NativeMethodRef, NativeFnPtr> {
     native_store
 }
 
-mod lang {
+/// The return value of a native method returning the result of an allocation, throwing the error
+/// if the allocation failed
+pub fn allocation_result(result: Result<ObjectPtr, String>, thread: &VMThread,
+                         exception: &mut Option<ObjectPtr>) -> Option<u64> {
+    match result {
+        Ok(object) => Some(object.to_val()),
+        Err(e) => {
+            *exception = Some(create_throwable_from_error(&e, thread));
+            None
+        }
+    }
+}
+
+pub mod lang {
     use smallvec::SmallVec;
 
     use crate::{VM_HANDLER, VMThread};
+    use crate::helper::{utof, utof2};
+    use crate::helper::number::{double_to_string, float_to_string};
     use crate::vm::class::method::MAX_NO_OF_ARGS;
+    use crate::vm::class_loader::native::allocation_result;
     use crate::vm::object::ObjectPtr;
-    use crate::vm::pool::string::StrArena;
+    use crate::vm::pool::string::get_string;
     use crate::vm::thread::thread::{create_throwable, create_throwable_message};
 
     #[allow(non_snake_case)]
@@ -118,14 +346,14 @@ mod lang {
             Some(val) => val
         };
 
-        let string = StrArena::get_string(string);
+        let string = get_string(string);
 
         match string.parse::<i64>() {
             Ok(val) => Some(val as u64),
             Err(e) => {
                 *exception = Some(create_throwable_message("java/lang/Exception", thread, // TODO: NumberFormatException
                                                            &e.to_string()));
-                return None;
+                None
             }
         }
     }
@@ -142,21 +370,21 @@ mod lang {
             Some(val) => val
         };
 
-        let string = StrArena::get_string(string);
+        let string = get_string(string);
 
         match string.parse::<i32>() {
             Ok(val) => Some(val as u64),
             Err(e) => {
                 *exception = Some(create_throwable_message("java/lang/Exception", thread, // TODO: NumberFormatException
                                                            &e.to_string()));
-                return None;
+                None
             }
         }
     }
 
     #[allow(non_snake_case)]
-    pub fn toString(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
-                    _: &mut Option<ObjectPtr>) -> Option<u64> {
+    pub fn toString(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                    exception: &mut Option<ObjectPtr>) -> Option<u64> {
         use std::io::Write;
 
         let mut buf: Vec<u8> = Vec::with_capacity(16);
@@ -164,9 +392,36 @@ mod lang {
 
         let str = std::str::from_utf8(&buf).unwrap();
         let vm = VM_HANDLER.get().unwrap();
-        let string = vm.string_pool.add_string(str);
+        allocation_result(vm.string_pool.add_string(Some(thread), str), thread, exception)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn toString_long(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                         exception: &mut Option<ObjectPtr>) -> Option<u64> {
+        let vm = VM_HANDLER.get().unwrap();
+        allocation_result(vm.string_pool.add_string(Some(thread), &(args[0] as i64).to_string()), thread, exception)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn toString_double(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                           exception: &mut Option<ObjectPtr>) -> Option<u64> {
+        let vm = VM_HANDLER.get().unwrap();
+        allocation_result(vm.string_pool.add_string(Some(thread), &double_to_string(utof2(args[0]))), thread, exception)
+    }
 
-        Some(string.to_val())
+    #[allow(non_snake_case)]
+    pub fn toString_float(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                          exception: &mut Option<ObjectPtr>) -> Option<u64> {
+        let vm = VM_HANDLER.get().unwrap();
+        allocation_result(vm.string_pool.add_string(Some(thread), &float_to_string(utof(args[0] as u32))), thread, exception)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn toString_char(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                         exception: &mut Option<ObjectPtr>) -> Option<u64> {
+        let vm = VM_HANDLER.get().unwrap();
+        allocation_result(vm.string_pool.new_string(Some(thread), &[args[0] as u16]), thread,
+                          exception)
     }
 
     pub mod system {
@@ -177,14 +432,21 @@ mod lang {
         use crate::{VM_HANDLER, VMThread};
         use crate::vm::class::method::MAX_NO_OF_ARGS;
         use crate::vm::object::ObjectPtr;
+        use crate::vm::thread::thread::create_throwable_from_error;
 
         #[allow(non_snake_case)]
         pub fn registerNatives(thread: &VMThread, _: SmallVec<[u64; MAX_NO_OF_ARGS]>,
-                               _: &mut Option<ObjectPtr>) -> Option<u64> {
+                               exception: &mut Option<ObjectPtr>) -> Option<u64> {
             let vm = VM_HANDLER.get().unwrap();
             let print_stream = vm.load_class("java/io/PrintStream").unwrap();
 
-            let ptr = vm.object_arena.new_object(print_stream);
+            let ptr = match thread.new_object(print_stream) {
+                Ok(ptr) => ptr,
+                Err(e) => {
+                    *exception = Some(create_throwable_from_error(&e, thread));
+                    return None;
+                }
+            };
 
             thread.stack.last().unwrap().methodref.0.data.static_fields[0].store(ptr.ptr as u64,
                                                                       Ordering::Relaxed);
@@ -193,6 +455,715 @@ mod lang {
         }
     }
 
+    pub mod string {
+        use smallvec::SmallVec;
+
+        use crate::{VM_HANDLER, VMThread};
+        use crate::vm::class::method::MAX_NO_OF_ARGS;
+        use crate::vm::class_loader::native::allocation_result;
+        use crate::vm::object::ObjectPtr;
+        use crate::vm::pool::string::{string_hash, string_value};
+        use crate::vm::thread::thread::{create_throwable, create_throwable_message};
+
+        pub fn concat(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                      exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let this = ObjectPtr::from_val(args[0]).unwrap();
+            let Some(other) = ObjectPtr::from_val(args[1]) else {
+                *exception = Some(create_throwable("java/lang/NullPointerException", thread));
+                return None;
+            };
+
+            let (a, b) = (string_value(this), string_value(other));
+            if b.char_array().is_empty() {
+                return Some(this.to_val());
+            }
+
+            let vm = VM_HANDLER.get().unwrap();
+            let chars = [a.char_array(), b.char_array()].concat();
+            allocation_result(vm.string_pool.new_string(Some(thread), &chars), thread, exception)
+        }
+
+        #[allow(non_snake_case)]
+        pub fn charAt(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                      exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let value = string_value(ObjectPtr::from_val(args[0]).unwrap());
+            let index = args[1] as i32;
+
+            match usize::try_from(index).ok().and_then(|i| value.char_array().get(i)) {
+                Some(c) => Some(*c as u64),
+                None => {
+                    *exception = Some(create_throwable_message(
+                        "java/lang/StringIndexOutOfBoundsException", thread,
+                        &format!("String index out of range: {}", index)));
+                    None
+                }
+            }
+        }
+
+        /// substring(int beginIndex)
+        pub fn substring(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                         exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let this = ObjectPtr::from_val(args[0]).unwrap();
+            let length = string_value(this).char_array().len() as i32;
+
+            substring_of(thread, this, args[1] as i32, length, exception)
+        }
+
+        /// substring(int beginIndex, int endIndex)
+        pub fn substring_range(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                               exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let this = ObjectPtr::from_val(args[0]).unwrap();
+
+            substring_of(thread, this, args[1] as i32, args[2] as i32, exception)
+        }
+
+        fn substring_of(thread: &VMThread, string: ObjectPtr, begin: i32, end: i32,
+                        exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let value = string_value(string);
+            let chars = value.char_array();
+            if begin < 0 || begin > end || end > chars.len() as i32 {
+                *exception = Some(create_throwable_message(
+                    "java/lang/StringIndexOutOfBoundsException", thread,
+                    &format!("begin {}, end {}, length {}", begin, end, chars.len())));
+                return None;
+            }
+
+            if begin == 0 && end as usize == chars.len() {
+                return Some(string.to_val());
+            }
+
+            let vm = VM_HANDLER.get().unwrap();
+            let substring = vm.string_pool.new_string(Some(thread),
+                                                      &chars[begin as usize..end as usize]);
+            allocation_result(substring, thread, exception)
+        }
+
+        /// indexOf(int ch)
+        #[allow(non_snake_case)]
+        pub fn indexOf(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                       _: &mut Option<ObjectPtr>) -> Option<u64> {
+            let value = string_value(ObjectPtr::from_val(args[0]).unwrap());
+
+            Some(index_of_char(value.char_array(), args[1] as i32, 0) as u64)
+        }
+
+        /// indexOf(int ch, int fromIndex)
+        #[allow(non_snake_case)]
+        pub fn indexOf_from(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                            _: &mut Option<ObjectPtr>) -> Option<u64> {
+            let value = string_value(ObjectPtr::from_val(args[0]).unwrap());
+            let from = (args[2] as i32).max(0) as usize;
+
+            Some(index_of_char(value.char_array(), args[1] as i32, from) as u64)
+        }
+
+        /// indexOf(String str)
+        #[allow(non_snake_case)]
+        pub fn indexOf_string(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                              exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let value = string_value(ObjectPtr::from_val(args[0]).unwrap());
+            let Some(str) = ObjectPtr::from_val(args[1]) else {
+                *exception = Some(create_throwable("java/lang/NullPointerException", thread));
+                return None;
+            };
+            let str = string_value(str);
+
+            let (chars, pattern) = (value.char_array(), str.char_array());
+            let index = if pattern.is_empty() {
+                Some(0)
+            } else {
+                chars.windows(pattern.len()).position(|w| w == pattern)
+            };
+
+            Some(index.map_or(-1, |i| i as i32) as u64)
+        }
+
+        /// Index of the first occurrence of the code point at or after `from`, or -1. Supplementary
+        /// code points are searched for as a surrogate pair.
+        fn index_of_char(chars: &[u16], ch: i32, from: usize) -> i32 {
+            if from >= chars.len() {
+                return -1;
+            }
+
+            let index = match char::from_u32(ch as u32) {
+                _ if (0..0x10000).contains(&ch) =>
+                    chars[from..].iter().position(|c| *c as i32 == ch),
+                Some(ch) => {
+                    let mut pair = [0; 2];
+                    let pair = ch.encode_utf16(&mut pair);
+                    chars[from..].windows(2).position(|w| w == pair)
+                }
+                None => None
+            };
+
+            index.map_or(-1, |i| (from + i) as i32)
+        }
+
+        #[allow(non_snake_case)]
+        pub fn hashCode(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                        _: &mut Option<ObjectPtr>) -> Option<u64> {
+            Some(string_hash(ObjectPtr::from_val(args[0]).unwrap()) as u64)
+        }
+
+        pub fn equals(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                      _: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            let this = ObjectPtr::from_val(args[0]).unwrap();
+
+            let equal = match ObjectPtr::from_val(args[1]) {
+                Some(other) if other == this => true,
+                Some(other) if other.get_class() == vm.string_class =>
+                    string_value(this).char_array() == string_value(other).char_array(),
+                _ => false
+            };
+
+            Some(equal as u64)
+        }
+
+        /// Compares the strings lexicographically by their UTF-16 code units. The result is the
+        /// difference of the first differing characters, or else of the lengths.
+        #[allow(non_snake_case)]
+        pub fn compareTo(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                         exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let this = string_value(ObjectPtr::from_val(args[0]).unwrap());
+            let Some(other) = ObjectPtr::from_val(args[1]) else {
+                *exception = Some(create_throwable("java/lang/NullPointerException", thread));
+                return None;
+            };
+            let other = string_value(other);
+
+            let (a, b) = (this.char_array(), other.char_array());
+            let result = match a.iter().zip(b).find(|(c1, c2)| c1 != c2) {
+                Some((c1, c2)) => *c1 as i32 - *c2 as i32,
+                None => a.len() as i32 - b.len() as i32
+            };
+
+            Some(result as u64)
+        }
+
+        /// Compares the strings code point by code point, considering them equal if they are the
+        /// same after converting both to upper case or both to lower case
+        #[allow(non_snake_case)]
+        pub fn equalsIgnoreCase(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                                _: &mut Option<ObjectPtr>) -> Option<u64> {
+            let this = string_value(ObjectPtr::from_val(args[0]).unwrap());
+            let Some(other) = ObjectPtr::from_val(args[1]) else {
+                return Some(false as u64);
+            };
+            let other = string_value(other);
+
+            let (a, b) = (this.char_array(), other.char_array());
+            let equal = a.len() == b.len() && char::decode_utf16(a.iter().copied())
+                .zip(char::decode_utf16(b.iter().copied()))
+                .all(|pair| match pair {
+                    (Ok(c1), Ok(c2)) => c1 == c2 || {
+                        let upper = (to_upper_case(c1), to_upper_case(c2));
+                        upper.0 == upper.1 || to_lower_case(upper.0) == to_lower_case(upper.1)
+                    },
+                    (Err(s1), Err(s2)) => s1.unpaired_surrogate() == s2.unpaired_surrogate(),
+                    _ => false
+                });
+
+            Some(equal as u64)
+        }
+
+        /// Character.toUpperCase(int): the single character mapping, the character itself if
+        /// it only has a multi-character one (like U+00DF)
+        fn to_upper_case(c: char) -> char {
+            let mut upper = c.to_uppercase();
+            match (upper.next(), upper.next()) {
+                (Some(u), None) => u,
+                _ => c
+            }
+        }
+
+        /// Character.toLowerCase(int)
+        fn to_lower_case(c: char) -> char {
+            let mut lower = c.to_lowercase();
+            match (lower.next(), lower.next()) {
+                (Some(l), None) => l,
+                // U+0130 is the only character with a multi-character lower case mapping
+                _ => 'i'
+            }
+        }
+
+        #[allow(non_snake_case)]
+        pub fn toString(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                        _: &mut Option<ObjectPtr>) -> Option<u64> {
+            Some(args[0])
+        }
+
+        pub fn intern(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                      _: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            let this = ObjectPtr::from_val(args[0]).unwrap();
+
+            Some(vm.string_pool.intern(this).to_val())
+        }
+    }
+
+    pub mod string_builder {
+        use smallvec::SmallVec;
+
+        use crate::{VM_HANDLER, VMThread};
+        use crate::helper::{utof, utof2};
+        use crate::helper::number::{double_to_string, float_to_string};
+        use crate::vm::class::method::MAX_NO_OF_ARGS;
+        use crate::vm::object::ObjectPtr;
+        use crate::vm::class_loader::native::allocation_result;
+        use crate::vm::pool::string::string_value;
+        use crate::vm::thread::thread::{create_throwable_from_error, create_throwable_message};
+
+        /// Index of the char[] field of java/lang/StringBuilder
+        const VALUE_FIELD: usize = 0;
+        /// Index of the int field of java/lang/StringBuilder holding the number of used chars
+        const COUNT_FIELD: usize = 1;
+
+        /// Returns the char array of the builder, replacing it with a larger one first if it has
+        /// less room than `minimum`. It grows the same way as in the JDK, to twice the size + 2.
+        fn ensure_capacity(thread: &VMThread, builder: ObjectPtr, minimum: usize)
+            -> Result<ObjectPtr, String> {
+            let vm = VM_HANDLER.get().unwrap();
+            let value = ObjectPtr::from_val(builder.get_field(VALUE_FIELD)).unwrap();
+            let capacity = value.char_array().len();
+            if minimum <= capacity {
+                return Ok(value);
+            }
+
+            let count = builder.get_field(COUNT_FIELD) as usize;
+            let grown = thread.new_array(vm.load_class("[C")?, (2 * capacity + 2).max(minimum))?;
+            grown.set_chars(0, &value.char_array()[..count]);
+            builder.put_field(VALUE_FIELD, grown.to_val());
+            Ok(grown)
+        }
+
+        fn append(thread: &VMThread, builder: ObjectPtr, chars: &[u16])
+            -> Result<ObjectPtr, String> {
+            let count = builder.get_field(COUNT_FIELD) as usize;
+            let value = ensure_capacity(thread, builder, count + chars.len())?;
+
+            value.set_chars(count, chars);
+            builder.put_field(COUNT_FIELD, (count + chars.len()) as u64);
+            Ok(builder)
+        }
+
+        fn append_str(thread: &VMThread, builder: ObjectPtr, str: &str)
+            -> Result<ObjectPtr, String> {
+            append(thread, builder, &str.encode_utf16().collect::<Vec<_>>())
+        }
+
+        /// append(String), appending "null" for null
+        pub fn append_string(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                             exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let builder = ObjectPtr::from_val(args[0]).unwrap();
+            let result = match ObjectPtr::from_val(args[1]) {
+                Some(string) => append(thread, builder, string_value(string).char_array()),
+                None => append_str(thread, builder, "null")
+            };
+            allocation_result(result, thread, exception)
+        }
+
+        pub fn append_char(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                           exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let builder = ObjectPtr::from_val(args[0]).unwrap();
+            allocation_result(append(thread, builder, &[args[1] as u16]), thread, exception)
+        }
+
+        pub fn append_int(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                          exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let builder = ObjectPtr::from_val(args[0]).unwrap();
+            allocation_result(append_str(thread, builder, &(args[1] as i32).to_string()), thread,
+                              exception)
+        }
+
+        pub fn append_long(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                           exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let builder = ObjectPtr::from_val(args[0]).unwrap();
+            allocation_result(append_str(thread, builder, &(args[1] as i64).to_string()), thread,
+                              exception)
+        }
+
+        pub fn append_float(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                            exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let builder = ObjectPtr::from_val(args[0]).unwrap();
+            let string = float_to_string(utof(args[1] as u32));
+            allocation_result(append_str(thread, builder, &string), thread, exception)
+        }
+
+        pub fn append_double(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                             exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let builder = ObjectPtr::from_val(args[0]).unwrap();
+            let string = double_to_string(utof2(args[1]));
+            allocation_result(append_str(thread, builder, &string), thread, exception)
+        }
+
+        #[allow(non_snake_case)]
+        pub fn charAt(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                      exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let builder = ObjectPtr::from_val(args[0]).unwrap();
+            let value = ObjectPtr::from_val(builder.get_field(VALUE_FIELD)).unwrap();
+            let count = builder.get_field(COUNT_FIELD) as usize;
+            let index = args[1] as i32;
+
+            match usize::try_from(index).ok().filter(|i| *i < count) {
+                Some(i) => Some(value.char_array()[i] as u64),
+                None => {
+                    *exception = Some(create_throwable_message(
+                        "java/lang/StringIndexOutOfBoundsException", thread,
+                        &format!("index {}, length {}", index, count)));
+                    None
+                }
+            }
+        }
+
+        /// Truncates the builder or extends it with '\0' characters
+        #[allow(non_snake_case)]
+        pub fn setLength(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                         exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let builder = ObjectPtr::from_val(args[0]).unwrap();
+            let count = builder.get_field(COUNT_FIELD) as usize;
+            let Ok(length) = usize::try_from(args[1] as i32) else {
+                *exception = Some(create_throwable_message(
+                    "java/lang/StringIndexOutOfBoundsException", thread,
+                    &format!("String index out of range: {}", args[1] as i32)));
+                return None;
+            };
+
+            if length > count {
+                match ensure_capacity(thread, builder, length) {
+                    Ok(value) => value.set_chars(count, &vec![0; length - count]),
+                    Err(e) => {
+                        *exception = Some(create_throwable_from_error(&e, thread));
+                        return None;
+                    }
+                }
+            }
+            builder.put_field(COUNT_FIELD, length as u64);
+
+            None
+        }
+
+        /// Reverses the characters, keeping the order of the two halves of surrogate pairs
+        pub fn reverse(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                       _: &mut Option<ObjectPtr>) -> Option<u64> {
+            let builder = ObjectPtr::from_val(args[0]).unwrap();
+            let value = ObjectPtr::from_val(builder.get_field(VALUE_FIELD)).unwrap();
+            let count = builder.get_field(COUNT_FIELD) as usize;
+
+            let mut chars = value.char_array()[..count].to_vec();
+            chars.reverse();
+            let is_high = |c: u16| (0xD800..0xDC00).contains(&c);
+            let is_low = |c: u16| (0xDC00..0xE000).contains(&c);
+            let mut i = 0;
+            while i + 1 < chars.len() {
+                if is_low(chars[i]) && is_high(chars[i + 1]) {
+                    chars.swap(i, i + 1);
+                    i += 1;
+                }
+                i += 1;
+            }
+            value.set_chars(0, &chars);
+
+            Some(builder.to_val())
+        }
+
+        #[allow(non_snake_case)]
+        pub fn toString(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                        exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            let builder = ObjectPtr::from_val(args[0]).unwrap();
+            let value = ObjectPtr::from_val(builder.get_field(VALUE_FIELD)).unwrap();
+            let count = builder.get_field(COUNT_FIELD) as usize;
+
+            let string = vm.string_pool.new_string(Some(thread), &value.char_array()[..count]);
+            allocation_result(string, thread, exception)
+        }
+    }
+
+    pub mod object {
+        use std::time::Duration;
+
+        use smallvec::SmallVec;
+
+        use crate::{VM_HANDLER, VMThread};
+        use crate::vm::class::method::MAX_NO_OF_ARGS;
+        use crate::vm::object::ObjectPtr;
+        use crate::vm::thread::registry::{ThreadRegistry, Wakeup};
+        use crate::vm::thread::thread::create_throwable_message;
+
+        /// Object.wait() and Object.wait(long)
+        pub fn wait(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                    exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            let object = ObjectPtr::from_val(args[0]).unwrap();
+            let millis = args.get(1).map_or(0, |m| *m as i64);
+
+            let timeout = match millis {
+                0 => None,
+                1.. => Some(Duration::from_millis(millis as u64)),
+                _ => {
+                    *exception = Some(create_throwable_message(
+                        "java/lang/IllegalArgumentException", thread, "timeout value is negative"));
+                    return None;
+                }
+            };
+
+            let state = ThreadRegistry::current_state();
+            match thread.blocking(|| vm.wait_sets.wait(object, &state, timeout)) {
+                Err(()) => {
+                    *exception = Some(create_throwable_message(
+                        "java/lang/IllegalMonitorStateException", thread,
+                        "current thread is not owner"));
+                }
+                Ok(Wakeup::Interrupted) => {
+                    state.is_interrupted(true);
+                    *exception = Some(create_throwable_message("java/lang/InterruptedException",
+                                                               thread, "wait interrupted"));
+                }
+                Ok(_) => {}
+            }
+
+            None
+        }
+
+        pub fn notify(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                      exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            notify_waiters(thread, args[0], false, exception)
+        }
+
+        #[allow(non_snake_case)]
+        pub fn notifyAll(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                         exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            notify_waiters(thread, args[0], true, exception)
+        }
+
+        fn notify_waiters(thread: &VMThread, object: u64, all: bool,
+                          exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            let object = ObjectPtr::from_val(object).unwrap();
+
+            if vm.wait_sets.notify(object, all).is_err() {
+                *exception = Some(create_throwable_message(
+                    "java/lang/IllegalMonitorStateException", thread,
+                    "current thread is not owner"));
+            }
+
+            None
+        }
+    }
+
+    pub mod thread {
+        use std::time::{Duration, Instant};
+
+        use smallvec::SmallVec;
+
+        use crate::{VM_HANDLER, VMThread};
+        use crate::vm::class::method::MAX_NO_OF_ARGS;
+        use crate::vm::class_loader::native::allocation_result;
+        use crate::vm::object::ObjectPtr;
+        use crate::vm::thread::registry::{ThreadRegistry, Wakeup};
+        use crate::vm::thread::thread::{create_throwable, create_throwable_message};
+
+        #[allow(non_snake_case)]
+        pub fn currentThread(thread: &VMThread, _: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                             exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            allocation_result(vm.threads.current(thread), thread, exception)
+        }
+
+        pub fn sleep(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                     exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let millis = args[0] as i64;
+            if millis < 0 {
+                *exception = Some(create_throwable_message("java/lang/IllegalArgumentException",
+                                                           thread, "timeout value is negative"));
+                return None;
+            }
+
+            let state = ThreadRegistry::current_state();
+            state.reset_notified();
+
+            let deadline = Instant::now() + Duration::from_millis(millis as u64);
+            if thread.blocking(|| state.park(Some(deadline))) == Wakeup::Interrupted {
+                state.is_interrupted(true);
+                *exception = Some(create_throwable_message("java/lang/InterruptedException",
+                                                           thread, "sleep interrupted"));
+            }
+            None
+        }
+
+        pub fn start0(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                      exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            let object = ObjectPtr::from_val(args[0]).unwrap();
+
+            if let Err(e) = vm.threads.start(object) {
+                *exception = Some(create_throwable_message("java/lang/Error", thread, &e));
+            }
+            None
+        }
+
+        #[allow(non_snake_case)]
+        pub fn isAlive(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                       _: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            let object = ObjectPtr::from_val(args[0]).unwrap();
+
+            Some(vm.threads.is_alive(object) as u64)
+        }
+
+        pub fn join(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                    exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            let object = ObjectPtr::from_val(args[0]).unwrap();
+            let millis = args[1] as i64;
+
+            let timeout = match millis {
+                0 => None,
+                1.. => Some(Duration::from_millis(millis as u64)),
+                _ => {
+                    *exception = Some(create_throwable_message(
+                        "java/lang/IllegalArgumentException", thread, "timeout value is negative"));
+                    return None;
+                }
+            };
+
+            if thread.blocking(|| vm.threads.join(object, timeout)) == Wakeup::Interrupted {
+                ThreadRegistry::current_state().is_interrupted(true);
+                *exception = Some(create_throwable("java/lang/InterruptedException", thread));
+            }
+            None
+        }
+
+        pub fn interrupt(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                         _: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            let object = ObjectPtr::from_val(args[0]).unwrap();
+
+            vm.threads.interrupt(object);
+            None
+        }
+
+        #[allow(non_snake_case)]
+        pub fn isInterrupted(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                             _: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            let object = ObjectPtr::from_val(args[0]).unwrap();
+
+            Some(vm.threads.is_interrupted(object) as u64)
+        }
+
+        /// Returns and clears the interrupt status of the current thread
+        pub fn interrupted(_: &VMThread, _: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                           _: &mut Option<ObjectPtr>) -> Option<u64> {
+            Some(ThreadRegistry::current_state().is_interrupted(true) as u64)
+        }
+    }
+
+    pub mod class_loader {
+        use smallvec::SmallVec;
+
+        use crate::{VM_HANDLER, VMThread};
+        use crate::vm::class::method::MAX_NO_OF_ARGS;
+        use crate::vm::object::ObjectPtr;
+        use crate::vm::pool::string::get_string;
+        use crate::vm::class_loader::bootstrap::internal_class_name;
+        use crate::vm::thread::thread::{create_throwable, create_throwable_from_error};
+
+        #[allow(non_snake_case)]
+        pub fn defineClass(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                           exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            let class_loader = ObjectPtr::from_val(args[0]).unwrap();
+            let name = ObjectPtr::from_val(args[1]).map(get_string);
+            let bytes = match ObjectPtr::from_val(args[2]) {
+                None => {
+                    *exception = Some(create_throwable("java/lang/NullPointerException", thread));
+                    return None;
+                }
+                Some(val) => val
+            };
+            let (offset, length) = (args[3] as i32, args[4] as i32);
+
+            if offset < 0 || length < 0 || offset as i64 + length as i64 > bytes.get_field(0) as i64 {
+                *exception = Some(create_throwable("java/lang/ArrayIndexOutOfBoundsException",
+                                                   thread));
+                return None;
+            }
+
+            let buf = &bytes.byte_array()[offset as usize..(offset + length) as usize];
+
+            match vm.define_class(class_loader, name.as_deref(), buf) {
+                Ok(class) => Some(class.ptr() as u64),
+                Err(e) => {
+                    *exception = Some(create_throwable_from_error(&e, thread));
+                    None
+                }
+            }
+        }
+
+        /// Returns the class if the loader has been recorded as its initiating loader
+        #[allow(non_snake_case)]
+        pub fn findLoadedClass(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                               _: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            let class_loader = ObjectPtr::from_val(args[0]).unwrap();
+            let name = match ObjectPtr::from_val(args[1]) {
+                None => return Some(0),
+                Some(name) => get_string(name)
+            };
+
+            let class = vm.find_loaded_class(class_loader, &internal_class_name(&name));
+            Some(class.map_or(0, |class| class.ptr() as u64))
+        }
+
+        /// Loads the class with the bootstrap class loader, returning null if it could not
+        #[allow(non_snake_case)]
+        pub fn findBootstrapClass(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                                  _: &mut Option<ObjectPtr>) -> Option<u64> {
+            let vm = VM_HANDLER.get().unwrap();
+            let name = match ObjectPtr::from_val(args[0]) {
+                None => return Some(0),
+                Some(name) => get_string(name)
+            };
+
+            Some(vm.load_class(&name).map_or(0, |class| class.ptr() as u64))
+        }
+    }
+
+    pub mod throwable {
+        use smallvec::SmallVec;
+
+        use crate::VMThread;
+        use crate::vm::class::method::MAX_NO_OF_ARGS;
+        use crate::vm::class_loader::native::allocation_result;
+        use crate::vm::object::ObjectPtr;
+        use crate::vm::thread::thread::fill_in_stack_trace;
+
+        /// Records the current stack, leaving out the frames of this method and of the
+        /// constructors of the throwable
+        #[allow(non_snake_case)]
+        pub fn fillInStackTrace(thread: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
+                                exception: &mut Option<ObjectPtr>) -> Option<u64> {
+            let throwable = ObjectPtr::from_val(args[0]).unwrap();
+
+            let mut depth = thread.stack.len();
+            while depth > 1 {
+                let (class, method) = thread.stack[depth - 1].methodref;
+                let method = &class.data.methods[method];
+                if method.name == "fillInStackTrace"
+                    || method.name == "<init>" && throwable.get_class().is_subclass(class) {
+                    depth -= 1;
+                } else {
+                    break;
+                }
+            }
+
+            let result = fill_in_stack_trace(throwable, thread, depth).map(|_| throwable);
+            allocation_result(result, thread, exception)
+        }
+    }
+
     pub mod math {
         use smallvec::SmallVec;
 
@@ -212,14 +1183,16 @@ mod lang {
 mod io {
     use smallvec::SmallVec;
     use crate::{VMThread};
-    use crate::helper::{utof2};
+    use crate::helper::utof2;
+    use crate::helper::number::double_to_string;
     use crate::vm::class::method::MAX_NO_OF_ARGS;
     use crate::vm::object::ObjectPtr;
-    use crate::vm::pool::string::StrArena;
+    use crate::vm::pool::string::get_string;
 
     pub fn print_char(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
                      _: &mut Option<ObjectPtr>) -> Option<u64> {
-        print!("{}", args[1] as u8 as char);
+        // A lone UTF-16 unit that is a surrogate can not be printed on its own
+        print!("{}", char::from_u32(args[1] as u16 as u32).unwrap_or(char::REPLACEMENT_CHARACTER));
         None
     }
 
@@ -237,16 +1210,7 @@ mod io {
 
     pub fn print_double(_: &VMThread, args: SmallVec<[u64; MAX_NO_OF_ARGS]>,
                         _: &mut Option<ObjectPtr>) -> Option<u64> {
-        use std::io::Write;
-
-        let mut buf: Vec<u8> = Vec::with_capacity(20);
-        let _ = write!(&mut buf, "{}", utof2(args[1]));
-        let str = std::str::from_utf8(&buf).unwrap();
-        if let None = str.find(".") {
-            print!("{}.0", str);
-        } else {
-            print!("{}", str);
-        }
+        print!("{}", double_to_string(utof2(args[1])));
         None
     }
 
@@ -256,7 +1220,7 @@ mod io {
         let str = args[1];
         match ObjectPtr::from_val(str) {
             None => print!("null"),
-            Some(str) => print!("{}", StrArena::get_string(str))
+            Some(str) => print!("{}", get_string(str))
         }
 
         None
@@ -1,12 +1,20 @@
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::Ordering;
 use smallvec::smallvec;
-use crate::{Class, ClassRef, VM_HANDLER, VMThread};
-use crate::ThreadStatus::{FAILED, FINISHED};
-use crate::vm::class::class::ClassState;
-use crate::vm::class::class::ClassState::{Initializing, Ready, Verified};
-use crate::vm::class::constant_pool::{CPEntry, SymbolicReference, UnresolvedReference};
-
-use crate::vm::class::constant_pool::UnresolvedReference::{ClassReference, FieldReference, InterfaceMethodReference, MethodReference};
+use crate::{ClassRef, VM_HANDLER, VMThread};
+use crate::ThreadStatus::{FAILED, FINISHED, RUNNING};
+use crate::vm::class::class::ClassState::{Erroneous, Initializing, Loaded, Ready, Verified};
+use crate::vm::class::constant_pool::{CPEntry, SymbolicReference};
+
+use crate::vm::class::constant_pool::UnresolvedReference::{ClassReference, DynamicReference, FieldReference, InterfaceMethodReference, InvokeDynamicReference, MethodReference};
+use crate::vm::class_loader::invokedynamic::link_call_site;
+use crate::vm::class_loader::verifier::PendingVerification;
 use crate::vm::class::field::FieldType;
+use crate::vm::class::method::MethodDescriptor;
+use crate::class_parser::constants::{AccessFlagClass, AccessFlagMethod};
+use crate::helper::has_flag;
+use crate::vm::object::ObjectPtr;
+use crate::vm::thread::thread::MethodRef;
 
 type Exception = String;
 
@@ -19,144 +27,259 @@ pub fn resolve(class: ClassRef, index: usize) -> Result<(), Exception> {
         UnresolvedSymbolicReference(ClassReference(name)) => {
             let vm = VM_HANDLER.get().unwrap();
 
-            let mut thread = VMThread::new();
-            let classloader = vm.classloader;
-
-            let ptr = vm.string_pool.intern_string(name);
-
-            // Should be done by the defining loader of class, but currently we only support a
-            // bootstrap class loader
-            // TODO: Access control
-            thread.start((classloader, 0), smallvec![0, ptr.ptr as u64]);
-            match thread.status {
-                FINISHED(Some(class_ptr)) => {
-                    let class_ref = ClassRef::new(class_ptr as *const Class);
-                    class.set_cp_entry(index, CPEntry::ResolvedSymbolicReference
-                        (SymbolicReference::ClassReference(class_ref)));
-                },
-                FAILED(e) => return Err(e),
-                _ => panic!("Can't happen")
-            }
+            // The defining loader of the referencing class is the initiating loader
+            let class_ref = vm.load_referenced_class(class.data.class_loader, name)?;
+            check_class_access(class, class_ref)?;
+            class.set_cp_entry(index, CPEntry::ResolvedSymbolicReference
+                (SymbolicReference::ClassReference(class_ref)));
 
             Ok(())
         },
-        UnresolvedSymbolicReference(method @ MethodReference(class_index, _name, _descriptor)) => {
-            resolve(class, *class_index as usize)?;
-
-            match class.get_cp_entry(*class_index as usize) {
-                ResolvedSymbolicReference(SymbolicReference::ClassReference(other_class)) => {
-                    if other_class.is_interface() {
-                        return Err(format!("Method reference's class should not be an interface \
-                        in {:?}", method));
-                    }
+        UnresolvedSymbolicReference(MethodReference(class_index, name, descriptor)) => {
+            let other_class = referenced_class(class, *class_index)?;
+            if other_class.is_interface() {
+                return Err(format!("IncompatibleClassChangeError: Found interface {}, but class \
+                    was expected", other_class.data.name.replace('/', ".")));
+            }
 
-                    let res = resolve_method(*other_class, method, false)?;
+            let res = resolve_method(other_class, name, descriptor, false)?;
+            check_method_access(class, other_class, &res)?;
 
-                    class.set_cp_entry(index, ResolvedSymbolicReference(res));
+            class.set_cp_entry(index, ResolvedSymbolicReference(res));
 
-                    Ok(())
-                }
-                _ => panic!()
-            }
+            Ok(())
         },
         UnresolvedSymbolicReference(FieldReference(class_index, name, descriptor)) => {
-            resolve(class, *class_index as usize)?;
-
-            match class.get_cp_entry(*class_index as usize) {
-                ResolvedSymbolicReference(SymbolicReference::ClassReference(other_class)) => {
-                    let res = resolve_field(*other_class, name, descriptor);
-
-                    match res {
-                        Ok(res) => {
-                            class.set_cp_entry(index, ResolvedSymbolicReference(res));
-                        }
-                        Err(exc) => panic!("{}", exc)
-                    }
-                }
-                _ => panic!()
+            let other_class = referenced_class(class, *class_index)?;
+
+            let res = resolve_field(other_class, name, descriptor)?;
+            if let SymbolicReference::FieldReference(declaring, instance, i) = res {
+                let field = declaring.resolved_field(instance, i);
+                check_member_access(class, other_class, declaring, field.flag, field.is_static())
+                    .map_err(|access| format!("IllegalAccessError: class {} tried to access \
+                        {}field {}.{}", class.data.name.replace('/', "."), access,
+                        declaring.data.name.replace('/', "."), field.name))?;
             }
+            class.set_cp_entry(index, ResolvedSymbolicReference(res));
 
             Ok(())
         },
-        UnresolvedSymbolicReference(method @ InterfaceMethodReference(class_index, _name,
-            _descriptor))
-        => {
-            resolve(class, *class_index as usize)?;
-
-            match class.get_cp_entry(*class_index as usize) {
-                ResolvedSymbolicReference(SymbolicReference::ClassReference(other_class)) => {
-                    if !other_class.is_interface() {
-                        return Err(format!("Interface method reference's class should be an \
-                        interface in {:?}", method)); // IncompatibleClassChangeError
-                    }
+        UnresolvedSymbolicReference(InterfaceMethodReference(class_index, name, descriptor)) => {
+            let other_class = referenced_class(class, *class_index)?;
+            if !other_class.is_interface() {
+                return Err(format!("IncompatibleClassChangeError: Found class {}, but interface \
+                    was expected", other_class.data.name.replace('/', ".")));
+            }
 
-                    let res = resolve_interface_method(*other_class, method)?;
-                    match res {
-                        SymbolicReference::ClassReference(_) => {}
-                        SymbolicReference::MethodReference(c, ind) => {
-                            println!("{:?} {}", c.data, ind)
-                        }
-                        SymbolicReference::FieldReference(_, _, _) => {}
-                    }
-                    class.set_cp_entry(index, ResolvedSymbolicReference(res));
+            let res = resolve_interface_method(other_class, name, descriptor)?;
+            check_method_access(class, other_class, &res)?;
+            class.set_cp_entry(index, ResolvedSymbolicReference(res));
 
-                    Ok(())
-                }
-                _ => panic!()
-            }
+            Ok(())
         }
+        UnresolvedSymbolicReference(InvokeDynamicReference(bootstrap_index, name, descriptor)) => {
+            let call_site = link_call_site(class, *bootstrap_index, name, descriptor)?;
+            class.set_cp_entry(index, ResolvedSymbolicReference(SymbolicReference::CallSite
+                (call_site)));
+
+            Ok(())
+        }
+        UnresolvedSymbolicReference(DynamicReference(bootstrap_index, name, descriptor)) =>
+            Err(format!("LinkageError: Dynamically-computed constants are not supported: {} \
+                {:?} of bootstrap method {}", name, descriptor, bootstrap_index)),
         _ => Ok(())
     }
 }
 
-fn resolve_method(class: ClassRef, method: &UnresolvedReference, superclass: bool) ->
-                                                                Result<SymbolicReference, Exception> {
-    match method {
-        MethodReference(_, name, descriptor) => {
-            // signature-polymorph methods first
-            if let Some((_, i)) = class.find_method(name, descriptor) {
-                return Ok(SymbolicReference::MethodReference(class, i));
-            }
+/// Resolves the class of a member reference, which is given by the index of its class reference
+fn referenced_class(class: ClassRef, class_index: u16) -> Result<ClassRef, Exception> {
+    resolve(class, class_index as usize)?;
 
-            if !class.data.superclass.ptr().is_null() {
-                if let Ok(res) = resolve_method(class.data.superclass, method, true) {
-                    return Ok(res)
-                }
-            }
+    match class.get_cp_entry(class_index as usize) {
+        CPEntry::ResolvedSymbolicReference(SymbolicReference::ClassReference(other_class)) =>
+            Ok(*other_class),
+        _ => Err(format!("ClassFormatError: Invalid class reference {} in class {}", class_index,
+                         class.data.name.replace('/', ".")))
+    }
+}
 
-            if superclass {
-                return Err(format!("No method found"));
-            }
+fn resolve_method(class: ClassRef, name: &str, descriptor: &MethodDescriptor, superclass: bool)
+    -> Result<SymbolicReference, Exception> {
+    // signature-polymorph methods first
+    if let Some((_, i)) = class.find_method(name, descriptor) {
+        return Ok(SymbolicReference::MethodReference(class, i));
+    }
+
+    if !class.data.superclass.ptr().is_null() {
+        if let Ok(res) = resolve_method(class.data.superclass, name, descriptor, true) {
+            return Ok(res)
+        }
+    }
 
-            // TODO: Implement interface lookup
+    if superclass {
+        return Err("No method found".to_string());
+    }
+
+    if let Some(res) = resolve_superinterface_method(class, name, descriptor) {
+        return Ok(res);
+    }
 
-            Err(format!("No method found: {:?}", method))
+    Err(format!("NoSuchMethodError: {}", descriptor.describe(&class.data.name, name)))
+}
+
+fn resolve_interface_method(class: ClassRef, name: &str, descriptor: &MethodDescriptor)
+    -> Result<SymbolicReference, Exception> {
+    // signature-polymorph methods first
+    if let Some((_, i)) = class.find_method(name, descriptor) {
+        return Ok(SymbolicReference::MethodReference(class, i));
+    }
+
+    let vm = VM_HANDLER.get().unwrap();
+    if let Some((m, i)) = vm.object_class.find_method(name, descriptor) {
+        let method = &m.data.methods[i];
+        if method.is_public() && !method.is_static() {
+            return Ok(SymbolicReference::MethodReference(vm.object_class, i));
         }
-        _ => panic!()
     }
+
+    if let Some(res) = resolve_superinterface_method(class, name, descriptor) {
+        return Ok(res);
+    }
+
+    Err(format!("NoSuchMethodError: {}", descriptor.describe(&class.data.name, name)))
 }
 
-fn resolve_interface_method(class: ClassRef, method: &UnresolvedReference) -> Result<SymbolicReference, Exception> {
-    match method {
-        InterfaceMethodReference(_, name, descriptor) => {
-            // signature-polymorph methods first
-            if let Some((_, i)) = class.find_method(name, descriptor) {
-                return Ok(SymbolicReference::MethodReference(class, i));
-            }
+/// Checks that class `d` may access class `c` (JVMS §5.4.4). Array classes are accessible if their
+/// element type is.
+fn check_class_access(d: ClassRef, c: ClassRef) -> Result<(), Exception> {
+    if c.is_array() {
+        let vm = VM_HANDLER.get().unwrap();
+        let element = c.data.name.trim_start_matches('[');
+        if element.len() == 1 {
+            return Ok(()); // Primitive type
+        }
 
-            let vm = VM_HANDLER.get().unwrap();
-            if let Some((m, i)) = vm.object_class.find_method(name, descriptor) {
-                let method = &m.data.methods[i];
-                if method.is_public() && !method.is_static() {
-                    return Ok(SymbolicReference::MethodReference(vm.object_class, i));
-                }
-            }
+        return match vm.find_loaded_class(c.data.class_loader, element) {
+            Some(element) => check_class_access(d, element),
+            None => Ok(())
+        };
+    }
 
-            // TODO: maximally-specific superinterface method
+    if has_flag(c.data.flag, AccessFlagClass::ACC_PUBLIC) || c.get_package() == d.get_package() {
+        Ok(())
+    } else {
+        Err(format!("IllegalAccessError: failed to access class {} from class {}",
+                    c.data.name.replace('/', "."), d.data.name.replace('/', ".")))
+    }
+}
 
-            Err(format!("No interface method found: {:?}", method))
+fn check_method_access(d: ClassRef, t: ClassRef, res: &SymbolicReference)
+    -> Result<(), Exception> {
+    if let SymbolicReference::MethodReference(declaring, i) = res {
+        let method = &declaring.data.methods[*i];
+        // The clone method of arrays is public, unlike that of Object
+        if t.is_array() && method.name == "clone" {
+            return Ok(());
         }
-        _ => panic!()
+
+        check_member_access(d, t, *declaring, method.flag, method.is_static())
+            .map_err(|access| format!("IllegalAccessError: class {} tried to access {}method {}",
+                                      d.data.name.replace('/', "."), access,
+                                      method.descriptor.describe(&declaring.data.name,
+                                                                 &method.name)))?;
+    }
+
+    Ok(())
+}
+
+/// Checks that class `d` may access a field or method with the given access flags declared in
+/// class `c` and referenced through class `t` (JVMS §5.4.4). Returns the access modifier of the
+/// member for the error message if it may not.
+fn check_member_access(d: ClassRef, t: ClassRef, c: ClassRef, flag: u16, is_static: bool)
+    -> Result<(), &'static str> {
+    // Fields and methods share the values of these flags
+    let same_package = c.get_package() == d.get_package();
+    if has_flag(flag, AccessFlagMethod::ACC_PUBLIC) {
+        Ok(())
+    } else if has_flag(flag, AccessFlagMethod::ACC_PROTECTED) {
+        let subclass = d.is_subclass(c)
+            && (is_static || t.is_subclass(d) || d.is_subclass(t));
+        if subclass || same_package { Ok(()) } else { Err("protected ") }
+    } else if has_flag(flag, AccessFlagMethod::ACC_PRIVATE) {
+        if d == c || nest_host(d) == nest_host(c) { Ok(()) } else { Err("private ") }
+    } else if same_package {
+        Ok(())
+    } else {
+        Err("")
+    }
+}
+
+/// Determines the host of the nest the class belongs to (JVMS §5.4.4). A class whose claimed
+/// host can not be loaded, is in another run-time package or does not list it as a member is the
+/// host of its own nest.
+fn nest_host(class: ClassRef) -> ClassRef {
+    let Some(name) = &class.data.nest_host else {
+        return class;
+    };
+
+    let vm = VM_HANDLER.get().unwrap();
+    match vm.load_referenced_class(class.data.class_loader, name) {
+        Ok(host) if host.get_package() == class.get_package()
+            && host.data.nest_members.contains(&class.data.name) => host,
+        _ => class
+    }
+}
+
+/// Chooses among the maximally-specific superinterface methods: the only non-abstract one if
+/// there is exactly one, otherwise an arbitrary one (JVMS §5.4.3.3)
+fn resolve_superinterface_method(class: ClassRef, name: &str, descriptor: &MethodDescriptor)
+    -> Option<SymbolicReference> {
+    let candidates = maximally_specific_methods(class, name, descriptor);
+
+    let mut non_abstract = candidates.iter()
+        .filter(|(c, i)| !c.data.methods[*i].is_abstract());
+    let res = match (non_abstract.next(), non_abstract.next()) {
+        (Some(res), None) => Some(res),
+        _ => candidates.first()
+    };
+
+    res.map(|(c, i)| SymbolicReference::MethodReference(*c, *i))
+}
+
+/// Collects the non-private, non-static methods with the given name and descriptor declared in
+/// the superinterfaces of the class, for which no subinterface declares one as well
+pub fn maximally_specific_methods(class: ClassRef, name: &str, descriptor: &MethodDescriptor)
+    -> Vec<MethodRef> {
+    let mut interfaces = vec![];
+    collect_superinterfaces(class, &mut interfaces);
+
+    let candidates: Vec<MethodRef> = interfaces.iter()
+        .filter_map(|i| i.find_method(name, descriptor))
+        .filter(|(c, i)| {
+            let method = &c.data.methods[*i];
+            !method.is_private() && !method.is_static()
+        })
+        .collect();
+
+    candidates.iter()
+        .filter(|(c, _)| !candidates.iter()
+            .any(|(other, _)| other != c && other.is_subclass(*c)))
+        .copied()
+        .collect()
+}
+
+/// Collects every direct and indirect superinterface of the class, including those of its
+/// superclasses
+fn collect_superinterfaces(class: ClassRef, interfaces: &mut Vec<ClassRef>) {
+    for interface in &class.data.interfaces {
+        if !interfaces.contains(interface) {
+            interfaces.push(*interface);
+            collect_superinterfaces(*interface, interfaces);
+        }
+    }
+
+    if !class.data.superclass.ptr().is_null() {
+        collect_superinterfaces(class.data.superclass, interfaces);
     }
 }
 
@@ -168,7 +291,7 @@ fn resolve_field(class: ClassRef, name: &str, descriptor: &FieldType) -> Result<
     let mut field= None;
 
     for f in &class.data.fields {
-        if &f.name == name && &f.descriptor == descriptor {
+        if f.name == name && &f.descriptor == descriptor {
             field = Some(f);
             break;
         }
@@ -180,13 +303,12 @@ fn resolve_field(class: ClassRef, name: &str, descriptor: &FieldType) -> Result<
         }
     }
 
-    return match field {
+    match field {
         None => {
             if !class.data.superclass.ptr().is_null() {
                 resolve_field(class.data.superclass, name, descriptor)
             } else {
-                Err(format!("Couldn't find field's reference {} in class {}", name,
-                            class.data.name))
+                Err(format!("NoSuchFieldError: {}", name))
             }
         },
         Some(field) => {
@@ -202,56 +324,298 @@ fn resolve_field(class: ClassRef, name: &str, descriptor: &FieldType) -> Result<
     }
 }
 
+/// Returns the index of an instance field, declared by the class or one of its superclasses, in
+/// the objects of the class
+pub fn instance_field_index(class: ClassRef, name: &str, descriptor: &FieldType)
+    -> Result<usize, Exception> {
+    match resolve_field(class, name, descriptor)? {
+        SymbolicReference::FieldReference(_, true, index) => Ok(index),
+        _ => Err(format!("IncompatibleClassChangeError: Expected non-static field {}", name))
+    }
+}
 
-pub fn initialize_class(class: ClassRef) -> Result<(), Exception> {
-    {
-        let class_state = class.state.get();
-        if class_state == ClassState::Ready || class_state == ClassState::Initializing {
-            return Ok(());
-        } else if class_state != ClassState::Verified {
-            return Err(format!("Class '{}' should be in state Verified at initialization but it was \
-                {:?}", class.data.name, class_state));
+/// Completes the verification of a derived class by checking the class assignments its code
+/// relies on. A class being checked by another thread is waited for, while recursive requests of
+/// the checking thread return immediately. Failures, including classes that could not be loaded
+/// for the checks, are reported again on every later attempt.
+fn verify_class(class: ClassRef, mutator: Option<usize>) -> Result<(), Exception> {
+    let vm = VM_HANDLER.get().unwrap();
+    let current = std::thread::current().id();
+
+    let checks = {
+        let mut pending = vm.pending_verifications.lock().unwrap();
+        loop {
+            if class.state.get() != Loaded {
+                return Ok(());
+            }
+
+            match pending.get(&class) {
+                Some(PendingVerification::Checks(_)) => {
+                    let previous = pending.insert(class, PendingVerification::Checking(current));
+                    if let Some(PendingVerification::Checks(checks)) = previous {
+                        break checks;
+                    }
+                }
+                Some(PendingVerification::Checking(thread)) if *thread == current => return Ok(()),
+                Some(PendingVerification::Checking(_)) => {
+                    pending = vm.gc.blocking(mutator, || vm.verification_done.wait(pending))
+                        .unwrap();
+                }
+                Some(PendingVerification::Failed(e)) => return Err(e.clone()),
+                None => return Err(format!("VerifyError: Class {} has no verification result",
+                                           class.data.name))
+            }
+        }
+    };
+
+    let result = checks.iter().try_for_each(|check| {
+        let load = |name: &str| vm.load_referenced_class(class.data.class_loader, name);
+        let (from, to) = (load(&check.from)?, load(&check.to)?);
+
+        if !to.is_interface() && !from.is_subclass(to) {
+            return Err(format!("VerifyError: Type '{}' is not assignable to '{}' in {}",
+                               check.from, check.to, check.location));
+        }
+        Ok(())
+    });
+
+    let mut pending = vm.pending_verifications.lock().unwrap();
+    match &result {
+        Ok(()) => {
+            pending.remove(&class);
+            class.state.set(Verified);
+        }
+        Err(e) => {
+            pending.insert(class, PendingVerification::Failed(e.clone()));
         }
+    }
+    vm.verification_done.notify_all();
+
+    result
+}
 
-        if let Err(_) = class.state.set_from(Verified, Initializing) {
-            return Ok(()); // Other thread does the initialization
+/// Reason a class could not be initialized
+#[derive(Debug)]
+pub enum InitializationError {
+    /// VM error of the form `Name: message`
+    Error(Exception),
+    /// Throwable thrown by a static initializer, along with its printed form
+    Thrown(ObjectPtr, String)
+}
+
+impl From<Exception> for InitializationError {
+    fn from(e: Exception) -> Self {
+        InitializationError::Error(e)
+    }
+}
+
+impl Display for InitializationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InitializationError::Error(e) | InitializationError::Thrown(_, e) => write!(f, "{}", e)
         }
     }
+}
+
+/// Initializes the class following JVMS §5.5. A class being initialized by another thread is
+/// waited for, while recursive requests of the initializing thread return immediately. Once an
+/// initialization failed, every later attempt fails with a NoClassDefFoundError. The mutator is
+/// that of the calling thread, it does not hold up collections while waiting.
+pub fn initialize_class(class: ClassRef, mutator: Option<usize>)
+    -> Result<(), InitializationError> {
+    if class.state.get() == Ready {
+        return Ok(());
+    }
+
+    verify_class(class, mutator)?;
+
+    {
+        let current = std::thread::current().id();
+        let mut initializer = class.init_lock.initializer.lock().unwrap();
+        loop {
+            match class.state.get() {
+                Verified => break,
+                Ready => return Ok(()),
+                Initializing if *initializer == Some(current) => return Ok(()),
+                Initializing => {
+                    let vm = VM_HANDLER.get().unwrap();
+                    initializer = vm.gc.blocking(mutator,
+                                                 || class.init_lock.completed.wait(initializer))
+                        .unwrap();
+                }
+                Erroneous => return Err(format!("NoClassDefFoundError: Could not initialize \
+                    class {}", class.data.name.replace('/', ".")).into()),
+                Loaded => return Err(format!("Class '{}' should be in state Verified at \
+                    initialization but it was Loaded", class.data.name).into())
+            }
+        }
 
+        *initializer = Some(current);
+        class.state.set(Initializing);
+    }
 
     eprintln!("Initializing {}", class.data.name);
 
-    // TODO: Apply ConstantValue attribute
-    let parent_list = vec![class.data.superclass];
-    for _i in &class.data.interfaces {
-        // TODO: Add to parent_list if declare at least one non-abstract, non-static method
+    let result = run_initialization(class, mutator);
+    let state = if result.is_ok() { Ready } else { Erroneous };
+
+    let mut initializer = class.init_lock.initializer.lock().unwrap();
+    *initializer = None;
+    class.state.set(state);
+    class.init_lock.completed.notify_all();
+
+    if result.is_ok() {
+        eprintln!("Initialized {}", class.data.name);
+    }
+    result
+}
+
+/// Steps of the initialization run by the initializing thread, after the class was marked as
+/// `Initializing`
+fn run_initialization(class: ClassRef, mutator: Option<usize>)
+    -> Result<(), InitializationError> {
+    // Static fields with a ConstantValue attribute get their value before anything else runs
+    for (i, field) in class.data.fields.iter().filter(|f| f.is_static()).enumerate() {
+        if let Some(index) = field.constant_value {
+            let value = match (class.get_cp_entry(index), &field.descriptor) {
+                (CPEntry::ConstantString(ptr), _) => ptr.to_val(),
+                // Sign-extended like the int constants pushed by the interpreter
+                (CPEntry::ConstantValue(val), FieldType::B | FieldType::C | FieldType::I
+                    | FieldType::S | FieldType::Z) => *val as u32 as i32 as u64,
+                (CPEntry::ConstantValue(val), _) => *val,
+                (entry, _) => return Err(format!("Unexpected constant value {:?} for field {}",
+                                                 entry, field.name).into())
+            };
+            class.data.static_fields[i].store(value, Ordering::Relaxed);
+        }
+    }
+
+    let mut parent_list = vec![];
+    if !class.data.superclass.ptr().is_null() {
+        parent_list.push(class.data.superclass);
+    }
+    if !class.is_interface() {
+        let mut interfaces = vec![];
+        collect_superinterfaces(class, &mut interfaces);
+
+        parent_list.extend(interfaces.into_iter().filter(|i| i.data.methods.iter()
+            .any(|m| !m.is_abstract() && !m.is_static())));
     }
 
     for i in parent_list {
-        initialize_class(i)?;
+        initialize_class(i, mutator)?;
     }
 
     let clinit = class.data.methods.iter().enumerate().find(|(_i,m)| {
         m.name == "<clinit>"
             && m.descriptor.ret == FieldType::V
-            && m.descriptor.parameters.len() == 0
+            && m.descriptor.parameters.is_empty()
             && m.is_static()
     });
 
-    match clinit {
-        Some((i, _)) => {
-            let mut thread = VMThread::new();
-            thread.start((class, i), smallvec![]);
+    if let Some((i, _)) = clinit {
+        let mut clinit_thread = VMThread::new();
+        clinit_thread.start((class, i), smallvec![]);
 
-            match thread.status {
-                FINISHED(_) => class.state.set(Ready),
-                FAILED(e) => return Err(e),
-                _ => panic!()
-            }
-        },
-        None => {}
+        match clinit_thread.status {
+            FINISHED(_) => {}
+            FAILED(e) => return Err(InitializationError::Thrown(
+                clinit_thread.exception.expect("Failed thread without exception"), e)),
+            RUNNING => return Err(format!("Initializer of {} did not complete",
+                                          class.data.name).into())
+        }
     }
 
-    eprintln!("Initialized {}", class.data.name);
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ClassRef, VM, VM_HANDLER};
+    use crate::vm::class::constant_pool::CPEntry::UnresolvedSymbolicReference;
+    use crate::vm::class::constant_pool::UnresolvedReference::{ClassReference, FieldReference,
+                                                                MethodReference};
+    use std::path::{Path, PathBuf};
+    use crate::helper::fixtures;
+    use crate::vm::class_loader::resolve::resolve;
+    use crate::vm::object::ObjectPtr;
+    use super::Exception;
+
+    /// Compiles the caller sources of a fixture against the classes under its `compile` directory,
+    /// next to the classes under its `runtime` directory, which are the ones the callers get to
+    /// see. Returns the directory to remove after the test.
+    fn compile_against(fixture: &str) -> PathBuf {
+        let directory = fixtures::temp_dir(fixture);
+        let compile = directory.join("compile");
+        let classes = directory.join("classes");
+        fixtures::javac(&format!("test/fixtures/{}/compile", fixture), &[], &compile);
+        fixtures::javac(&format!("test/fixtures/{}/runtime", fixture), &[], &classes);
+        fixtures::javac(&format!("test/fixtures/{}/caller", fixture), &[&compile], &classes);
+
+        directory
+    }
+
+    /// Defines a class compiled by `compile_against` with the bootstrap class loader
+    fn define(directory: &Path, class: &str) -> ClassRef {
+        let vm = VM_HANDLER.get().unwrap();
+        let bytes = std::fs::read(directory.join("classes").join(class.to_string() + ".class"))
+            .unwrap();
+        vm.define_class(ObjectPtr::null(), None, &bytes).unwrap()
+    }
+
+    /// Resolves the first unresolved class, field or method reference of `class` with the given
+    /// name
+    fn resolve_named(class: ClassRef, name: &str) -> Result<(), Exception> {
+        let index = (1..=class.data.constant_pool.len()).find(|i| {
+            match class.get_cp_entry(*i) {
+                UnresolvedSymbolicReference(ClassReference(n) | MethodReference(_, n, _)
+                    | FieldReference(_, n, _)) => n == name,
+                _ => false
+            }
+        }).unwrap();
+        resolve(class, index)
+    }
+
+    #[test]
+    fn resolution_failures_are_linkage_errors() {
+        VM_HANDLER.get_or_init(|| VM::vm_init(false));
+
+        let directory = compile_against("linkage");
+        for class in ["linkage/Target", "linkage/Shape"] {
+            define(&directory, class);
+        }
+        let caller = define(&directory, "linkage/Caller");
+        std::fs::remove_dir_all(directory).unwrap();
+
+        assert_eq!(resolve_named(caller, "gone").unwrap_err(),
+                   "NoSuchMethodError: 'void linkage.Target.gone()'");
+        assert_eq!(resolve_named(caller, "field").unwrap_err(), "NoSuchFieldError: field");
+        assert_eq!(resolve_named(caller, "area").unwrap_err(), "IncompatibleClassChangeError: \
+            Found interface linkage.Shape, but class was expected");
+    }
+
+    #[test]
+    fn inaccessible_members_are_illegal_access_errors() {
+        VM_HANDLER.get_or_init(|| VM::vm_init(false));
+
+        let directory = compile_against("access");
+        for class in ["other/Holder", "other/Secret", "access/Outer"] {
+            define(&directory, class);
+        }
+        let caller = define(&directory, "access/Caller");
+        let inner = define(&directory, "access/Outer$Inner");
+        std::fs::remove_dir_all(directory).unwrap();
+
+        assert_eq!(resolve_named(caller, "secret").unwrap_err(), "IllegalAccessError: class \
+            access.Caller tried to access private field other.Holder.secret");
+        assert_eq!(resolve_named(caller, "hidden").unwrap_err(), "IllegalAccessError: class \
+            access.Caller tried to access private method 'void other.Holder.hidden()'");
+        assert_eq!(resolve_named(caller, "internal").unwrap_err(), "IllegalAccessError: class \
+            access.Caller tried to access method 'void other.Holder.internal()'");
+        assert_eq!(resolve_named(caller, "other/Secret").unwrap_err(), "IllegalAccessError: \
+            failed to access class other.Secret from class access.Caller");
+
+        // Nestmates may access each other's private members
+        assert_eq!(resolve_named(inner, "secret"), Ok(()));
+    }
+}
@@ -0,0 +1,1261 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::thread::ThreadId;
+use crate::class_parser::attributes::{Attribute, CodeAttribute, StackMapFrame, VerificationTypeInfo};
+use crate::class_parser::constants::{AccessFlagMethod, CPInfo};
+use crate::class_parser::types::{MethodInfo, ParsedClass};
+use crate::helper::has_flag;
+use crate::vm::class::field::FieldType;
+use crate::vm::class::method::MethodDescriptor;
+use crate::vm::instructions::{Instruction, instruction_length, read_i32, switch_operands_start};
+
+type Exception = String;
+
+/// Class files older than this have no StackMapTable attributes, they are only checked for
+/// well-formed instructions
+const TYPE_CHECKING_VERSION: u16 = 50;
+
+/// Result of type checking the methods of a class (JVMS §4.10.1)
+#[derive(Debug)]
+pub enum Verification {
+    /// The class is valid, provided that the assignability checks hold. Those need other classes
+    /// to be loaded, which could lead back to the class being derived, so they are left until
+    /// the class is linked.
+    Passed(Vec<AssignabilityCheck>),
+    Failed(String)
+}
+
+/// Verification state of a derived class that is not linked yet
+#[derive(Debug)]
+pub enum PendingVerification {
+    /// Type checking passed, the assignability checks are still to be done
+    Checks(Vec<AssignabilityCheck>),
+    /// The given thread is doing the assignability checks
+    Checking(ThreadId),
+    /// Linking failed with the given error, which every later attempt fails with as well
+    Failed(Exception)
+}
+
+impl From<Verification> for PendingVerification {
+    fn from(verification: Verification) -> Self {
+        match verification {
+            Verification::Passed(checks) => PendingVerification::Checks(checks),
+            Verification::Failed(e) => PendingVerification::Failed(format!("VerifyError: {}", e))
+        }
+    }
+}
+
+/// Requirement that a class type is assignable to another one
+#[derive(Debug)]
+pub struct AssignabilityCheck {
+    pub from: String,
+    pub to: String,
+    pub location: String
+}
+
+/// Verifies the code of every method of the class. Malformed Code attributes are format errors
+/// and are returned as `Err`, type errors result in `Verification::Failed`.
+pub fn verify(parsed_class: &ParsedClass) -> Result<Verification, Exception> {
+    let class = ClassFile::new(parsed_class)?;
+
+    let mut checks = vec![];
+    for method in &parsed_class.methods {
+        let name = class.utf8(method.name_index)?;
+        let raw_descriptor = class.utf8(method.descriptor_index)?;
+        let descriptor = MethodDescriptor::parse(raw_descriptor)
+            .ok_or(format!("Could not parse method descriptor {}", raw_descriptor))?;
+
+        let code = match MethodCode::new(&class, method)? {
+            None => continue,
+            Some(code) => code
+        };
+
+        let mut verifier = MethodVerifier {
+            class: &class,
+            name,
+            raw_descriptor,
+            descriptor,
+            is_static: has_flag(method.access_flags, AccessFlagMethod::ACC_STATIC),
+            code: &code,
+            checks: &mut checks,
+            pc: 0
+        };
+
+        let res = if parsed_class.major_version < TYPE_CHECKING_VERSION {
+            verifier.instruction_starts().map(|_| ())
+        } else {
+            verifier.verify()
+        };
+
+        if let Err(e) = res {
+            return Ok(Verification::Failed(verifier.error(&e)));
+        }
+    }
+
+    Ok(Verification::Passed(checks))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum VerificationType {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    /// Object created by the `new` instruction at the given offset, before calling its
+    /// constructor
+    Uninitialized(usize),
+    /// Class or array type
+    Reference(FieldType)
+}
+
+use VerificationType::*;
+
+impl VerificationType {
+    fn from_field_type(field_type: &FieldType) -> VerificationType {
+        match field_type {
+            FieldType::B | FieldType::C | FieldType::S | FieldType::Z | FieldType::I => Integer,
+            FieldType::F => Float,
+            FieldType::J => Long,
+            FieldType::D => Double,
+            FieldType::L(_) | FieldType::A(_) => Reference(field_type.clone()),
+            FieldType::V => Top
+        }
+    }
+
+    fn object(name: &str) -> VerificationType {
+        Reference(FieldType::L(name.to_string()))
+    }
+
+    fn is_category2(&self) -> bool {
+        matches!(self, Long | Double)
+    }
+
+    fn is_reference(&self) -> bool {
+        matches!(self, Null | UninitializedThis | Uninitialized(_) | Reference(_))
+    }
+}
+
+impl Display for VerificationType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Top => write!(f, "top"),
+            Integer => write!(f, "integer"),
+            Float => write!(f, "float"),
+            Long => write!(f, "long"),
+            Double => write!(f, "double"),
+            Null => write!(f, "null"),
+            UninitializedThis => write!(f, "uninitializedThis"),
+            Uninitialized(offset) => write!(f, "uninitialized({})", offset),
+            Reference(FieldType::L(name)) => write!(f, "'{}'", name),
+            Reference(field_type) => write!(f, "'{}'", descriptor(field_type))
+        }
+    }
+}
+
+fn descriptor(field_type: &FieldType) -> String {
+    match field_type {
+        FieldType::L(name) => format!("L{};", name),
+        FieldType::A(component) => format!("[{}", descriptor(component)),
+        primitive => format!("{:?}", primitive)
+    }
+}
+
+/// Type of a class named in the constant pool, where array classes are named by their descriptor
+fn class_type(name: &str) -> Result<FieldType, String> {
+    if name.starts_with('[') {
+        FieldType::parse(name).ok_or(format!("Invalid array class {}", name))
+    } else {
+        Ok(FieldType::L(name.to_string()))
+    }
+}
+
+fn stack_size(stack: &[VerificationType]) -> usize {
+    stack.iter().map(|t| if t.is_category2() { 2 } else { 1 }).sum()
+}
+
+#[derive(Clone, Debug)]
+struct Frame {
+    /// One entry for every local variable, the second half of longs and doubles is top
+    locals: Vec<VerificationType>,
+    stack: Vec<VerificationType>,
+    /// Whether the constructor still has to call another constructor on `this`
+    this_uninit: bool
+}
+
+struct ClassFile<'a> {
+    parsed_class: &'a ParsedClass,
+    name: &'a str,
+    superclass: Option<&'a str>
+}
+
+impl<'a> ClassFile<'a> {
+    fn new(parsed_class: &'a ParsedClass) -> Result<ClassFile<'a>, Exception> {
+        let mut class = ClassFile { parsed_class, name: "", superclass: None };
+        class.name = class.class_name(parsed_class.this_class)?;
+        if parsed_class.super_class != 0 {
+            class.superclass = Some(class.class_name(parsed_class.super_class)?);
+        }
+
+        Ok(class)
+    }
+
+    fn cp(&self, index: u16) -> Option<&'a CPInfo> {
+        self.parsed_class.constant_pool.get((index as usize).checked_sub(1)?)
+    }
+
+    fn utf8(&self, index: u16) -> Result<&'a str, String> {
+        match self.cp(index) {
+            Some(CPInfo::Utf8(str)) => Ok(str),
+            _ => Err(format!("Expected a Utf8 constant at index {}", index))
+        }
+    }
+
+    fn class_name(&self, index: u16) -> Result<&'a str, String> {
+        match self.cp(index) {
+            Some(CPInfo::Class(name)) => self.utf8(*name),
+            _ => Err(format!("Expected a Class constant at index {}", index))
+        }
+    }
+
+    fn name_and_type(&self, index: u16) -> Result<(&'a str, &'a str), String> {
+        match self.cp(index) {
+            Some(CPInfo::NameAndType(name, descriptor)) =>
+                Ok((self.utf8(*name)?, self.utf8(*descriptor)?)),
+            _ => Err(format!("Expected a NameAndType constant at index {}", index))
+        }
+    }
+
+    /// Class name, member name and descriptor of a field or method reference
+    fn member_ref(&self, index: u16) -> Result<(&'a str, &'a str, &'a str), String> {
+        match self.cp(index) {
+            Some(CPInfo::Fieldref(class, name_and_type) | CPInfo::Methodref(class, name_and_type)
+                 | CPInfo::InterfaceMethodref(class, name_and_type)) => {
+                let (name, descriptor) = self.name_and_type(*name_and_type)?;
+                Ok((self.class_name(*class)?, name, descriptor))
+            }
+            _ => Err(format!("Expected a member reference at index {}", index))
+        }
+    }
+}
+
+struct Handler {
+    start: usize,
+    end: usize,
+    target: usize,
+    catch_type: Option<String>
+}
+
+/// Contents of the Code attribute used by the verifier
+struct MethodCode<'a> {
+    max_stack: usize,
+    max_locals: usize,
+    code: &'a [u8],
+    handlers: Vec<Handler>,
+    stack_map_table: &'a [StackMapFrame]
+}
+
+impl<'a> MethodCode<'a> {
+    fn new(class: &ClassFile<'a>, method: &'a MethodInfo) -> Result<Option<MethodCode<'a>>,
+        Exception> {
+        let code_attributes: Vec<&CodeAttribute> = method.attributes.iter()
+            .filter_map(|a| match a {
+                Attribute::Code(code) => Some(code),
+                _ => None
+            }).collect();
+
+        let without_code = has_flag(method.access_flags, AccessFlagMethod::ACC_ABSTRACT)
+            || has_flag(method.access_flags, AccessFlagMethod::ACC_NATIVE);
+        let code = match (code_attributes.as_slice(), without_code) {
+            ([], true) => return Ok(None),
+            ([code], false) => code,
+            ([], false) => return Err("Absent Code attribute".to_string()),
+            _ => return Err("Unexpected Code attribute".to_string())
+        };
+
+        let mut handlers = Vec::with_capacity(code.exception_table.len());
+        for e in &code.exception_table {
+            let catch_type = match e.catch_type {
+                0 => None,
+                index => Some(class.class_name(index)?.to_string())
+            };
+
+            handlers.push(Handler {
+                start: e.start_pc as usize,
+                end: e.end_pc as usize,
+                target: e.handler_pc as usize,
+                catch_type
+            });
+        }
+
+        let mut stack_map_table: Option<&[StackMapFrame]> = None;
+        for a in &code.attributes {
+            if let Attribute::StackMapTable(frames) = a {
+                if stack_map_table.is_some() {
+                    return Err("Multiple StackMapTable attributes".to_string());
+                }
+                stack_map_table = Some(frames);
+            }
+        }
+
+        Ok(Some(MethodCode {
+            max_stack: code.max_stack as usize,
+            max_locals: code.max_locals as usize,
+            code: &code.code,
+            handlers,
+            stack_map_table: stack_map_table.unwrap_or_default()
+        }))
+    }
+}
+
+struct MethodVerifier<'a> {
+    class: &'a ClassFile<'a>,
+    name: &'a str,
+    raw_descriptor: &'a str,
+    descriptor: MethodDescriptor,
+    is_static: bool,
+    code: &'a MethodCode<'a>,
+    checks: &'a mut Vec<AssignabilityCheck>,
+    /// Offset of the instruction being checked
+    pc: usize
+}
+
+impl MethodVerifier<'_> {
+    fn location(&self) -> String {
+        format!("{}.{}{} at offset {}", self.class.name, self.name, self.raw_descriptor, self.pc)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} in {}", message, self.location())
+    }
+
+    fn verify(&mut self) -> Result<(), String> {
+        let starts = self.instruction_starts()?;
+        let frames = self.stack_map_frames(&starts)?;
+        let code = self.code.code;
+
+        for handler in &self.code.handlers {
+            if handler.start >= handler.end || handler.end > code.len() || !starts[handler.start]
+                || !starts[handler.end]
+                || handler.target >= code.len() || !starts[handler.target] {
+                return Err(format!("Illegal exception table range {}..{} with handler at {}",
+                                   handler.start, handler.end, handler.target));
+            }
+
+            let catch_type = handler.catch_type.as_deref().unwrap_or("java/lang/Throwable");
+            if !self.is_assignable(&VerificationType::object(catch_type),
+                                   &VerificationType::object("java/lang/Throwable")) {
+                return Err(format!("Catch type {} is not a class", catch_type));
+            }
+        }
+
+        let mut current = Some(self.initial_frame()?);
+        let mut pc = 0;
+        while pc < code.len() {
+            self.pc = pc;
+
+            if let Some(frame) = frames.get(&pc) {
+                if let Some(current) = &current {
+                    self.check_frame(current, frame)?;
+                }
+                current = Some(frame.clone());
+            }
+
+            let mut frame = current.take().ok_or("Expecting a stack map frame")?;
+
+            for handler in self.code.handlers.iter().filter(|h| h.start <= pc && pc < h.end) {
+                let catch_type = handler.catch_type.as_deref().unwrap_or("java/lang/Throwable");
+                let exception_frame = Frame {
+                    locals: frame.locals.clone(),
+                    stack: vec![VerificationType::object(catch_type)],
+                    this_uninit: frame.this_uninit
+                };
+
+                let target = frames.get(&handler.target)
+                    .ok_or("Expecting a stack map frame at the exception handler")?;
+                self.check_frame(&exception_frame, target)?;
+            }
+
+            let instruction = unsafe { Instruction::from_unchecked(code[pc]) };
+            if self.execute(instruction, &mut frame, &frames)? {
+                current = Some(frame);
+            }
+
+            pc += instruction_length(instruction, code, pc).ok_or("Truncated instruction")?;
+        }
+
+        if current.is_some() {
+            return Err("Falling off the end of the code".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Marks the offsets where instructions start, and checks that every instruction is
+    /// supported and fits into the code. The element after the code is also marked, as it is a
+    /// valid end of exception handler ranges.
+    fn instruction_starts(&mut self) -> Result<Vec<bool>, String> {
+        let code = self.code.code;
+        let mut starts = vec![false; code.len() + 1];
+
+        let mut pc = 0;
+        while pc < code.len() {
+            self.pc = pc;
+            starts[pc] = true;
+
+            if !Instruction::exists(code[pc]) {
+                return Err(format!("Unsupported instruction {}", code[pc]));
+            }
+
+            let instruction = unsafe { Instruction::from_unchecked(code[pc]) };
+            pc += instruction_length(instruction, code, pc).ok_or("Truncated instruction")?;
+        }
+
+        if pc > code.len() {
+            return Err("Truncated instruction".to_string());
+        }
+        starts[code.len()] = true;
+
+        Ok(starts)
+    }
+
+    fn initial_frame(&self) -> Result<Frame, String> {
+        let mut locals = vec![];
+        let mut this_uninit = false;
+
+        if !self.is_static {
+            if self.name == "<init>" && self.class.superclass.is_some() {
+                locals.push(UninitializedThis);
+                this_uninit = true;
+            } else {
+                locals.push(VerificationType::object(self.class.name));
+            }
+        }
+
+        locals.extend(self.descriptor.parameters.iter().map(VerificationType::from_field_type));
+        let locals = self.expand_locals(&locals)?;
+
+        Ok(Frame { locals, stack: vec![], this_uninit })
+    }
+
+    /// Converts local variable types as listed in stack map frames and descriptors, where longs
+    /// and doubles take a single entry, to one entry per local variable
+    fn expand_locals(&self, types: &[VerificationType]) -> Result<Vec<VerificationType>, String> {
+        let mut locals = Vec::with_capacity(self.code.max_locals);
+        for t in types {
+            locals.push(t.clone());
+            if t.is_category2() {
+                locals.push(Top);
+            }
+        }
+
+        if locals.len() > self.code.max_locals {
+            return Err(format!("Local variables exceed max_locals {}", self.code.max_locals));
+        }
+        locals.resize(self.code.max_locals, Top);
+
+        Ok(locals)
+    }
+
+    /// Expands the frames of the StackMapTable attribute, keyed by their offset
+    fn stack_map_frames(&mut self, starts: &[bool]) -> Result<HashMap<usize, Frame>, String> {
+        let initial = self.initial_frame()?;
+        // Locals as listed in the previous frame
+        let mut locals: Vec<VerificationType> = vec![];
+        let mut i = 0;
+        while i < initial.locals.len() && initial.locals[i] != Top {
+            locals.push(initial.locals[i].clone());
+            i += if initial.locals[i].is_category2() { 2 } else { 1 };
+        }
+
+        let mut frames = HashMap::new();
+        let mut offset: Option<usize> = None;
+        for stack_map_frame in self.code.stack_map_table {
+            let stack = match stack_map_frame {
+                StackMapFrame::Same { .. } => vec![],
+                StackMapFrame::SameLocals1StackItem { stack, .. } =>
+                    vec![self.verification_type(stack)?],
+                StackMapFrame::Chop { k, .. } => {
+                    if *k as usize > locals.len() {
+                        return Err("Stack map frame chops too many locals".to_string());
+                    }
+                    locals.truncate(locals.len() - *k as usize);
+                    vec![]
+                }
+                StackMapFrame::Append { locals: appended, .. } => {
+                    for t in appended {
+                        locals.push(self.verification_type(t)?);
+                    }
+                    vec![]
+                }
+                StackMapFrame::Full { locals: full_locals, stack, .. } => {
+                    locals = full_locals.iter().map(|t| self.verification_type(t))
+                        .collect::<Result<_, _>>()?;
+                    stack.iter().map(|t| self.verification_type(t)).collect::<Result<_, _>>()?
+                }
+            };
+
+            let delta = stack_map_frame.offset_delta() as usize;
+            let frame_offset = match offset {
+                None => delta,
+                Some(previous) => previous + delta + 1
+            };
+            offset = Some(frame_offset);
+
+            if frame_offset >= self.code.code.len() || !starts[frame_offset] {
+                return Err(format!("Stack map frame at invalid offset {}", frame_offset));
+            }
+            if stack_size(&stack) > self.code.max_stack {
+                return Err("Stack map frame exceeds max_stack".to_string());
+            }
+
+            for t in locals.iter().chain(stack.iter()) {
+                if let Uninitialized(new_offset) = t {
+                    if self.code.code.get(*new_offset) != Some(&(Instruction::new as u8))
+                        || !starts[*new_offset] {
+                        return Err(format!("Stack map frame refers to no new instruction at {}",
+                                           new_offset));
+                    }
+                }
+            }
+
+            let expanded = self.expand_locals(&locals)?;
+            let this_uninit = expanded.contains(&UninitializedThis);
+            frames.insert(frame_offset, Frame { locals: expanded, stack, this_uninit });
+        }
+
+        Ok(frames)
+    }
+
+    fn verification_type(&self, info: &VerificationTypeInfo) -> Result<VerificationType, String> {
+        Ok(match info {
+            VerificationTypeInfo::Top => Top,
+            VerificationTypeInfo::Integer => Integer,
+            VerificationTypeInfo::Float => Float,
+            VerificationTypeInfo::Double => Double,
+            VerificationTypeInfo::Long => Long,
+            VerificationTypeInfo::Null => Null,
+            VerificationTypeInfo::UninitializedThis => UninitializedThis,
+            VerificationTypeInfo::Object(index) =>
+                Reference(class_type(self.class.class_name(*index)?)?),
+            VerificationTypeInfo::Uninitialized(offset) => Uninitialized(*offset as usize)
+        })
+    }
+
+    fn check_frame(&mut self, from: &Frame, to: &Frame) -> Result<(), String> {
+        if from.stack.len() != to.stack.len() {
+            return Err("Inconsistent stack height with the stack map frame".to_string());
+        }
+
+        for (a, b) in from.locals.iter().zip(&to.locals).chain(from.stack.iter().zip(&to.stack)) {
+            if !self.is_assignable(a, b) {
+                return Err(format!("Type {} is not assignable to {} of the stack map frame",
+                                   a, b));
+            }
+        }
+
+        if from.this_uninit && !to.this_uninit {
+            return Err("Uninitialized this is not allowed by the stack map frame".to_string());
+        }
+
+        Ok(())
+    }
+
+    fn is_assignable(&mut self, from: &VerificationType, to: &VerificationType) -> bool {
+        match (from, to) {
+            (_, Top) => true,
+            (Null, Reference(_)) => true,
+            (Reference(from), Reference(to)) => self.is_reference_assignable(from, to),
+            _ => from == to
+        }
+    }
+
+    fn is_reference_assignable(&mut self, from: &FieldType, to: &FieldType) -> bool {
+        use FieldType::{A, L};
+
+        match (from, to) {
+            (_, L(to)) if to == "java/lang/Object" => true,
+            (L(from), L(to)) => {
+                if from != to {
+                    let location = self.location();
+                    self.checks.push(AssignabilityCheck {
+                        from: from.clone(),
+                        to: to.clone(),
+                        location
+                    });
+                }
+                true
+            }
+            (A(_), L(to)) => to == "java/lang/Cloneable" || to == "java/io/Serializable",
+            (A(from), A(to)) if matches!(**from, L(_) | A(_)) && matches!(**to, L(_) | A(_)) =>
+                self.is_reference_assignable(from, to),
+            (A(from), A(to)) => from == to,
+            _ => false
+        }
+    }
+
+    fn push(&self, frame: &mut Frame, t: VerificationType) -> Result<(), String> {
+        frame.stack.push(t);
+        if stack_size(&frame.stack) > self.code.max_stack {
+            return Err("Operand stack overflow".to_string());
+        }
+
+        Ok(())
+    }
+
+    fn pop(&mut self, frame: &mut Frame, expected: &VerificationType) -> Result<VerificationType,
+        String> {
+        let t = frame.stack.pop().ok_or("Operand stack underflow")?;
+        if !self.is_assignable(&t, expected) {
+            return Err(format!("Bad type on operand stack: expected {}, found {}", expected, t));
+        }
+
+        Ok(t)
+    }
+
+    fn pop_reference(&self, frame: &mut Frame) -> Result<VerificationType, String> {
+        let t = frame.stack.pop().ok_or("Operand stack underflow")?;
+        if !t.is_reference() {
+            return Err(format!("Bad type on operand stack: expected a reference, found {}", t));
+        }
+
+        Ok(t)
+    }
+
+    fn pop_category1(&self, frame: &mut Frame) -> Result<VerificationType, String> {
+        let t = frame.stack.pop().ok_or("Operand stack underflow")?;
+        if t.is_category2() {
+            return Err(format!("Bad type on operand stack: expected a category 1 value, found {}",
+                               t));
+        }
+
+        Ok(t)
+    }
+
+    /// Pops an array whose component type satisfies `component`, returning the component type or
+    /// None for a null array
+    fn pop_array(&self, frame: &mut Frame, component: impl Fn(&FieldType) -> bool) ->
+                                                                Result<Option<FieldType>, String> {
+        match frame.stack.pop().ok_or("Operand stack underflow")? {
+            Null => Ok(None),
+            Reference(FieldType::A(t)) if component(&t) => Ok(Some(*t)),
+            t => Err(format!("Bad type on operand stack: expected an array, found {}", t))
+        }
+    }
+
+    fn load(&self, frame: &mut Frame, index: usize, expected: &VerificationType) ->
+                                                                        Result<(), String> {
+        let t = frame.locals.get(index).ok_or(format!("Illegal local variable index {}", index))?;
+        let valid = match expected {
+            Reference(_) => t.is_reference(),
+            expected if expected.is_category2() => t == expected && index + 1 < frame.locals.len(),
+            expected => t == expected
+        };
+        if !valid {
+            return Err(format!("Bad local variable type: expected {}, found {}", expected, t));
+        }
+
+        let t = t.clone();
+        self.push(frame, t)
+    }
+
+    fn store(&self, frame: &mut Frame, index: usize, expected: &VerificationType) ->
+                                                                        Result<(), String> {
+        let t = frame.stack.pop().ok_or("Operand stack underflow")?;
+        let valid = match expected {
+            Reference(_) => t.is_reference(),
+            expected => &t == expected
+        };
+        if !valid {
+            return Err(format!("Bad type on operand stack: expected {}, found {}", expected, t));
+        }
+
+        let size = if t.is_category2() { 2 } else { 1 };
+        if index + size > frame.locals.len() {
+            return Err(format!("Illegal local variable index {}", index));
+        }
+
+        if index > 0 && frame.locals[index - 1].is_category2() {
+            frame.locals[index - 1] = Top;
+        }
+        if size == 2 {
+            frame.locals[index + 1] = Top;
+        }
+        frame.locals[index] = t;
+
+        Ok(())
+    }
+
+    fn branch(&mut self, frame: &Frame, offset: i64, frames: &HashMap<usize, Frame>) ->
+                                                                        Result<(), String> {
+        let target = self.pc as i64 + offset;
+        let target_frame = usize::try_from(target).ok().and_then(|t| frames.get(&t))
+            .ok_or(format!("Expecting a stack map frame at branch target {}", target))?;
+
+        self.check_frame(frame, target_frame)
+    }
+
+    /// Applies the instruction at `self.pc` to the frame. Returns whether the next instruction
+    /// can be reached by falling through.
+    fn execute(&mut self, instruction: Instruction, frame: &mut Frame,
+               frames: &HashMap<usize, Frame>) -> Result<bool, String> {
+        use Instruction::*;
+
+        let code = self.code.code;
+        let pc = self.pc;
+        let u1 = |i: usize| code[pc + i];
+        let u2 = |i: usize| u16::from_be_bytes([code[pc + i], code[pc + i + 1]]);
+        let i2 = |i: usize| i16::from_be_bytes([code[pc + i], code[pc + i + 1]]) as i64;
+
+        match instruction {
+            nop => {}
+            aconst_null => self.push(frame, Null)?,
+            iconst_m1 | iconst_0 | iconst_1 | iconst_2 | iconst_3 | iconst_4 | iconst_5 | bipush
+            | sipush => self.push(frame, Integer)?,
+            lconst_0 | lconst_1 => self.push(frame, Long)?,
+            fconst_0 | fconst_1 | fconst_2 => self.push(frame, Float)?,
+            dconst_0 | dconst_1 => self.push(frame, Double)?,
+            ldc | ldc_w | ldc2_w => {
+                let index = if instruction == ldc { u1(1) as u16 } else { u2(1) };
+                let t = match self.class.cp(index) {
+                    Some(CPInfo::Integer(_)) => Integer,
+                    Some(CPInfo::Float(_)) => Float,
+                    Some(CPInfo::Long(_, _)) => Long,
+                    Some(CPInfo::Double(_, _)) => Double,
+                    Some(CPInfo::String(_)) => VerificationType::object("java/lang/String"),
+                    Some(CPInfo::Class(_)) => VerificationType::object("java/lang/Class"),
+                    Some(CPInfo::MethodType(_)) =>
+                        VerificationType::object("java/lang/invoke/MethodType"),
+                    Some(CPInfo::MethodHandle(_, _)) =>
+                        VerificationType::object("java/lang/invoke/MethodHandle"),
+                    Some(CPInfo::Dynamic(_, name_and_type)) => {
+                        let descriptor = self.class.name_and_type(*name_and_type)?.1;
+                        VerificationType::from_field_type(&FieldType::parse(descriptor)
+                            .ok_or(format!("Invalid field descriptor {}", descriptor))?)
+                    }
+                    _ => return Err(format!("Invalid constant pool index {} for ldc", index))
+                };
+
+                if t.is_category2() != (instruction == ldc2_w) {
+                    return Err(format!("Constant {} has the wrong size for {:?}", t,
+                                       instruction));
+                }
+                self.push(frame, t)?;
+            }
+
+            iload => self.load(frame, u1(1) as usize, &Integer)?,
+            lload => self.load(frame, u1(1) as usize, &Long)?,
+            fload => self.load(frame, u1(1) as usize, &Float)?,
+            dload => self.load(frame, u1(1) as usize, &Double)?,
+            aload => self.load(frame, u1(1) as usize, &VerificationType::object("java/lang/Object"))?,
+            iload_0 | iload_1 | iload_2 | iload_3 =>
+                self.load(frame, (instruction as u8 - iload_0 as u8) as usize, &Integer)?,
+            lload_0 | lload_1 | lload_2 | lload_3 =>
+                self.load(frame, (instruction as u8 - lload_0 as u8) as usize, &Long)?,
+            fload_0 | fload_1 | fload_2 | fload_3 =>
+                self.load(frame, (instruction as u8 - fload_0 as u8) as usize, &Float)?,
+            dload_0 | dload_1 | dload_2 | dload_3 =>
+                self.load(frame, (instruction as u8 - dload_0 as u8) as usize, &Double)?,
+            aload_0 | aload_1 | aload_2 | aload_3 =>
+                self.load(frame, (instruction as u8 - aload_0 as u8) as usize,
+                          &VerificationType::object("java/lang/Object"))?,
+
+            iaload => {
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| *t == FieldType::I)?;
+                self.push(frame, Integer)?;
+            }
+            laload => {
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| *t == FieldType::J)?;
+                self.push(frame, Long)?;
+            }
+            faload => {
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| *t == FieldType::F)?;
+                self.push(frame, Float)?;
+            }
+            daload => {
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| *t == FieldType::D)?;
+                self.push(frame, Double)?;
+            }
+            aaload => {
+                self.pop(frame, &Integer)?;
+                let component = self.pop_array(frame,
+                                               FieldType::is_reference)?;
+                self.push(frame, component.map_or(Null, Reference))?;
+            }
+            baload => {
+                // Shared by byte and boolean arrays
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| matches!(t, FieldType::B | FieldType::Z))?;
+                self.push(frame, Integer)?;
+            }
+            caload => {
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| *t == FieldType::C)?;
+                self.push(frame, Integer)?;
+            }
+            saload => {
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| *t == FieldType::S)?;
+                self.push(frame, Integer)?;
+            }
+
+            istore => self.store(frame, u1(1) as usize, &Integer)?,
+            lstore => self.store(frame, u1(1) as usize, &Long)?,
+            fstore => self.store(frame, u1(1) as usize, &Float)?,
+            dstore => self.store(frame, u1(1) as usize, &Double)?,
+            astore => self.store(frame, u1(1) as usize,
+                                 &VerificationType::object("java/lang/Object"))?,
+            istore_0 | istore_1 | istore_2 | istore_3 =>
+                self.store(frame, (instruction as u8 - istore_0 as u8) as usize, &Integer)?,
+            lstore_0 | lstore_1 | lstore_2 | lstore_3 =>
+                self.store(frame, (instruction as u8 - lstore_0 as u8) as usize, &Long)?,
+            fstore_0 | fstore_1 | fstore_2 | fstore_3 =>
+                self.store(frame, (instruction as u8 - fstore_0 as u8) as usize, &Float)?,
+            dstore_0 | dstore_1 | dstore_2 | dstore_3 =>
+                self.store(frame, (instruction as u8 - dstore_0 as u8) as usize, &Double)?,
+            astore_0 | astore_1 | astore_2 | astore_3 =>
+                self.store(frame, (instruction as u8 - astore_0 as u8) as usize,
+                           &VerificationType::object("java/lang/Object"))?,
+
+            iastore => {
+                self.pop(frame, &Integer)?;
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| *t == FieldType::I)?;
+            }
+            lastore => {
+                self.pop(frame, &Long)?;
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| *t == FieldType::J)?;
+            }
+            fastore => {
+                self.pop(frame, &Float)?;
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| *t == FieldType::F)?;
+            }
+            dastore => {
+                self.pop(frame, &Double)?;
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| *t == FieldType::D)?;
+            }
+            aastore => {
+                self.pop(frame, &VerificationType::object("java/lang/Object"))?;
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, FieldType::is_reference)?;
+            }
+            bastore => {
+                self.pop(frame, &Integer)?;
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| matches!(t, FieldType::B | FieldType::Z))?;
+            }
+            castore => {
+                self.pop(frame, &Integer)?;
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| *t == FieldType::C)?;
+            }
+            sastore => {
+                self.pop(frame, &Integer)?;
+                self.pop(frame, &Integer)?;
+                self.pop_array(frame, |t| *t == FieldType::S)?;
+            }
+
+            pop => {
+                self.pop_category1(frame)?;
+            }
+            dup => {
+                let t = self.pop_category1(frame)?;
+                self.push(frame, t.clone())?;
+                self.push(frame, t)?;
+            }
+
+            iadd | isub | imul | idiv | ishl | ixor => self.arithmetic(frame, &Integer, 2)?,
+            ladd | lmul | ldiv | lrem => self.arithmetic(frame, &Long, 2)?,
+            fadd | fsub | fmul | fdiv | frem => self.arithmetic(frame, &Float, 2)?,
+            dadd | dsub | dmul | ddiv => self.arithmetic(frame, &Double, 2)?,
+            fneg => self.arithmetic(frame, &Float, 1)?,
+            dneg => self.arithmetic(frame, &Double, 1)?,
+            iinc => {
+                let index = u1(1) as usize;
+                if frame.locals.get(index) != Some(&Integer) {
+                    return Err(format!("Bad local variable type for iinc at index {}", index));
+                }
+            }
+
+            i2l => self.convert(frame, Integer, Long)?,
+            i2f => self.convert(frame, Integer, Float)?,
+            l2i => self.convert(frame, Long, Integer)?,
+            l2f => self.convert(frame, Long, Float)?,
+            l2d => self.convert(frame, Long, Double)?,
+            f2i => self.convert(frame, Float, Integer)?,
+            f2l => self.convert(frame, Float, Long)?,
+            f2d => self.convert(frame, Float, Double)?,
+            d2f => self.convert(frame, Double, Float)?,
+            i2b | i2c => self.convert(frame, Integer, Integer)?,
+            lcmp => {
+                self.pop(frame, &Long)?;
+                self.pop(frame, &Long)?;
+                self.push(frame, Integer)?;
+            }
+            fcmpl | fcmpg => {
+                self.pop(frame, &Float)?;
+                self.pop(frame, &Float)?;
+                self.push(frame, Integer)?;
+            }
+
+            ifeq | ifne | iflt | ifge | ifgt | ifle => {
+                self.pop(frame, &Integer)?;
+                self.branch(frame, i2(1), frames)?;
+            }
+            if_icmpeq | if_icmpge | if_icmpgt => {
+                self.pop(frame, &Integer)?;
+                self.pop(frame, &Integer)?;
+                self.branch(frame, i2(1), frames)?;
+            }
+            if_acmpeq | if_acmpne => {
+                self.pop_reference(frame)?;
+                self.pop_reference(frame)?;
+                self.branch(frame, i2(1), frames)?;
+            }
+            ifnull | ifnonnull => {
+                self.pop_reference(frame)?;
+                self.branch(frame, i2(1), frames)?;
+            }
+            goto => {
+                self.branch(frame, i2(1), frames)?;
+                return Ok(false);
+            }
+            tableswitch | lookupswitch => {
+                self.pop(frame, &Integer)?;
+
+                let start = switch_operands_start(pc);
+                let operand = |index| read_i32(code, index)
+                    .ok_or_else(|| "Truncated instruction".to_string());
+                let mut offsets = vec![operand(start)? as i64];
+                if instruction == tableswitch {
+                    let (low, high) = (operand(start + 4)?, operand(start + 8)?);
+                    if low > high {
+                        return Err("Invalid tableswitch bounds".to_string());
+                    }
+                    offsets.extend((0..=(high as i64 - low as i64) as usize)
+                        .map(|i| operand(start + 12 + 4 * i).map(i64::from))
+                        .collect::<Result<Vec<_>, _>>()?);
+                } else {
+                    let pairs = operand(start + 4)?;
+                    if pairs < 0 {
+                        return Err("Invalid lookupswitch pair count".to_string());
+                    }
+
+                    let keys = (0..pairs as usize)
+                        .map(|i| operand(start + 8 + 8 * i)).collect::<Result<Vec<_>, _>>()?;
+                    if keys.windows(2).any(|w| w[0] >= w[1]) {
+                        return Err("Unsorted lookupswitch keys".to_string());
+                    }
+                    offsets.extend((0..pairs as usize)
+                        .map(|i| operand(start + 12 + 8 * i).map(i64::from))
+                        .collect::<Result<Vec<_>, _>>()?);
+                }
+
+                for offset in offsets {
+                    self.branch(frame, offset, frames)?;
+                }
+                return Ok(false);
+            }
+
+            ireturn => return self.return_value(frame, Integer).map(|_| false),
+            lreturn => return self.return_value(frame, Long).map(|_| false),
+            freturn => return self.return_value(frame, Float).map(|_| false),
+            dreturn => return self.return_value(frame, Double).map(|_| false),
+            areturn => return self.return_value(frame,
+                                                VerificationType::object("java/lang/Object"))
+                .map(|_| false),
+            _return => {
+                if self.descriptor.ret != FieldType::V {
+                    return Err("Method expects a return value".to_string());
+                }
+                if frame.this_uninit {
+                    return Err("Constructor must call super() or this() before return"
+                        .to_string());
+                }
+                return Ok(false);
+            }
+
+            getstatic | putstatic | getfield | putfield => {
+                let (class_name, _, descriptor) = self.class.member_ref(u2(1))?;
+                let field_type = FieldType::parse(descriptor)
+                    .ok_or(format!("Invalid field descriptor {}", descriptor))?;
+                let field_type = VerificationType::from_field_type(&field_type);
+                let class_type = Reference(class_type(class_name)?);
+
+                match instruction {
+                    getstatic => self.push(frame, field_type)?,
+                    putstatic => {
+                        self.pop(frame, &field_type)?;
+                    }
+                    getfield => {
+                        self.pop(frame, &class_type)?;
+                        self.push(frame, field_type)?;
+                    }
+                    _ => {
+                        self.pop(frame, &field_type)?;
+                        // Fields of this class may be set before calling the super constructor
+                        if frame.stack.last() == Some(&UninitializedThis)
+                            && class_name == self.class.name {
+                            frame.stack.pop();
+                        } else {
+                            self.pop(frame, &class_type)?;
+                        }
+                    }
+                }
+            }
+
+            invokevirtual | invokespecial | invokestatic | invokeinterface | invokedynamic => {
+                let (class_name, name, descriptor) = if instruction == invokedynamic {
+                    let name_and_type = match self.class.cp(u2(1)) {
+                        Some(CPInfo::InvokeDynamic(_, name_and_type)) => *name_and_type,
+                        _ => return Err("Expected an InvokeDynamic constant".to_string())
+                    };
+                    if u2(3) != 0 {
+                        return Err("Invalid invokedynamic operands".to_string());
+                    }
+
+                    let (name, descriptor) = self.class.name_and_type(name_and_type)?;
+                    ("", name, descriptor)
+                } else {
+                    self.class.member_ref(u2(1))?
+                };
+
+                let method = MethodDescriptor::parse(descriptor)
+                    .ok_or(format!("Invalid method descriptor {}", descriptor))?;
+                let is_init = name == "<init>";
+                if name.starts_with('<') && (!is_init || instruction != invokespecial
+                    || method.ret != FieldType::V) {
+                    return Err(format!("Illegal call to {}", name));
+                }
+
+                if instruction == invokeinterface {
+                    let words: usize = method.parameters.iter()
+                        .map(|p| if matches!(p, FieldType::J | FieldType::D) { 2 } else { 1 })
+                        .sum();
+                    if u1(3) as usize != words + 1 || u1(4) != 0 {
+                        return Err("Invalid invokeinterface operands".to_string());
+                    }
+                }
+
+                for parameter in method.parameters.iter().rev() {
+                    self.pop(frame, &VerificationType::from_field_type(parameter))?;
+                }
+
+                if is_init {
+                    self.call_constructor(frame, class_name)?;
+                } else if instruction == invokespecial {
+                    self.pop(frame, &VerificationType::object(self.class.name))?;
+                } else if instruction == invokevirtual || instruction == invokeinterface {
+                    self.pop(frame, &Reference(class_type(class_name)?))?;
+                }
+
+                if method.ret != FieldType::V {
+                    self.push(frame, VerificationType::from_field_type(&method.ret))?;
+                }
+            }
+
+            new => {
+                let class_name = self.class.class_name(u2(1))?;
+                if class_name.starts_with('[') {
+                    return Err(format!("Illegal new of array class {}", class_name));
+                }
+                self.push(frame, Uninitialized(pc))?;
+            }
+            newarray => {
+                let component = match u1(1) {
+                    4 => FieldType::Z,
+                    5 => FieldType::C,
+                    6 => FieldType::F,
+                    7 => FieldType::D,
+                    8 => FieldType::B,
+                    9 => FieldType::S,
+                    10 => FieldType::I,
+                    11 => FieldType::J,
+                    atype => return Err(format!("Invalid newarray type {}", atype))
+                };
+                self.pop(frame, &Integer)?;
+                self.push(frame, Reference(FieldType::A(Box::new(component))))?;
+            }
+            anewarray => {
+                let class_name = self.class.class_name(u2(1))?;
+                if class_name.bytes().take_while(|c| *c == b'[').count() >= 255 {
+                    return Err("Array type has too many dimensions".to_string());
+                }
+                self.pop(frame, &Integer)?;
+                self.push(frame, Reference(FieldType::A(Box::new(class_type(class_name)?))))?;
+            }
+            multianewarray => {
+                let class_name = self.class.class_name(u2(1))?;
+                let dimensions = u1(3) as usize;
+                if dimensions == 0 {
+                    return Err("multianewarray with zero dimensions".to_string());
+                }
+                if class_name.bytes().take_while(|c| *c == b'[').count() < dimensions {
+                    return Err(format!("Array class {} has less than {} dimensions", class_name,
+                                       dimensions));
+                }
+                for _ in 0..dimensions {
+                    self.pop(frame, &Integer)?;
+                }
+                self.push(frame, Reference(class_type(class_name)?))?;
+            }
+            arraylength => {
+                self.pop_array(frame, |_| true)?;
+                self.push(frame, Integer)?;
+            }
+            athrow => {
+                self.pop(frame, &VerificationType::object("java/lang/Throwable"))?;
+                return Ok(false);
+            }
+            checkcast | instanceof => {
+                let class_name = self.class.class_name(u2(1))?;
+                self.pop(frame, &VerificationType::object("java/lang/Object"))?;
+                self.push(frame, if instruction == checkcast {
+                    Reference(class_type(class_name)?)
+                } else {
+                    Integer
+                })?;
+            }
+            monitorenter | monitorexit => {
+                self.pop(frame, &VerificationType::object("java/lang/Object"))?;
+            }
+
+            breakpoint | impdep1 | impdep2 =>
+                return Err(format!("Reserved instruction {:?}", instruction))
+        }
+
+        Ok(true)
+    }
+
+    /// Pops `operands` values of the given type and pushes the result of the same type
+    fn arithmetic(&mut self, frame: &mut Frame, t: &VerificationType, operands: usize) ->
+                                                                            Result<(), String> {
+        for _ in 0..operands {
+            self.pop(frame, t)?;
+        }
+        self.push(frame, t.clone())
+    }
+
+    fn convert(&mut self, frame: &mut Frame, from: VerificationType, to: VerificationType) ->
+                                                                            Result<(), String> {
+        self.pop(frame, &from)?;
+        self.push(frame, to)
+    }
+
+    fn return_value(&mut self, frame: &mut Frame, kind: VerificationType) -> Result<(), String> {
+        let expected = VerificationType::from_field_type(&self.descriptor.ret);
+        let matches_kind = match kind {
+            Reference(_) => matches!(expected, Reference(_)),
+            _ => expected == kind
+        };
+        if !matches_kind {
+            return Err(format!("Bad return type: method returns {}", expected));
+        }
+
+        self.pop(frame, &expected).map(|_| ())
+    }
+
+    /// Checks the receiver of a constructor call, and marks it initialized everywhere in the
+    /// frame
+    fn call_constructor(&mut self, frame: &mut Frame, class_name: &str) -> Result<(), String> {
+        let receiver = frame.stack.pop().ok_or("Operand stack underflow")?;
+        let initialized = match &receiver {
+            UninitializedThis => {
+                if class_name != self.class.name && Some(class_name) != self.class.superclass {
+                    return Err(format!("Bad constructor call to {} on uninitialized this",
+                                       class_name));
+                }
+                frame.this_uninit = false;
+                VerificationType::object(self.class.name)
+            }
+            Uninitialized(offset) => {
+                let code = self.code.code;
+                let index = u16::from_be_bytes([code[offset + 1], code[offset + 2]]);
+                let created = self.class.class_name(index)?;
+                if created != class_name {
+                    return Err(format!("Bad constructor call to {} on uninitialized {}",
+                                       class_name, created));
+                }
+                VerificationType::object(created)
+            }
+            t => return Err(format!("Bad type on operand stack: expected an uninitialized \
+                object, found {}", t))
+        };
+
+        for t in frame.locals.iter_mut().chain(frame.stack.iter_mut()) {
+            if *t == receiver {
+                *t = initialized.clone();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::class_parser::parse_class;
+    use crate::helper::fixtures;
+    use crate::vm::class_loader::verifier::{verify, Verification};
+
+    fn verify_class() -> Vec<u8> {
+        fixtures::class_file("test/fixtures/verifier", "Verify")
+    }
+
+    /// Verifies the class with the byte at `offset` from the only occurrence of `bytes` in its
+    /// class file replaced by `value`
+    fn verify_patched(bytes: &[u8], offset: isize, value: u8) -> Verification {
+        let mut buf = verify_class();
+        let mut found = buf.windows(bytes.len()).enumerate().filter(|(_, w)| *w == bytes);
+        let (start, _) = found.next().unwrap();
+        assert!(found.next().is_none());
+
+        buf[start.checked_add_signed(offset).unwrap()] = value;
+        verify(&parse_class(&buf).unwrap()).unwrap()
+    }
+
+    fn failure(verification: Verification) -> String {
+        match verification {
+            Verification::Failed(e) => e,
+            Verification::Passed(_) => panic!("Verification should have failed")
+        }
+    }
+
+    #[test]
+    fn accepts_javac_output() {
+        match verify(&parse_class(&verify_class()).unwrap()).unwrap() {
+            Verification::Passed(checks) => assert!(checks.is_empty()),
+            Verification::Failed(e) => panic!("{}", e)
+        }
+    }
+
+    #[test]
+    fn rejects_bad_code() {
+        // The code of add: iload_0, iload_1, iadd, ireturn
+        let add = [0x1a, 0x1b, 0x60, 0xac];
+
+        // iadd replaced by fadd
+        assert_eq!(failure(verify_patched(&add, 2, 0x62)),
+                   "Bad type on operand stack: expected float, found integer in Verify.add(II)I at \
+                   offset 2");
+
+        // The low byte of max_stack, which precedes max_locals and the code length
+        assert_eq!(failure(verify_patched(&add, -7, 1)),
+                   "Operand stack overflow in Verify.add(II)I at offset 1");
+
+        // The stack map frame is moved from the branch target to the instruction before it. The
+        // StackMapTable attribute of max has 3 bytes holding a single same_frame at offset 7.
+        let frame = [0x00, 0x00, 0x00, 0x03, 0x00, 0x01, 0x07];
+        assert_eq!(failure(verify_patched(&frame, 6, 6)),
+                   "Expecting a stack map frame at branch target 7 in Verify.max(II)I at offset 2");
+    }
+}
@@ -0,0 +1,434 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// Read-only ZIP archive (like a JAR file), kept in memory. Only stored and deflated entries are
+/// supported.
+#[derive(Debug)]
+pub struct ZipArchive {
+    data: Vec<u8>,
+    entries: HashMap<String, ZipEntry>
+}
+
+#[derive(Debug)]
+struct ZipEntry {
+    method: u16,
+    crc: u32,
+    compressed_size: usize,
+    uncompressed_size: usize,
+    local_header_offset: usize
+}
+
+impl ZipArchive {
+    pub fn open(path: &Path) -> Result<ZipArchive, String> {
+        let data = std::fs::read(path).map_err(|e| e.to_string())?;
+        ZipArchive::new(data)
+    }
+
+    pub fn new(data: Vec<u8>) -> Result<ZipArchive, String> {
+        // The end of central directory record is followed by a comment of at most 64 KiB
+        let search_start = data.len().saturating_sub(22 + u16::MAX as usize);
+        let end = (search_start..data.len().saturating_sub(21)).rev()
+            .find(|i| read_u32(&data, *i) == Some(END_OF_CENTRAL_DIRECTORY_SIGNATURE))
+            .ok_or("Not a ZIP archive: no end of central directory record")?;
+
+        let entry_count = read_u16(&data, end + 10).ok_or(TRUNCATED)? as usize;
+        let mut offset = read_u32(&data, end + 16).ok_or(TRUNCATED)? as usize;
+
+        let mut entries = HashMap::with_capacity(entry_count);
+        for _ in 0..entry_count {
+            if read_u32(&data, offset) != Some(CENTRAL_HEADER_SIGNATURE) {
+                return Err(format!("Invalid central directory header at {}", offset));
+            }
+
+            let field = |relative: usize| read_u16(&data, offset + relative).ok_or(TRUNCATED);
+            let name_length = field(28)? as usize;
+            let extra_length = field(30)? as usize;
+            let comment_length = field(32)? as usize;
+
+            let entry = ZipEntry {
+                method: field(10)?,
+                crc: read_u32(&data, offset + 16).ok_or(TRUNCATED)?,
+                compressed_size: read_u32(&data, offset + 20).ok_or(TRUNCATED)? as usize,
+                uncompressed_size: read_u32(&data, offset + 24).ok_or(TRUNCATED)? as usize,
+                local_header_offset: read_u32(&data, offset + 42).ok_or(TRUNCATED)? as usize
+            };
+
+            let name = data.get(offset + 46..offset + 46 + name_length).ok_or(TRUNCATED)?;
+            entries.insert(String::from_utf8_lossy(name).into_owned(), entry);
+
+            offset += 46 + name_length + extra_length + comment_length;
+        }
+
+        Ok(ZipArchive { data, entries })
+    }
+
+    /// Returns the uncompressed contents of the entry, or None if there is no entry with that name
+    pub fn read(&self, name: &str) -> Option<Result<Vec<u8>, String>> {
+        let entry = self.entries.get(name)?;
+        Some(self.read_entry(entry).map_err(|e| format!("{} in entry {}", e, name)))
+    }
+
+    fn read_entry(&self, entry: &ZipEntry) -> Result<Vec<u8>, String> {
+        let offset = entry.local_header_offset;
+        if read_u32(&self.data, offset) != Some(LOCAL_HEADER_SIGNATURE) {
+            return Err("Invalid local file header".to_string());
+        }
+
+        // The sizes in the local header may be missing, the central directory is authoritative
+        let name_length = read_u16(&self.data, offset + 26).ok_or(TRUNCATED)? as usize;
+        let extra_length = read_u16(&self.data, offset + 28).ok_or(TRUNCATED)? as usize;
+        let start = offset + 30 + name_length + extra_length;
+        let compressed = self.data.get(start..start + entry.compressed_size).ok_or(TRUNCATED)?;
+
+        let contents = match entry.method {
+            STORED => compressed.to_vec(),
+            DEFLATED => inflate(compressed, entry.uncompressed_size)?,
+            method => return Err(format!("Unsupported compression method {}", method))
+        };
+
+        if contents.len() != entry.uncompressed_size {
+            return Err(format!("Expected {} bytes, got {}", entry.uncompressed_size,
+                               contents.len()));
+        }
+        if crc32(&contents) != entry.crc {
+            return Err("CRC mismatch".to_string());
+        }
+
+        Ok(contents)
+    }
+}
+
+const TRUNCATED: &str = "Truncated ZIP archive";
+
+fn read_u16(data: &[u8], index: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(index..index + 2)?.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], index: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(index..index + 4)?.try_into().unwrap()))
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+// Base values and extra bits of the length (257..=285) and distance codes (RFC 1951 §3.2.5)
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43,
+    51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4,
+    4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257,
+    385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9,
+    9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+// Order in which the code length code lengths are stored in a dynamic block
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2,
+    14, 1, 15];
+
+const MAX_CODE_LENGTH: usize = 15;
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let byte = *self.data.get(self.position).ok_or("Unexpected end of deflate stream")?;
+            self.position += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+
+        let res = self.buffer & ((1u64 << n) - 1) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(res)
+    }
+
+    fn align_to_byte(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// Canonical Huffman code, stored as the number of codes per length and the symbols ordered by
+/// their code
+struct Huffman {
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    symbols: Vec<u16>
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; MAX_CODE_LENGTH + 2];
+        for length in 1..=MAX_CODE_LENGTH {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0; offsets[MAX_CODE_LENGTH + 1] as usize];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length as usize] as usize] = symbol as u16;
+                offsets[*length as usize] += 1;
+            }
+        }
+
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..=MAX_CODE_LENGTH {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err("Invalid Huffman code in deflate stream".to_string())
+    }
+}
+
+/// Decompresses a raw deflate stream (RFC 1951), failing as soon as the output would exceed `size`
+/// bytes, so a corrupt or malicious entry can not allocate more than its declared size
+pub fn inflate(data: &[u8], size: usize) -> Result<Vec<u8>, String> {
+    let mut reader = BitReader { data, position: 0, buffer: 0, count: 0 };
+    let mut output = Vec::with_capacity(size);
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let header = data.get(reader.position..reader.position + 4)
+                    .ok_or("Unexpected end of deflate stream")?;
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                if length != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err("Invalid stored block length".to_string());
+                }
+
+                let start = reader.position + 4;
+                let block = data.get(start..start + length as usize)
+                    .ok_or("Unexpected end of deflate stream")?;
+                check_size(output.len() + block.len(), size)?;
+                output.extend_from_slice(block);
+                reader.position = start + length as usize;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut reader, &mut output, size, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, size, &literals, &distances)?;
+            }
+            _ => return Err("Invalid deflate block type".to_string())
+        }
+
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+fn read_dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for i in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[*i] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let (value, repeat) = match code_lengths.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..i].last()
+                    .ok_or("Repeated code length without a previous one")?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize)
+        };
+
+        let run = lengths.get_mut(i..i + repeat).ok_or("Too many code lengths")?;
+        run.fill(value);
+        i += repeat;
+    }
+
+    Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])))
+}
+
+fn check_size(length: usize, size: usize) -> Result<(), String> {
+    if length > size {
+        Err(format!("Deflate stream is longer than the expected {} bytes", size))
+    } else {
+        Ok(())
+    }
+}
+
+fn inflate_block(reader: &mut BitReader, output: &mut Vec<u8>, size: usize, literals: &Huffman,
+                 distances: &Huffman) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => {
+                check_size(output.len() + 1, size)?;
+                output.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err("Invalid length code".to_string());
+                }
+                let length = LENGTH_BASE[index] as usize
+                    + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+
+                let index = distances.decode(reader)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err("Invalid distance code".to_string());
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+
+                if distance > output.len() {
+                    return Err("Distance too far back in deflate stream".to_string());
+                }
+                check_size(output.len() + length, size)?;
+
+                // The copied range may overlap with the bytes being written
+                let start = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::OnceLock;
+    use crate::helper::fixtures;
+    use crate::vm::class_loader::zip::{crc32, inflate, ZipArchive};
+
+    /// An archive with a stored entry, and deflated ones using a fixed and a dynamic Huffman code
+    pub fn test_zip() -> Vec<u8> {
+        static ARCHIVE: OnceLock<Vec<u8>> = OnceLock::new();
+
+        ARCHIVE.get_or_init(|| {
+            let directory = fixtures::temp_dir("zip");
+            std::fs::write(directory.join("stored.class"), b"stored entry\n").unwrap();
+            std::fs::write(directory.join("fixed.class"), b"abcabcabcabc fixed").unwrap();
+            std::fs::write(directory.join("dynamic.class"), dynamic_contents()).unwrap();
+
+            let archive = directory.join("test.zip");
+            fixtures::jar(&archive, None, &directory, &["fixed.class", "dynamic.class"], true);
+            fixtures::jar(&archive, None, &directory, &["stored.class"], false);
+
+            let data = std::fs::read(&archive).unwrap();
+            std::fs::remove_dir_all(directory).unwrap();
+            data
+        }).clone()
+    }
+
+    fn dynamic_contents() -> Vec<u8> {
+        (0..200).map(|i| format!("line {} of the deflated entry\n", i)).collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn reads_entries() {
+        let archive = ZipArchive::new(test_zip()).unwrap();
+
+        assert_eq!(archive.read("stored.class").unwrap().unwrap(), b"stored entry\n");
+        assert_eq!(archive.read("fixed.class").unwrap().unwrap(), b"abcabcabcabc fixed");
+        assert_eq!(archive.read("dynamic.class").unwrap().unwrap(), dynamic_contents());
+        assert!(archive.read("missing.class").is_none());
+    }
+
+    #[test]
+    fn rejects_corrupt_archives() {
+        assert!(ZipArchive::new(b"not a zip".to_vec()).is_err());
+
+        let mut corrupt = test_zip();
+        let position = corrupt.windows(12).position(|w| w == b"stored entry").unwrap();
+        corrupt[position] = b'S';
+        let archive = ZipArchive::new(corrupt).unwrap();
+        assert!(archive.read("stored.class").unwrap().unwrap_err().contains("CRC mismatch"));
+    }
+
+    #[test]
+    fn rejects_entries_longer_than_declared() {
+        let mut archive = test_zip();
+        let name = archive.windows(13).rposition(|w| w == b"dynamic.class").unwrap();
+        // The name follows the 46 bytes of the central directory header
+        let size = name - 46 + 24;
+        archive[size..size + 4].copy_from_slice(&100u32.to_le_bytes());
+
+        let archive = ZipArchive::new(archive).unwrap();
+        assert!(archive.read("dynamic.class").unwrap().unwrap_err()
+            .contains("longer than the expected 100 bytes"));
+    }
+
+    #[test]
+    fn inflates_stored_blocks() {
+        // A single final stored block
+        let data = [0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o'];
+        assert_eq!(inflate(&data, 5).unwrap(), b"hello");
+        assert!(inflate(&data[..7], 5).is_err());
+        assert!(inflate(&data, 4).unwrap_err().contains("longer than the expected 4 bytes"));
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+}
@@ -59,9 +59,16 @@ pub enum Instruction {
     aload_2 = 44,
     aload_3 = 45,
     iaload = 46,
+    laload = 47,
+    faload = 48,
+    daload = 49,
     aaload = 50,
+    baload = 51,
+    caload = 52,
+    saload = 53,
     istore = 54,
     lstore = 55,
+    fstore = 56,
     dstore = 57,
     astore = 58,
     istore_0 = 59,
@@ -72,6 +79,10 @@ pub enum Instruction {
     lstore_1 = 64,
     lstore_2 = 65,
     lstore_3 = 66,
+    fstore_0 = 67,
+    fstore_1 = 68,
+    fstore_2 = 69,
+    fstore_3 = 70,
     dstore_0 = 71,
     dstore_1 = 72,
     dstore_2 = 73,
@@ -81,32 +92,51 @@ pub enum Instruction {
     astore_2 = 77,
     astore_3 = 78,
     iastore = 79,
+    lastore = 80,
+    fastore = 81,
+    dastore = 82,
     aastore = 83,
+    bastore = 84,
+    castore = 85,
+    sastore = 86,
     pop = 87,
     dup = 89,
     iadd = 96,
     ladd = 97,
+    fadd = 98,
     dadd = 99,
     isub = 100,
+    fsub = 102,
     dsub = 103,
     imul = 104,
     lmul = 105,
+    fmul = 106,
     dmul = 107,
     idiv = 108,
     ldiv = 109,
+    fdiv = 110,
     ddiv = 111,
     lrem = 113,
+    frem = 114,
+    fneg = 118,
     dneg = 119,
     ishl = 120,
     ixor = 130,
     iinc = 132,
     i2l = 133,
+    i2f = 134,
     l2i = 136,
+    l2f = 137,
     l2d = 138,
+    f2i = 139,
+    f2l = 140,
     f2d = 141,
+    d2f = 144,
     i2b = 145,
     i2c = 146,
     lcmp = 148,
+    fcmpl = 149,
+    fcmpg = 150,
     ifeq = 153,
     ifne = 154,
     iflt = 155,
@@ -119,8 +149,11 @@ pub enum Instruction {
     if_acmpeq = 165,
     if_acmpne = 166,
     goto = 167,
+    tableswitch = 170,
+    lookupswitch = 171,
     ireturn = 172,
     lreturn = 173,
+    freturn = 174,
     dreturn = 175,
     areturn = 176,
     _return = 177,
@@ -132,6 +165,7 @@ pub enum Instruction {
     invokespecial = 183,
     invokestatic = 184,
     invokeinterface = 185,
+    invokedynamic = 186,
     new = 187,
     newarray = 188,
     anewarray = 189,
@@ -139,6 +173,11 @@ pub enum Instruction {
     athrow = 191,
     checkcast = 192,
     instanceof = 193,
+    monitorenter = 194,
+    monitorexit = 195,
+    multianewarray = 197,
+    ifnull = 198,
+    ifnonnull = 199,
     breakpoint = 202,
     impdep1 = 254,
     impdep2 = 255,
@@ -156,11 +195,25 @@ impl Instruction {
     }
 }
 
+/// Reads a big-endian signed 32-bit operand, or None if it runs past the end of the code
+pub fn read_i32(code: &[u8], index: usize) -> Option<i32> {
+    code.get(index..index.checked_add(4)?)
+        .map(|bytes| i32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// Start of the 4-byte aligned operands of a tableswitch or lookupswitch instruction at `pc`
+#[inline(always)]
+pub const fn switch_operands_start(pc: usize) -> usize {
+    (pc + 4) & !3
+}
+
+/// Length of the instruction at `pc` including its operands, or None if the operands of a switch
+/// instruction run past the end of the code
 #[inline(always)]
-pub const fn instruction_length(instr: Instruction) -> usize {
+pub fn instruction_length(instr: Instruction, code: &[u8], pc: usize) -> Option<usize> {
     use Instruction::*;
 
-    match instr {
+    Some(match instr {
         nop => 1,
         aconst_null => 1,
         iconst_m1 => 1,
@@ -195,10 +248,11 @@ pub const fn instruction_length(instr: Instruction) -> usize {
         fload_0 | fload_1 | fload_2 | fload_3 => 1,
         dload_0 | dload_1 | dload_2 | dload_3 => 1,
         aload_0 | aload_1 | aload_2 | aload_3 => 1,
-        iaload => 1,
-        aaload => 1,
+        iaload | laload | faload | daload => 1,
+        aaload | baload | caload | saload => 1,
         istore => 2,
         lstore => 2,
+        fstore => 2,
         dstore => 2,
         astore => 2,
         istore_0 => 1,
@@ -209,40 +263,67 @@ pub const fn instruction_length(instr: Instruction) -> usize {
         lstore_1 => 1,
         lstore_2 => 1,
         lstore_3 => 1,
+        fstore_0 | fstore_1 | fstore_2 | fstore_3 => 1,
         dstore_0 | dstore_1 | dstore_2 | dstore_3 => 1,
         astore_0 | astore_1 | astore_2 | astore_3 => 1,
-        iastore => 1,
-        aastore => 1,
+        iastore | lastore | fastore | dastore => 1,
+        aastore | bastore | castore | sastore => 1,
         pop => 1,
         dup => 1,
         iadd => 1,
         ladd => 1,
+        fadd => 1,
         dadd => 1,
         isub => 1,
+        fsub => 1,
         dsub => 1,
         imul => 1,
         lmul => 1,
+        fmul => 1,
         dmul => 1,
         idiv => 1,
         ldiv => 1,
+        fdiv => 1,
         ddiv => 1,
         lrem => 1,
+        frem => 1,
+        fneg => 1,
         dneg => 1,
         ishl => 1,
         ixor => 1,
         iinc => 3,
         i2l => 1,
+        i2f => 1,
         l2i => 1,
+        l2f => 1,
         l2d => 1,
+        f2i => 1,
+        f2l => 1,
         f2d => 1,
+        d2f => 1,
         i2b => 1,
         i2c => 1,
         lcmp => 1,
+        fcmpl | fcmpg => 1,
         ifeq | ifne | iflt | ifge | ifgt | ifle | if_icmpeq | if_icmpge | if_icmpgt => 3,
         if_acmpeq | if_acmpne => 3,
         goto => 3,
+        tableswitch => {
+            let start = switch_operands_start(pc);
+            let low = read_i32(code, start + 4)? as i64;
+            let high = read_i32(code, start + 8)? as i64;
+
+            start - pc + 12 + 4 * (high - low + 1).max(0) as usize
+        }
+        lookupswitch => {
+            let start = switch_operands_start(pc);
+            let npairs = read_i32(code, start + 4)?.max(0) as usize;
+
+            start - pc + 8 + 8 * npairs
+        }
         ireturn => 1,
         lreturn => 1,
+        freturn => 1,
         dreturn => 1,
         areturn => 1,
         _return => 1,
@@ -254,6 +335,7 @@ pub const fn instruction_length(instr: Instruction) -> usize {
         invokespecial => 3,
         invokestatic => 3,
         invokeinterface => 5,
+        invokedynamic => 5,
         new => 3,
         newarray => 2,
         anewarray => 3,
@@ -261,8 +343,48 @@ pub const fn instruction_length(instr: Instruction) -> usize {
         athrow => 1,
         checkcast => 3,
         instanceof => 3,
+        monitorenter | monitorexit => 1,
+        multianewarray => 4,
+        ifnull | ifnonnull => 3,
         breakpoint => 1,
         impdep1 => 1,
         impdep2 => 1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::instructions::{Instruction, instruction_length, read_i32};
+
+    #[test]
+    fn switch_length() {
+        // tableswitch at pc 1 with two bytes of padding, low = 1 and high = 3
+        let code = [0, 170, 0, 0,
+            0, 0, 0, 20,
+            0, 0, 0, 1,
+            0, 0, 0, 3,
+            0, 0, 0, 4, 0, 0, 0, 8, 0, 0, 0, 12];
+        assert_eq!(instruction_length(Instruction::tableswitch, &code, 1), Some(27));
+
+        // lookupswitch at pc 0 with three bytes of padding and one pair
+        let code = [171, 0, 0, 0,
+            0, 0, 0, 16,
+            0, 0, 0, 1,
+            0, 0, 0, 42, 0, 0, 0, 8];
+        assert_eq!(instruction_length(Instruction::lookupswitch, &code, 0), Some(20));
+
+        assert_eq!(instruction_length(Instruction::goto, &[], 0), Some(3));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn truncated_operands() {
+        assert_eq!(read_i32(&[0, 0, 1, 2], 0), Some(258));
+        assert_eq!(read_i32(&[0, 0, 1, 2], 1), None);
+
+        // tableswitch missing its high bound
+        let code = [170, 0, 0, 0,
+            0, 0, 0, 20,
+            0, 0, 0, 1];
+        assert_eq!(instruction_length(Instruction::tableswitch, &code, 0), None);
+    }
+}
@@ -1,9 +1,9 @@
 
 use std::ptr::{null, null_mut};
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU16, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use crate::Class;
 use crate::vm::class::class::ClassRef;
+use crate::vm::thread::monitor::Monitor;
 
 
 /// Points to a variable length struct having the following data layout:
@@ -11,7 +11,9 @@ use crate::vm::class::class::ClassRef;
 ///     header: ObjectHeader,
 ///     data: [AtomicU64, FIELD_NO]
 /// }
-#[derive(Copy, Clone, Debug, PartialEq)]
+/// Arrays store their length in the first word, followed by the elements packed according to
+/// the size of the component type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(transparent)]
 pub struct ObjectPtr {
     pub ptr: *const AtomicU64
@@ -37,7 +39,7 @@ impl ObjectPtr {
         }
     }
 
-    pub fn to_val(&self) -> u64 {
+    pub fn to_val(self) -> u64 {
         self.ptr as u64
     }
 
@@ -49,6 +51,14 @@ impl ObjectPtr {
         }
     }
 
+    pub fn monitor(&self) -> &Monitor {
+        unsafe {
+            let header: &ObjectHeader = &*self.ptr.cast();
+
+            &header.monitor
+        }
+    }
+
     pub fn get_field(&self, field_no: usize) -> u64 {
         let class = self.get_class();
         debug_assert!(field_no < class.data.instance_field_count
@@ -59,7 +69,7 @@ impl ObjectPtr {
         let ptr: *const AtomicU64 = ptr.cast();
 
         unsafe {
-            (*ptr.offset(field_no as isize)).load(Ordering::Relaxed)
+            (*ptr.add(field_no)).load(Ordering::Relaxed)
         }
     }
 
@@ -73,54 +83,98 @@ impl ObjectPtr {
         let ptr: *const AtomicU64 = ptr.cast();
 
         unsafe {
-            (*ptr.offset(field_no as isize)).store(val, Ordering::Relaxed);
+            (*ptr.add(field_no)).store(val, Ordering::Relaxed);
         }
     }
 
+    /// Start of the elements of an array, right after its length
+    fn array_data(&self) -> *const u8 {
+        let mut ptr: *const ObjectHeader = self.ptr.cast();
+        ptr = unsafe { ptr.offset(1) };
+        let ptr: *const AtomicU64 = ptr.cast();
+
+        unsafe { ptr.offset(1).cast() }
+    }
+
+    /// Loads an array element, zero-extended to 64 bits
     pub fn get_from_array(&self, index: usize) -> Option<u64> {
         let length = self.get_field(0);
         if index >= length as usize {
             return None;
         }
 
-        let mut ptr: *const ObjectHeader = self.ptr.cast();
-        ptr = unsafe { ptr.offset(1) };
-        let ptr: *const AtomicU64 = ptr.cast();
-
-        unsafe {
-            Some((*ptr.offset(1 + index as isize)).load(Ordering::Relaxed))
-        }
+        let ptr = self.array_data();
+        let val = unsafe {
+            match self.get_class().element_size() {
+                1 => (*ptr.cast::<AtomicU8>().add(index)).load(Ordering::Relaxed) as u64,
+                2 => (*ptr.cast::<AtomicU16>().add(index)).load(Ordering::Relaxed) as u64,
+                4 => (*ptr.cast::<AtomicU32>().add(index)).load(Ordering::Relaxed) as u64,
+                _ => (*ptr.cast::<AtomicU64>().add(index)).load(Ordering::Relaxed)
+            }
+        };
+
+        Some(val)
     }
 
+    /// Stores an array element, truncating the value to the size of the element type
     pub fn store_to_array(&self, index: usize, val: u64) -> Option<()> {
         let length = self.get_field(0);
         if index >= length as usize {
             return None;
         }
 
-        let mut ptr: *const ObjectHeader = self.ptr.cast();
-        ptr = unsafe { ptr.offset(1) };
-        let ptr: *const AtomicU64 = ptr.cast();
-
+        let ptr = self.array_data();
         unsafe {
-            (*ptr.offset(1 + index as isize)).store(val, Ordering::Relaxed)
+            match self.get_class().element_size() {
+                1 => (*ptr.cast::<AtomicU8>().add(index)).store(val as u8, Ordering::Relaxed),
+                2 => (*ptr.cast::<AtomicU16>().add(index)).store(val as u16, Ordering::Relaxed),
+                4 => (*ptr.cast::<AtomicU32>().add(index)).store(val as u32, Ordering::Relaxed),
+                _ => (*ptr.cast::<AtomicU64>().add(index)).store(val, Ordering::Relaxed)
+            }
         }
         Some(())
     }
+
+    /// Contents of a byte or boolean array
+    pub fn byte_array(&self) -> &[u8] {
+        debug_assert_eq!(self.get_class().element_size(), 1);
+
+        unsafe { std::slice::from_raw_parts(self.array_data(), self.get_field(0) as usize) }
+    }
+
+    /// Contents of a char or short array
+    pub fn char_array(&self) -> &[u16] {
+        debug_assert_eq!(self.get_class().element_size(), 2);
+
+        unsafe {
+            std::slice::from_raw_parts(self.array_data().cast(), self.get_field(0) as usize)
+        }
+    }
+
+    /// Copies the characters into a char or short array, starting at `index`
+    pub fn set_chars(&self, index: usize, chars: &[u16]) {
+        debug_assert_eq!(self.get_class().element_size(), 2);
+        assert!(index + chars.len() <= self.get_field(0) as usize);
+
+        unsafe {
+            let ptr = self.array_data().cast::<u16>().cast_mut().add(index);
+            std::ptr::copy_nonoverlapping(chars.as_ptr(), ptr, chars.len());
+        }
+    }
 }
 
 #[derive(Debug)]
 // #[repr(align(64))]
 pub struct ObjectHeader {
     pub class: *const Class,
-    pub lock: Mutex<()>
+    pub monitor: Monitor
 }
 
 impl Clone for ObjectHeader {
     fn clone(&self) -> Self {
         Self {
             class: self.class,
-            lock: Default::default()
+            monitor: Default::default()
         }
     }
 }
@@ -129,7 +183,7 @@ impl ObjectHeader {
     pub fn new(ptr: *const Class) -> ObjectHeader {
         ObjectHeader {
             class: ptr,
-            lock: Default::default()
+            monitor: Default::default()
         }
     }
 }
@@ -138,7 +192,7 @@ impl Default for ObjectHeader {
     fn default() -> Self {
         ObjectHeader {
             class: null(),
-            lock: Default::default()
+            monitor: Default::default()
         }
     }
 }
@@ -149,6 +203,7 @@ unsafe impl Send for ObjectHeader {}
 #[cfg(test)]
 mod tests {
     use crate::{VM, VM_HANDLER};
+    use crate::vm::pool::object::ObjectArena;
 
     #[test]
     fn object_test() {
@@ -180,4 +235,35 @@ mod tests {
             assert_eq!(obj.get_from_array(i), Some(i as u64));
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn packed_array_test() {
+        let vm = VM_HANDLER.get_or_init(|| VM::vm_init(false));
+
+        let bytes = vm.object_arena.new_array(vm.load_class("[B").unwrap(), 1000);
+        let chars = vm.object_arena.new_array(vm.load_class("[C").unwrap(), 1000);
+        let longs = vm.object_arena.new_array(vm.load_class("[J").unwrap(), 1000);
+        // Words taken by the elements, beyond those of the header and the length
+        let empty = vm.object_arena.new_array(vm.load_class("[B").unwrap(), 0);
+        let element_words = |array| ObjectArena::object_size(array) - ObjectArena::object_size(empty);
+        assert_eq!(element_words(bytes), 125);
+        assert_eq!(element_words(chars), 250);
+        assert_eq!(element_words(longs), 1000);
+
+        // Elements are truncated to their type and do not overlap their neighbours
+        bytes.store_to_array(1, 0x1ff);
+        chars.store_to_array(1, 0x1_ffff);
+        longs.store_to_array(1, u64::MAX);
+        for array in [bytes, chars, longs] {
+            assert_eq!(array.get_from_array(0), Some(0));
+            assert_eq!(array.get_from_array(2), Some(0));
+        }
+        assert_eq!(bytes.get_from_array(1), Some(0xff));
+        assert_eq!(chars.get_from_array(1), Some(0xffff));
+        assert_eq!(longs.get_from_array(1), Some(u64::MAX));
+
+        assert_eq!(bytes.get_from_array(999), Some(0));
+        assert_eq!(bytes.get_from_array(1000), None);
+        assert_eq!(&bytes.byte_array()[..3], &[0, 0xff, 0]);
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::{ClassRef, VM, VMThread};
+use crate::vm::class::constant_pool::CPEntry;
+use crate::vm::object::ObjectPtr;
+use crate::vm::pool::object::ObjectArena;
+
+/// Stop-the-world mark-sweep collector.
+///
+/// Every OS thread executing Java code registers its outermost VMThread as a mutator, which the
+/// VMThreads nested in it (e.g. running a static initializer) join. A collection only starts once
+/// all mutators have stopped at a safepoint or are blocked (e.g. on a monitor), where every
+/// reference they hold is on the frames of their VMThreads. Frames are untyped, so they are
+/// scanned conservatively, while heap objects and static fields are traced precisely based on
+/// their field descriptors.
+#[derive(Default)]
+pub struct GarbageCollector {
+    state: Mutex<GcState>,
+    cond: Condvar,
+    pub collections: AtomicUsize
+}
+
+#[derive(Default)]
+struct GcState {
+    next_id: usize,
+    mutators: HashMap<usize, Mutator>,
+    collecting: bool
+}
+
+struct Mutator {
+    /// The VMThreads running on the OS thread, outermost first
+    threads: Vec<*const VMThread>,
+    stopped: bool
+}
+
+unsafe impl Send for Mutator {}
+
+impl GarbageCollector {
+    pub fn register(&self, thread: *const VMThread) -> usize {
+        let mut state = self.state.lock().unwrap();
+        while state.collecting {
+            state = self.cond.wait(state).unwrap();
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.mutators.insert(id, Mutator { threads: vec![thread], stopped: false });
+
+        id
+    }
+
+    /// Adds a VMThread started by one already running on the mutator's OS thread. The mutator is
+    /// running, so no collection can be in progress.
+    pub fn join(&self, id: usize, thread: *const VMThread) {
+        let mut state = self.state.lock().unwrap();
+        state.mutators.get_mut(&id).unwrap().threads.push(thread);
+    }
+
+    /// Removes the innermost VMThread added by `join`
+    pub fn leave(&self, id: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.mutators.get_mut(&id).unwrap().threads.pop();
+    }
+
+    pub fn unregister(&self, id: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.mutators.remove(&id);
+
+        self.cond.notify_all();
+    }
+
+    /// Called by a mutator at a safepoint after a collection was requested. The first mutator to
+    /// arrive performs the collection once every other one has stopped.
+    pub fn safepoint(&self, vm: &VM, id: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.mutators.get_mut(&id).unwrap().stopped = true;
+
+        if state.collecting {
+            self.cond.notify_all();
+            while state.collecting {
+                state = self.cond.wait(state).unwrap();
+            }
+        } else {
+            state.collecting = true;
+            while state.mutators.values().any(|m| !m.stopped) {
+                state = self.cond.wait(state).unwrap();
+            }
+
+            self.collect(vm, &state);
+
+            state.collecting = false;
+            self.cond.notify_all();
+        }
+
+        state.mutators.get_mut(&id).unwrap().stopped = false;
+    }
+
+    /// Runs an operation that may block indefinitely (like waiting for a monitor). The mutator
+    /// counts as stopped meanwhile, so it can not hold up a collection requested by other threads.
+    /// Its frames must not change until the operation returns.
+    pub fn blocking<T>(&self, id: Option<usize>, f: impl FnOnce() -> T) -> T {
+        let Some(id) = id else {
+            return f();
+        };
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.mutators.get_mut(&id).unwrap().stopped = true;
+            self.cond.notify_all();
+        }
+
+        let res = f();
+
+        let mut state = self.state.lock().unwrap();
+        while state.collecting {
+            state = self.cond.wait(state).unwrap();
+        }
+        state.mutators.get_mut(&id).unwrap().stopped = false;
+
+        res
+    }
+
+    fn collect(&self, vm: &VM, state: &GcState) {
+        let before = vm.object_arena.used_bytes();
+
+        let mut marker = Marker::new(&vm.object_arena);
+        for thread in state.mutators.values().flat_map(|m| &m.threads) {
+            let thread = unsafe { &**thread };
+            for frame in thread.stack.iter() {
+                for val in frame.values() {
+                    marker.mark_value(*val);
+                }
+            }
+            for root in unsafe { thread.local_roots() } {
+                marker.mark_value(root.to_val());
+            }
+        }
+
+        if let Some(error) = vm.out_of_memory_error.get() {
+            marker.mark_value(error.to_val());
+        }
+
+        for class in vm.classes.lock().unwrap().iter() {
+            let class = ClassRef::new(&**class);
+
+            for (i, field) in class.data.fields.iter().filter(|f| f.is_static()).enumerate() {
+                if field.descriptor.is_reference() {
+                    marker.mark_value(class.data.static_fields[i].load(Ordering::Relaxed));
+                }
+            }
+
+            for i in 1..=class.data.constant_pool.len() {
+                if let CPEntry::ConstantString(ptr) = class.get_cp_entry(i) {
+                    marker.mark_value(ptr.to_val());
+                }
+            }
+        }
+
+        // Class loaders stay alive as long as their classes, which are never unloaded
+        for class_loader in vm.loaded_classes.lock().unwrap().keys() {
+            marker.mark_value(class_loader.to_val());
+        }
+
+        for thread in vm.threads.objects() {
+            marker.mark_value(thread.to_val());
+        }
+
+        marker.trace();
+        // Interned strings are weak, the table must not refer to the objects about to be freed
+        vm.string_pool.sweep_interned(|obj| vm.object_arena.is_marked(obj));
+        vm.object_arena.sweep();
+
+        self.collections.fetch_add(1, Ordering::Relaxed);
+        if vm.args.read().unwrap().print_gc {
+            eprintln!("[GC] {}K -> {}K ({}K)", before / 1024,
+                      vm.object_arena.used_bytes() / 1024,
+                      vm.object_arena.capacity_bytes() / 1024);
+        }
+    }
+}
+
+fn has_reference_elements(array_class: ClassRef) -> bool {
+    let component = &array_class.data.name[1..];
+    !(component.len() == 1 && "BCDFIJSZ".contains(component))
+}
+
+/// Computes the indices of the reference-typed instance fields, superclass fields first
+fn reference_fields(class: ClassRef, fields: &mut Vec<usize>) {
+    if class.data.superclass.ptr().is_null() {
+        return;
+    }
+
+    reference_fields(class.data.superclass, fields);
+
+    let instance_fields = class.data.fields.iter().filter(|f| !f.is_static());
+    for (i, field) in instance_fields.enumerate() {
+        if field.descriptor.is_reference() {
+            fields.push(class.data.superclass.data.instance_field_count + i);
+        }
+    }
+}
+
+pub struct Marker<'a> {
+    arena: &'a ObjectArena,
+    worklist: Vec<ObjectPtr>,
+    field_cache: HashMap<ClassRef, Vec<usize>>
+}
+
+impl<'a> Marker<'a> {
+    pub fn new(arena: &'a ObjectArena) -> Self {
+        Marker {
+            arena,
+            worklist: vec![],
+            field_cache: Default::default()
+        }
+    }
+
+    /// Marks the object the value points to, if it is a pointer to an object at all
+    pub fn mark_value(&mut self, val: u64) {
+        if let Some(obj) = self.arena.object_at(val) {
+            if self.arena.mark(obj) {
+                self.worklist.push(obj);
+            }
+        }
+    }
+
+    pub fn trace(&mut self) {
+        while let Some(obj) = self.worklist.pop() {
+            let class = obj.get_class();
+
+            if class.is_array() {
+                if has_reference_elements(class) {
+                    for i in 0..obj.get_field(0) as usize {
+                        self.mark_value(obj.get_from_array(i).unwrap());
+                    }
+                }
+            } else {
+                let fields = self.field_cache.entry(class).or_insert_with(|| {
+                    let mut fields = vec![];
+                    reference_fields(class, &mut fields);
+                    fields
+                });
+
+                let values: Vec<u64> = fields.iter().map(|i| obj.get_field(*i)).collect();
+                for val in values {
+                    self.mark_value(val);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{VM, VM_HANDLER};
+    use crate::vm::pool::gc::Marker;
+    use crate::vm::pool::object::ObjectArena;
+    use crate::vm::thread::thread::allocate;
+
+    #[test]
+    fn unreachable_objects_are_freed() {
+        let vm = VM_HANDLER.get_or_init(|| VM::vm_init(false));
+        let arena = ObjectArena::new(1024 * 1024);
+
+        let int_array = vm.load_class("[I").unwrap();
+        let object_array = vm.load_class("[java/lang/Object").unwrap();
+
+        let root = arena.new_array(object_array, 2);
+        let reachable = arena.new_array(int_array, 16);
+        let unreachable = arena.new_array(int_array, 16);
+        root.store_to_array(1, reachable.to_val());
+        reachable.store_to_array(3, 42);
+
+        let mut marker = Marker::new(&arena);
+        marker.mark_value(root.to_val());
+        marker.trace();
+        arena.sweep();
+
+        assert!(arena.object_at(root.to_val()).is_some());
+        assert!(arena.object_at(reachable.to_val()).is_some());
+        assert!(arena.object_at(unreachable.to_val()).is_none());
+        assert_eq!(reachable.get_from_array(3), Some(42));
+
+        let reused = arena.new_array(int_array, 16);
+        assert_eq!(reused, unreachable);
+        assert_eq!(reused.get_from_array(3), Some(0));
+    }
+
+    #[test]
+    fn heap_stays_bounded() {
+        let vm = VM_HANDLER.get_or_init(|| VM::vm_init(false));
+        let arena = ObjectArena::new(64 * 1024);
+
+        let object_array = vm.load_class("[java/lang/Object").unwrap();
+        let root = arena.new_array(object_array, 1);
+
+        for _ in 0..10_000 {
+            let obj = match arena.try_new_object(vm.object_class) {
+                Some(obj) => obj,
+                None => {
+                    let mut marker = Marker::new(&arena);
+                    marker.mark_value(root.to_val());
+                    marker.trace();
+                    arena.sweep();
+
+                    arena.new_object(vm.object_class)
+                }
+            };
+            root.store_to_array(0, obj.to_val());
+        }
+
+        assert!(arena.used_bytes() <= arena.capacity_bytes());
+        assert!(arena.object_at(root.get_from_array(0).unwrap()).is_some());
+    }
+
+    #[test]
+    fn exhausted_heap_is_an_out_of_memory_error() {
+        let vm = VM_HANDLER.get_or_init(|| VM::vm_init(false));
+        let long_array = vm.load_class("[J").unwrap();
+
+        let result = allocate(None, |arena| arena.try_new_array(long_array, i32::MAX as usize));
+        assert_eq!(result, Err("OutOfMemoryError: Java heap space".to_string()));
+    }
+}
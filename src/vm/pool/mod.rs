@@ -1,2 +1,3 @@
 pub mod object;
+pub mod gc;
 pub mod string;
@@ -1,87 +1,278 @@
 use std::alloc;
 use std::alloc::Layout;
-use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
 use crate::{ClassRef, ObjectHeader};
 use crate::vm::object::ObjectPtr;
 
+pub const DEFAULT_HEAP_SIZE: usize = 512 * 1024 * 1024;
+
+/// Heap of Java objects. Objects are never moved, as raw pointers to them are stored everywhere.
+/// Memory is handed out from the gaps between live objects found by the last collection, and
+/// from a bump pointer past the last live object.
+///
+/// Every allocated object has its first word recorded in the `starts` bitmap, which lets the
+/// collector tell real object pointers apart from arbitrary values on the operand stack.
 #[derive(Debug)]
 pub struct ObjectArena {
-    pub last_index: AtomicUsize,
     arena: *mut AtomicU64,
-    cap: usize
+    cap: usize,
+    allocation: Mutex<FreeList>,
+
+    starts: Vec<AtomicU64>,
+    marks: Vec<AtomicU64>,
+
+    used: AtomicUsize,
+    allocated_since_gc: AtomicUsize,
+    gc_threshold: AtomicUsize,
+    gc_requested: AtomicBool,
 }
 
 unsafe impl Sync for ObjectArena {}
 unsafe impl Send for ObjectArena {}
 
+#[derive(Debug, Default)]
+struct FreeList {
+    last_index: usize,
+    chunks: Vec<(usize, usize)>, // (start, length) in words, ordered by address
+    current: usize
+}
+
 const HEADER_SIZE: usize = std::mem::size_of::<ObjectHeader>();
 const HEADER_ALIGN: usize = std::mem::align_of::<ObjectHeader>();
+const WORD_SIZE: usize = std::mem::size_of::<AtomicU64>();
+
+impl FreeList {
+    fn take(&mut self, size: usize, cap: usize) -> Option<usize> {
+        let min_size = ObjectArena::calc_align(0);
+
+        let mut i = self.current;
+        while i < self.chunks.len() {
+            let (start, length) = self.chunks[i];
+            if length >= size {
+                self.chunks[i] = (start + size, length - size);
+                if i == self.current && length - size < min_size {
+                    self.current += 1;
+                }
+
+                return Some(start);
+            } else if i == self.current && length < min_size {
+                self.current += 1;
+            }
+
+            i += 1;
+        }
+
+        if self.last_index + size >= cap {
+            return None;
+        }
+
+        let offset = self.last_index;
+        self.last_index += size;
+        Some(offset)
+    }
+}
 
 impl ObjectArena {
     fn calc_align(size: usize) -> usize {
-        let size = HEADER_SIZE + size * std::mem::size_of::<AtomicU64>();
+        let size = HEADER_SIZE + size * WORD_SIZE;
         let i = size / HEADER_ALIGN;
 
         let total_size = i * HEADER_ALIGN;
 
         if size > total_size {
-            (total_size + HEADER_ALIGN) / std::mem::size_of::<AtomicU64>()
+            (total_size + HEADER_ALIGN) / WORD_SIZE
         } else {
-            total_size / std::mem::size_of::<AtomicU64>()
+            total_size / WORD_SIZE
         }
     }
 
-    pub fn new() -> Self {
-        const SIZE: usize = 1000 * 1000 * 1024;
+    pub fn new(size: usize) -> Self {
+        let cap = size / WORD_SIZE;
 
-        let layout = Layout::array::<AtomicU64>(SIZE).unwrap();
+        let layout = Layout::array::<AtomicU64>(cap).unwrap();
         let ptr = unsafe { alloc::alloc(layout) } as *mut AtomicU64;
 
         if ptr.is_null() {
             alloc::handle_alloc_error(layout);
         }
 
+        let bitmap_size = cap.div_ceil(64);
+
         ObjectArena {
-            last_index: AtomicUsize::new(0),
             arena: ptr,
-            cap: SIZE
+            cap,
+            allocation: Default::default(),
+            starts: (0..bitmap_size).map(|_| AtomicU64::new(0)).collect(),
+            marks: (0..bitmap_size).map(|_| AtomicU64::new(0)).collect(),
+            used: AtomicUsize::new(0),
+            allocated_since_gc: AtomicUsize::new(0),
+            gc_threshold: AtomicUsize::new(cap / 2),
+            gc_requested: AtomicBool::new(false),
         }
     }
 
+    #[cfg(test)]
     pub fn new_object(&self, class: ClassRef) -> ObjectPtr {
-        self.allocate_object(class, class.data.instance_field_count)
+        self.try_new_object(class).unwrap_or_else(|| panic!("Allocation request failed"))
     }
 
+    #[cfg(test)]
     pub fn new_array(&self, class: ClassRef, length: usize) -> ObjectPtr {
-        let obj = self.allocate_object(class, length + 1);
+        self.try_new_array(class, length).unwrap_or_else(|| panic!("Allocation request failed"))
+    }
+
+    pub fn try_new_object(&self, class: ClassRef) -> Option<ObjectPtr> {
+        self.allocate_object(class, class.data.instance_field_count)
+    }
+
+    pub fn try_new_array(&self, class: ClassRef, length: usize) -> Option<ObjectPtr> {
+        let obj = self.allocate_object(class, Self::array_words(class, length))?;
         obj.put_field(0, length as u64);
-        obj
+        Some(obj)
     }
 
-    fn allocate_object(&self, class: ClassRef, size: usize) -> ObjectPtr {
+    fn allocate_object(&self, class: ClassRef, size: usize) -> Option<ObjectPtr> {
         let size = Self::calc_align(size);
-        let offset = self.last_index.fetch_add(size, Ordering::AcqRel);
+        let offset = self.allocation.lock().unwrap().take(size, self.cap)?;
 
-        if offset + size >= self.cap {
-            panic!("Allocation request failed");
-        }
-
-        let ptr = unsafe { self.arena.offset(offset as isize ) };
+        let ptr = unsafe { self.arena.add(offset) };
         let mut header: *mut ObjectHeader = ptr.cast();
         unsafe { header.write(ObjectHeader::new(class.ptr())); }
         header = unsafe { header.offset(1)};
 
         let field_ptr: *mut AtomicU64 = header.cast();
-        for i in 0..size {
-            unsafe { *field_ptr.offset(i as isize) = AtomicU64::new(0); }
+        let field_count = size - HEADER_SIZE / WORD_SIZE;
+        for i in 0..field_count {
+            unsafe { field_ptr.add(i).write(AtomicU64::new(0)); }
+        }
+
+        Self::set_bit(&self.starts, offset);
+
+        self.used.fetch_add(size, Ordering::Relaxed);
+        let allocated = self.allocated_since_gc.fetch_add(size, Ordering::Relaxed) + size;
+        if allocated > self.gc_threshold.load(Ordering::Relaxed) {
+            self.gc_requested.store(true, Ordering::Release);
         }
 
-        ObjectPtr { ptr }
+        Some(ObjectPtr { ptr })
+    }
+
+    /// Number of words holding the length and the elements of an array
+    fn array_words(class: ClassRef, length: usize) -> usize {
+        1 + (length * class.element_size()).div_ceil(WORD_SIZE)
+    }
+
+    /// Size of the object in words, including its header
+    pub fn object_size(obj: ObjectPtr) -> usize {
+        let class = obj.get_class();
+        if class.is_array() {
+            Self::calc_align(Self::array_words(class, obj.get_field(0) as usize))
+        } else {
+            Self::calc_align(class.data.instance_field_count)
+        }
+    }
+
+    pub fn gc_requested(&self) -> bool {
+        self.gc_requested.load(Ordering::Relaxed)
+    }
+
+    pub fn request_gc(&self) {
+        self.gc_requested.store(true, Ordering::Release);
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.used.load(Ordering::Relaxed) * WORD_SIZE
+    }
+
+    pub fn capacity_bytes(&self) -> usize {
+        self.cap * WORD_SIZE
+    }
+
+    /// Returns the object starting at the given address, if the value is a pointer to a live
+    /// object of this arena.
+    pub fn object_at(&self, val: u64) -> Option<ObjectPtr> {
+        let base = self.arena as u64;
+        if val < base || !(val - base).is_multiple_of(WORD_SIZE as u64) {
+            return None;
+        }
+
+        let offset = ((val - base) / WORD_SIZE as u64) as usize;
+        if offset >= self.cap || !Self::get_bit(&self.starts, offset) {
+            return None;
+        }
+
+        Some(ObjectPtr { ptr: val as *const AtomicU64 })
+    }
+
+    /// Marks the object, returning false if it was already marked
+    pub fn mark(&self, obj: ObjectPtr) -> bool {
+        !Self::set_bit(&self.marks, self.offset_of(obj))
+    }
+
+    pub fn is_marked(&self, obj: ObjectPtr) -> bool {
+        Self::get_bit(&self.marks, self.offset_of(obj))
+    }
+
+    /// Frees every unmarked object and rebuilds the free list from the gaps between the marked
+    /// ones. Must only be called while no other thread allocates or accesses the heap.
+    pub fn sweep(&self) {
+        let mut allocation = self.allocation.lock().unwrap();
+
+        let mut chunks = vec![];
+        let mut live_end = 0;
+        let mut live = 0;
+
+        for word in 0..self.starts.len() {
+            let mut starts = self.starts[word].load(Ordering::Relaxed);
+            let marks = self.marks[word].swap(0, Ordering::Relaxed);
+
+            while starts != 0 {
+                let bit = starts.trailing_zeros() as usize;
+                starts &= starts - 1;
+
+                let offset = word * 64 + bit;
+                if marks & (1 << bit) == 0 {
+                    self.starts[word].fetch_and(!(1 << bit), Ordering::Relaxed);
+                    continue;
+                }
+
+                if offset > live_end {
+                    chunks.push((live_end, offset - live_end));
+                }
+
+                let size = Self::object_size(ObjectPtr { ptr: unsafe { self.arena.add(offset) } });
+                live_end = offset + size;
+                live += size;
+            }
+        }
+
+        allocation.chunks = chunks;
+        allocation.current = 0;
+        allocation.last_index = live_end;
+
+        self.used.store(live, Ordering::Relaxed);
+        self.allocated_since_gc.store(0, Ordering::Relaxed);
+        self.gc_threshold.store((self.cap - live) / 2, Ordering::Relaxed);
+        self.gc_requested.store(false, Ordering::Release);
+    }
+
+    fn offset_of(&self, obj: ObjectPtr) -> usize {
+        (obj.to_val() - self.arena as u64) as usize / WORD_SIZE
+    }
+
+    fn get_bit(bitmap: &[AtomicU64], offset: usize) -> bool {
+        bitmap[offset / 64].load(Ordering::Relaxed) & (1 << (offset % 64)) != 0
+    }
+
+    fn set_bit(bitmap: &[AtomicU64], offset: usize) -> bool {
+        let mask = 1 << (offset % 64);
+        bitmap[offset / 64].fetch_or(mask, Ordering::Relaxed) & mask != 0
     }
 }
 
 impl Default for ObjectArena {
     fn default() -> Self {
-        Self::new()
+        Self::new(DEFAULT_HEAP_SIZE)
     }
-}
\ No newline at end of file
+}
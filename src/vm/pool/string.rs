@@ -1,129 +1,218 @@
-use std::alloc;
-use std::alloc::Layout;
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::convert::Infallible;
+use std::hash::{BuildHasher, RandomState};
 use std::sync::RwLock;
 
-use crate::VM_HANDLER;
+use crate::{VM_HANDLER, VMThread};
 use crate::vm::object::ObjectPtr;
-
+use crate::vm::thread::thread::allocate;
+
+/// Index of the char[] field of java/lang/String holding its UTF-16 code units
+pub const VALUE_FIELD: usize = 0;
+/// Index of the int field of java/lang/String caching its hash code, 0 if not yet computed
+pub const HASH_FIELD: usize = 1;
+
+/// Number of independently locked parts of the table of interned strings
+const INTERN_SHARDS: usize = 16;
+
+/// Creates java/lang/String objects and keeps track of the interned ones. The characters of a
+/// string are stored in a char[] in the object heap, like any other array, so creating a string
+/// only synchronizes with the allocator.
+///
+/// The table of interned strings is split into shards by hash, so that interning different strings
+/// rarely contends on the same lock. It does not keep its strings alive: the collector drops the
+/// entries whose string became unreachable.
 #[derive(Debug)]
 pub struct StringPool {
-    pub buffers: RwLock<Vec<StrArena>>,
-    pub interned_string: RwLock<HashMap<String, ObjectPtr>>
+    interned: [RwLock<HashMap<Box<[u16]>, ObjectPtr>>; INTERN_SHARDS],
+    hasher: RandomState
 }
 
-impl StringPool {
-    pub fn add_string(&self, value: &str) -> ObjectPtr {
-        let mut buffers = self.buffers.write().unwrap();
-        let buffer = buffers.last_mut().unwrap();
-        let option = buffer.add_string(value);
-
-        match option {
-            None => {
-                buffers.push(StrArena::new());
-                drop(buffers);
-
-                eprintln!("Creating new StrArena");
-                self.add_string(value)
-            }
-            Some(res) => {
-                res
-            }
+impl Default for StringPool {
+    fn default() -> Self {
+        Self {
+            interned: Default::default(),
+            hasher: RandomState::new()
         }
     }
+}
+
+impl StringPool {
+    pub fn add_string(&self, thread: Option<&VMThread>, value: &str) -> Result<ObjectPtr, String> {
+        let chars: Vec<u16> = value.encode_utf16().collect();
+        self.new_string(thread, &chars)
+    }
+
+    /// Creates a string of the given UTF-16 code units. The string and its char[] are allocated
+    /// together, see `allocate` for when the allocating thread may collect garbage.
+    pub fn new_string(&self, thread: Option<&VMThread>, chars: &[u16])
+        -> Result<ObjectPtr, String> {
+        let vm = VM_HANDLER.get().unwrap();
+        let char_array = vm.load_class("[C")?;
+
+        let string = allocate(thread.and_then(VMThread::mutator), |arena| {
+            let value = arena.try_new_array(char_array, chars.len())?;
+            let string = arena.try_new_object(vm.string_class)?;
+            string.put_field(VALUE_FIELD, value.to_val());
+            Some(string)
+        })?;
+        string_value(string).set_chars(0, chars);
+
+        Ok(string)
+    }
+
+    pub fn intern_string(&self, thread: Option<&VMThread>, value: &str)
+        -> Result<ObjectPtr, String> {
+        let chars: Vec<u16> = value.encode_utf16().collect();
+        self.intern_utf16(thread, &chars)
+    }
+
+    /// Like intern_string, but takes the UTF-16 code units, which may contain unpaired surrogates
+    pub fn intern_utf16(&self, thread: Option<&VMThread>, chars: &[u16])
+        -> Result<ObjectPtr, String> {
+        self.intern_chars(chars, || self.new_string(thread, chars))
+    }
+
+    /// Returns the interned string equal to the given one. If there is none yet, the given string
+    /// becomes the interned one, like String.intern() does.
+    pub fn intern(&self, string: ObjectPtr) -> ObjectPtr {
+        self.intern_chars(string_value(string).char_array(), || Ok::<_, Infallible>(string))
+            .unwrap_or_else(|never| match never {})
+    }
 
-    pub fn intern_string(&self, value: &str) -> ObjectPtr {
+    fn intern_chars<E>(&self, chars: &[u16], create: impl FnOnce() -> Result<ObjectPtr, E>)
+        -> Result<ObjectPtr, E> {
+        let shard = self.shard(chars);
         {
-            let interned_map = self.interned_string.read().unwrap();
-            if let Some(index) = interned_map.get(value) {
-                return *index;
+            let interned_map = shard.read().unwrap();
+            if let Some(index) = interned_map.get(chars) {
+                return Ok(*index);
             }
         }
 
-        let obj = self.add_string(value);
+        let obj = create()?;
 
-        let mut interned_map = self.interned_string.write().unwrap();
-        interned_map.insert(value.to_string(), obj);
+        let mut interned_map = shard.write().unwrap();
+        Ok(*interned_map.entry(chars.into()).or_insert(obj))
+    }
 
-        obj
+    pub fn interned_count(&self) -> usize {
+        self.interned.iter().map(|shard| shard.read().unwrap().len()).sum()
     }
-}
 
-impl Default for StringPool {
-    fn default() -> Self {
-        StringPool {
-            buffers: RwLock::new(vec![StrArena::new()]),
-            interned_string: Default::default()
+    /// Forgets the interned strings that were not marked by the collector. Must be called after
+    /// marking and before the heap is swept.
+    pub fn sweep_interned(&self, is_marked: impl Fn(ObjectPtr) -> bool) {
+        for shard in &self.interned {
+            shard.write().unwrap().retain(|_, string| is_marked(*string));
         }
     }
-}
 
-#[derive(Debug)]
-pub struct StrArena {
-    last_index: AtomicUsize,
-    arena: *mut u8,
-    cap: usize,
+    fn shard(&self, value: &[u16]) -> &RwLock<HashMap<Box<[u16]>, ObjectPtr>> {
+        &self.interned[self.hasher.hash_one(value) as usize % INTERN_SHARDS]
+    }
 }
 
-unsafe impl Send for StrArena {}
-unsafe impl Sync for StrArena {}
-
-impl StrArena {
-    pub fn new() -> StrArena {
-        const SIZE: usize = 1024;
+/// The char[] holding the characters of a string
+pub fn string_value(string: ObjectPtr) -> ObjectPtr {
+    let vm = VM_HANDLER.get().unwrap();
+    debug_assert_eq!(string.get_class(), vm.string_class);
 
-        let layout = Layout::array::<u8>(SIZE).unwrap();
-        let ptr = unsafe { alloc::alloc(layout) };
+    ObjectPtr::from_val(string.get_field(VALUE_FIELD)).unwrap()
+}
 
-        if ptr.is_null() {
-            alloc::handle_alloc_error(layout);
-        }
+/// Copies the string into a Rust string, replacing unpaired surrogates with U+FFFD
+pub fn get_string(string: ObjectPtr) -> String {
+    String::from_utf16_lossy(string_value(string).char_array())
+}
 
-        StrArena {
-            last_index: AtomicUsize::new(0),
-            arena: ptr,
-            cap: SIZE
-        }
+/// The hash code of String.hashCode(), s[0]*31^(n-1) + s[1]*31^(n-2) + ... + s[n-1]. It is
+/// computed on first use and cached in the string, unless it is 0.
+pub fn string_hash(string: ObjectPtr) -> i32 {
+    let cached = string.get_field(HASH_FIELD) as i32;
+    if cached != 0 {
+        return cached;
     }
 
-    pub fn add_string(&self, str: &str) -> Option<ObjectPtr> {
-        let str = str.as_bytes();
-        let len = str.len();
-        assert!(len < self.cap);
-
-        let offset = self.last_index.fetch_add(len, Ordering::AcqRel);
+    let hash = string_value(string).char_array().iter()
+        .fold(0i32, |h, c| h.wrapping_mul(31).wrapping_add(*c as i32));
+    string.put_field(HASH_FIELD, hash as u64);
+    hash
+}
 
-        if offset + len >= self.cap {
-            self.last_index.fetch_sub(len, Ordering::AcqRel);
-            return None;
-        }
+#[cfg(test)]
+mod tests {
+    use crate::{VM, VM_HANDLER};
+    use crate::vm::pool::gc::Marker;
+    use crate::vm::pool::object::ObjectArena;
+    use crate::vm::pool::string::{get_string, string_hash, string_value, StringPool, HASH_FIELD,
+                                  VALUE_FIELD};
 
-        let ptr = unsafe { self.arena.offset(offset as isize) };
+    #[test]
+    fn strings_are_utf16() {
+        let vm = VM_HANDLER.get_or_init(|| VM::vm_init(false));
 
-        unsafe { std::ptr::copy_nonoverlapping(str.as_ptr(), ptr, len) };
+        let string = vm.string_pool.add_string(None, "a\u{e9}\u{1F600}").unwrap();
+        assert_eq!(string_value(string).char_array(), [0x61, 0xE9, 0xD83D, 0xDE00]);
+        assert_eq!(get_string(string), "a\u{e9}\u{1F600}");
 
-        let vm = VM_HANDLER.get().unwrap();
-        let string = vm.object_arena.new_object(vm.string_class);
-        string.put_field(0, len as u64);
-        string.put_field(1, ptr as u64);
+        let long = "x".repeat(5000);
+        assert_eq!(get_string(vm.string_pool.add_string(None, &long).unwrap()), long);
 
-        Some(string)
+        assert_eq!(get_string(vm.string_pool.new_string(None, &[0xD800, 0x62]).unwrap()), "\u{FFFD}b");
+        assert_eq!(vm.string_pool.intern_string(None, "interned").unwrap(),
+                   vm.string_pool.intern_string(None, "interned").unwrap());
     }
 
-    pub fn get_string(obj: ObjectPtr) -> String {
-        let vm = VM_HANDLER.get().unwrap();
-        assert_eq!(obj.get_class(), vm.string_class);
+    #[test]
+    fn hash_codes_match_the_jdk() {
+        let vm = VM_HANDLER.get_or_init(|| VM::vm_init(false));
 
-        let length = obj.get_field(0) as usize;
-        let ptr = obj.get_field(1) as *mut u8;
+        assert_eq!(string_hash(vm.string_pool.add_string(None, "").unwrap()), 0);
+        assert_eq!(string_hash(vm.string_pool.add_string(None, "hello").unwrap()), 99162322);
+        // Overflows wrap around like Java int arithmetic
+        let string = vm.string_pool.add_string(None, "polygenelubricants").unwrap();
+        assert_eq!(string_hash(string), i32::MIN);
 
-        if length == 0 {
-            return String::new();
-        }
+        let string = vm.string_pool.add_string(None, "\u{e9}t\u{e9}").unwrap();
+        assert_eq!(string.get_field(HASH_FIELD), 0);
+        assert_eq!(string_hash(string), 227742);
+        assert_eq!(string.get_field(HASH_FIELD) as i32, 227742);
+    }
 
-        let str = unsafe { std::slice::from_raw_parts(ptr, length) };
-        let str = Vec::from(str);
-        String::from_utf8(str).unwrap()
+    #[test]
+    fn unreachable_interned_strings_are_dropped() {
+        let vm = VM_HANDLER.get_or_init(|| VM::vm_init(false));
+        let arena = ObjectArena::new(64 * 1024);
+        let pool = StringPool::default();
+
+        let char_array = vm.load_class("[C").unwrap();
+        let new_string = |value: &str| {
+            let chars: Vec<u16> = value.encode_utf16().collect();
+            let array = arena.new_array(char_array, chars.len());
+            array.set_chars(0, &chars);
+
+            let string = arena.new_object(vm.string_class);
+            string.put_field(VALUE_FIELD, array.to_val());
+            string
+        };
+
+        let object_array = vm.load_class("[java/lang/Object").unwrap();
+        let root = arena.new_array(object_array, 1);
+        let kept = new_string("kept");
+        root.store_to_array(0, kept.to_val());
+
+        assert_eq!(pool.intern(kept), kept);
+        assert_eq!(pool.intern(new_string("dropped")), pool.intern(new_string("dropped")));
+        assert_eq!(pool.interned_count(), 2);
+
+        let mut marker = Marker::new(&arena);
+        marker.mark_value(root.to_val());
+        marker.trace();
+        pool.sweep_interned(|obj| arena.is_marked(obj));
+        arena.sweep();
+
+        assert_eq!(pool.interned_count(), 1);
+        assert_eq!(pool.intern(new_string("kept")), kept);
     }
-}
\ No newline at end of file
+}
@@ -110,6 +110,11 @@ impl Frame {
     pub fn peek_nth(&self, index: usize) -> u64 {
         unsafe { self.data[self.stack_top-index-1].assume_init() }
     }
+
+    /// Local variables followed by the operand stack
+    pub fn values(&self) -> &[u64] {
+        unsafe { std::mem::transmute::<&[MaybeUninit<u64>], &[u64]>(&self.data[..self.stack_top]) }
+    }
 }
 
 #[cfg(test)]
@@ -1,2 +1,4 @@
 pub mod thread;
-pub mod frame;
\ No newline at end of file
+pub mod frame;
+pub mod monitor;
+pub mod registry;
\ No newline at end of file
@@ -0,0 +1,264 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::ThreadId;
+use std::time::{Duration, Instant};
+use crate::vm::object::ObjectPtr;
+use crate::vm::thread::registry::{ThreadState, Wakeup};
+
+/// Reentrant monitor associated with every object. The owner is identified by the OS thread,
+/// so nested VMThreads running on the same thread share the monitors held by the outer one.
+#[derive(Debug, Default)]
+pub struct Monitor {
+    state: Mutex<MonitorState>,
+    released: Condvar
+}
+
+#[derive(Debug, Default)]
+struct MonitorState {
+    owner: Option<ThreadId>,
+    count: usize
+}
+
+impl Monitor {
+    /// Acquires the monitor if it is free or already owned by the current thread
+    pub fn try_enter(&self) -> bool {
+        let current = std::thread::current().id();
+        let mut state = self.state.lock().unwrap();
+
+        match state.owner {
+            None => {
+                state.owner = Some(current);
+                state.count = 1;
+                true
+            }
+            Some(owner) if owner == current => {
+                state.count += 1;
+                true
+            }
+            _ => false
+        }
+    }
+
+    /// Blocks until the monitor is acquired
+    pub fn enter(&self) {
+        let current = std::thread::current().id();
+        let mut state = self.state.lock().unwrap();
+
+        while state.owner.is_some_and(|owner| owner != current) {
+            state = self.released.wait(state).unwrap();
+        }
+
+        state.owner = Some(current);
+        state.count += 1;
+    }
+
+    /// Releases one level of ownership, failing if the current thread does not own the monitor
+    pub fn exit(&self) -> Result<(), ()> {
+        let current = std::thread::current().id();
+        let mut state = self.state.lock().unwrap();
+
+        if state.owner != Some(current) {
+            return Err(());
+        }
+
+        state.count -= 1;
+        if state.count == 0 {
+            state.owner = None;
+            self.released.notify_one();
+        }
+
+        Ok(())
+    }
+
+    pub fn is_owned_by_current(&self) -> bool {
+        self.state.lock().unwrap().owner == Some(std::thread::current().id())
+    }
+
+    /// Releases the monitor completely, returning the recursion count to restore with `reenter`
+    fn release(&self) -> Result<usize, ()> {
+        let current = std::thread::current().id();
+        let mut state = self.state.lock().unwrap();
+
+        if state.owner != Some(current) {
+            return Err(());
+        }
+
+        let count = state.count;
+        state.owner = None;
+        state.count = 0;
+        self.released.notify_one();
+
+        Ok(count)
+    }
+
+    fn reenter(&self, count: usize) {
+        let mut state = self.state.lock().unwrap();
+
+        while state.owner.is_some() {
+            state = self.released.wait(state).unwrap();
+        }
+
+        state.owner = Some(std::thread::current().id());
+        state.count = count;
+    }
+}
+
+/// Wait sets of the objects, keyed by their address. Only objects with waiting threads have an
+/// entry, keeping object headers small.
+#[derive(Default)]
+pub struct WaitSets {
+    sets: Mutex<HashMap<u64, VecDeque<Arc<ThreadState>>>>
+}
+
+impl WaitSets {
+    /// Implements Object.wait: releases the object's monitor, waits until notified, interrupted or
+    /// until the timeout elapses, then re-acquires the monitor. Fails if the current thread does
+    /// not own the monitor.
+    pub fn wait(&self, object: ObjectPtr, thread: &Arc<ThreadState>, timeout: Option<Duration>)
+        -> Result<Wakeup, ()> {
+        let monitor = object.monitor();
+        if !monitor.is_owned_by_current() {
+            return Err(());
+        }
+
+        // Entering the wait set before releasing the monitor means no notification can be missed
+        thread.reset_notified();
+        self.sets.lock().unwrap().entry(object.to_val()).or_default().push_back(thread.clone());
+        let count = monitor.release()?;
+
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut wakeup = thread.park(deadline);
+
+        if wakeup != Wakeup::Notified {
+            let mut sets = self.sets.lock().unwrap();
+            let removed = match sets.get_mut(&object.to_val()) {
+                Some(set) => {
+                    let len = set.len();
+                    set.retain(|t| !Arc::ptr_eq(t, thread));
+                    let removed = set.len() != len;
+                    if set.is_empty() {
+                        sets.remove(&object.to_val());
+                    }
+                    removed
+                }
+                None => false
+            };
+
+            // Otherwise a notification selected this thread concurrently with the timeout or
+            // interrupt. It must not get lost, so the wait returns normally, leaving the interrupt
+            // status set.
+            if !removed {
+                wakeup = Wakeup::Notified;
+            }
+        }
+
+        monitor.reenter(count);
+        Ok(wakeup)
+    }
+
+    /// Wakes up one of the threads waiting on the object, or all of them
+    pub fn notify(&self, object: ObjectPtr, all: bool) -> Result<(), ()> {
+        if !object.monitor().is_owned_by_current() {
+            return Err(());
+        }
+
+        let mut sets = self.sets.lock().unwrap();
+        if let Some(set) = sets.get_mut(&object.to_val()) {
+            let count = if all { set.len() } else { 1 };
+            for thread in set.drain(..count) {
+                thread.notify();
+            }
+
+            if set.is_empty() {
+                sets.remove(&object.to_val());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use crate::{VM, VM_HANDLER};
+    use crate::vm::thread::monitor::{Monitor, WaitSets};
+    use crate::vm::thread::registry::{ThreadState, Wakeup};
+
+    #[test]
+    fn reentrant() {
+        let monitor = Monitor::default();
+
+        assert!(monitor.try_enter());
+        monitor.enter();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                assert!(!monitor.try_enter());
+                assert_eq!(monitor.exit(), Err(()));
+            });
+        });
+
+        assert_eq!(monitor.exit(), Ok(()));
+        assert_eq!(monitor.exit(), Ok(()));
+        assert_eq!(monitor.exit(), Err(()));
+    }
+
+    #[test]
+    fn mutual_exclusion() {
+        let monitor = Arc::new(Monitor::default());
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let threads: Vec<_> = (0..4).map(|_| {
+            let monitor = monitor.clone();
+            let counter = counter.clone();
+            std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    monitor.enter();
+                    // Non-atomic read-modify-write, only correct under the monitor
+                    let val = counter.load(Ordering::Relaxed);
+                    std::thread::yield_now();
+                    counter.store(val + 1, Ordering::Relaxed);
+                    monitor.exit().unwrap();
+                }
+            })
+        }).collect();
+
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        assert_eq!(counter.load(Ordering::Relaxed), 4000);
+    }
+
+    #[test]
+    fn wait_and_notify() {
+        let vm = VM_HANDLER.get_or_init(|| VM::vm_init(false));
+        let object = vm.object_arena.new_object(vm.object_class);
+        let wait_sets = WaitSets::default();
+        let state = Arc::new(ThreadState::default());
+
+        assert_eq!(wait_sets.wait(object, &state, None), Err(()));
+        assert_eq!(wait_sets.notify(object, false), Err(()));
+
+        object.monitor().enter();
+        assert_eq!(wait_sets.wait(object, &state, Some(Duration::from_millis(1))),
+                   Ok(Wakeup::TimedOut));
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                // Can only get the monitor once the main thread waits
+                object.monitor().enter();
+                wait_sets.notify(object, true).unwrap();
+                object.monitor().exit().unwrap();
+            });
+
+            assert_eq!(wait_sets.wait(object, &state, None), Ok(Wakeup::Notified));
+        });
+
+        state.interrupt();
+        assert_eq!(wait_sets.wait(object, &state, None), Ok(Wakeup::Interrupted));
+        assert!(object.monitor().exit().is_ok());
+    }
+}
@@ -0,0 +1,305 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use smallvec::smallvec;
+use crate::{ClassRef, initialize_class, ThreadStatus, VM_HANDLER, VMThread};
+use crate::vm::class::field::FieldType;
+use crate::vm::class::method::MethodDescriptor;
+use crate::vm::class_loader::resolve::instance_field_index;
+use crate::vm::object::ObjectPtr;
+use crate::vm::pool::string::get_string;
+
+/// Instance field indices of java/lang/Thread
+struct ThreadFields {
+    name: usize,
+    daemon: usize,
+    started: usize
+}
+
+impl ThreadFields {
+    /// Looks the fields up by name and descriptor on first use
+    fn get() -> &'static ThreadFields {
+        static FIELDS: OnceLock<ThreadFields> = OnceLock::new();
+
+        FIELDS.get_or_init(|| {
+            let vm = VM_HANDLER.get().unwrap();
+            let class = vm.load_class("java/lang/Thread").unwrap();
+            let field = |name: &str, descriptor: FieldType| {
+                instance_field_index(class, name, &descriptor).unwrap()
+            };
+
+            ThreadFields {
+                name: field("name", FieldType::L("java/lang/String".to_string())),
+                daemon: field("daemon", FieldType::Z),
+                started: field("started", FieldType::Z)
+            }
+        })
+    }
+}
+
+thread_local! {
+    // The java/lang/Thread object of the Java thread running on this OS thread
+    static CURRENT_THREAD: Cell<Option<ObjectPtr>> = const { Cell::new(None) };
+    static CURRENT_STATE: RefCell<Arc<ThreadState>> = RefCell::new(Default::default());
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Wakeup {
+    Notified,
+    Interrupted,
+    TimedOut
+}
+
+/// Interrupt status of a thread, along with the place where it parks while sleeping or waiting
+/// on a monitor
+#[derive(Default)]
+pub struct ThreadState {
+    flags: Mutex<Flags>,
+    wakeup: Condvar
+}
+
+#[derive(Default)]
+struct Flags {
+    interrupted: bool,
+    notified: bool
+}
+
+impl ThreadState {
+    pub fn interrupt(&self) {
+        self.flags.lock().unwrap().interrupted = true;
+        self.wakeup.notify_all();
+    }
+
+    pub fn is_interrupted(&self, clear: bool) -> bool {
+        let mut flags = self.flags.lock().unwrap();
+        let interrupted = flags.interrupted;
+        if clear {
+            flags.interrupted = false;
+        }
+
+        interrupted
+    }
+
+    /// Blocks until the thread is notified or interrupted, or the deadline passes. The interrupt
+    /// status is left unchanged.
+    pub fn park(&self, deadline: Option<Instant>) -> Wakeup {
+        let mut flags = self.flags.lock().unwrap();
+        loop {
+            if flags.notified {
+                return Wakeup::Notified;
+            } else if flags.interrupted {
+                return Wakeup::Interrupted;
+            }
+
+            flags = match deadline {
+                None => self.wakeup.wait(flags).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Wakeup::TimedOut;
+                    }
+
+                    self.wakeup.wait_timeout(flags, deadline - now).unwrap().0
+                }
+            };
+        }
+    }
+
+    pub fn notify(&self) {
+        self.flags.lock().unwrap().notified = true;
+        self.wakeup.notify_all();
+    }
+
+    pub fn reset_notified(&self) {
+        self.flags.lock().unwrap().notified = false;
+    }
+}
+
+/// Keeps track of the alive Java threads, keyed by the address of their java/lang/Thread object.
+/// Registered thread objects are GC roots.
+#[derive(Default)]
+pub struct ThreadRegistry {
+    threads: Mutex<HashMap<u64, JavaThread>>,
+    finished: Condvar
+}
+
+struct JavaThread {
+    daemon: bool,
+    state: Arc<ThreadState>
+}
+
+impl ThreadRegistry {
+    /// Starts executing the `run` method of the thread object on a new OS thread
+    pub fn start(&self, thread: ObjectPtr) -> Result<(), String> {
+        let class = thread.get_class();
+        let run = find_virtual(class, "run")
+            .ok_or_else(|| format!("No run method found in {}", class.data.name))?;
+        let name = get_string(ObjectPtr::from_val(thread.get_field(ThreadFields::get().name))
+            .ok_or("Thread has no name")?);
+
+        let state = Arc::new(ThreadState::default());
+        self.add(thread, state.clone());
+
+        let res = std::thread::Builder::new()
+            .name(name)
+            .spawn(move || {
+                CURRENT_THREAD.with(|c| c.set(Some(thread)));
+                CURRENT_STATE.with(|c| *c.borrow_mut() = state);
+
+                // Joining threads must be woken up even if the VM panics on this thread
+                let _ = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                    let mut vm_thread = VMThread::new();
+                    vm_thread.start(run, smallvec![thread.to_val()]);
+                    if let ThreadStatus::FAILED(err) = vm_thread.status {
+                        VM_HANDLER.get().unwrap().threads.report_uncaught(&err);
+                    }
+                }));
+
+                VM_HANDLER.get().unwrap().threads.exit_current();
+            });
+
+        res.map(|_| ()).map_err(|e| {
+            self.remove(thread);
+            e.to_string()
+        })
+    }
+
+    /// Returns the thread object of the calling thread, `vm_thread` being the VMThread asking for
+    /// it. The thread running `main` gets one created on first use.
+    pub fn current(&self, vm_thread: &VMThread) -> Result<ObjectPtr, String> {
+        if let Some(thread) = CURRENT_THREAD.with(|c| c.get()) {
+            return Ok(thread);
+        }
+
+        let vm = VM_HANDLER.get().unwrap();
+        let class = vm.load_class("java/lang/Thread").unwrap();
+        initialize_class(class, vm_thread.mutator()).unwrap();
+
+        let thread = vm_thread.new_object(class)?;
+        let name = vm_thread.with_root(thread, || {
+            vm.string_pool.intern_string(Some(vm_thread), "main")
+        })?;
+        thread.put_field(ThreadFields::get().name, name.to_val());
+        thread.put_field(ThreadFields::get().started, 1);
+
+        self.add(thread, Self::current_state());
+        CURRENT_THREAD.with(|c| c.set(Some(thread)));
+
+        Ok(thread)
+    }
+
+    /// Prints an exception the Java thread running on this OS thread did not catch, given in its
+    /// printed form, the way `java` does
+    pub fn report_uncaught(&self, exception: &str) {
+        let name = match CURRENT_THREAD.with(|c| c.get()) {
+            Some(thread) => ObjectPtr::from_val(thread.get_field(ThreadFields::get().name))
+                .map_or("null".to_string(), get_string),
+            // The thread running `main` only gets a thread object once it asks for it
+            None => "main".to_string()
+        };
+        eprintln!("Exception in thread \"{}\" {}", name, exception);
+    }
+
+    /// Marks the Java thread running on this OS thread as terminated
+    pub fn exit_current(&self) {
+        if let Some(thread) = CURRENT_THREAD.with(|c| c.take()) {
+            self.remove(thread);
+        }
+    }
+
+    /// The interrupt status and parking place of the calling thread
+    pub fn current_state() -> Arc<ThreadState> {
+        CURRENT_STATE.with(|c| c.borrow().clone())
+    }
+
+    pub fn is_alive(&self, thread: ObjectPtr) -> bool {
+        self.threads.lock().unwrap().contains_key(&thread.to_val())
+    }
+
+    /// Interrupts the thread. Threads that are not alive are not affected.
+    pub fn interrupt(&self, thread: ObjectPtr) {
+        let threads = self.threads.lock().unwrap();
+        if let Some(t) = threads.get(&thread.to_val()) {
+            t.state.interrupt();
+        }
+
+        // Wakes up the threads joining others, in case one of them was interrupted
+        self.finished.notify_all();
+    }
+
+    pub fn is_interrupted(&self, thread: ObjectPtr) -> bool {
+        self.threads.lock().unwrap().get(&thread.to_val())
+            .is_some_and(|t| t.state.is_interrupted(false))
+    }
+
+    /// Waits for the thread to terminate, or at most until the timeout elapses. Returns
+    /// `Wakeup::Interrupted` if the calling thread got interrupted meanwhile.
+    pub fn join(&self, thread: ObjectPtr, timeout: Option<Duration>) -> Wakeup {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let current = Self::current_state();
+
+        let mut threads = self.threads.lock().unwrap();
+        while threads.contains_key(&thread.to_val()) {
+            if current.is_interrupted(false) {
+                return Wakeup::Interrupted;
+            }
+
+            threads = match deadline {
+                None => self.finished.wait(threads).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Wakeup::TimedOut;
+                    }
+
+                    self.finished.wait_timeout(threads, deadline - now).unwrap().0
+                }
+            };
+        }
+
+        Wakeup::Notified
+    }
+
+    /// Waits until every non-daemon thread has terminated
+    pub fn wait_for_non_daemon(&self) {
+        let mut threads = self.threads.lock().unwrap();
+        while threads.values().any(|t| !t.daemon) {
+            threads = self.finished.wait(threads).unwrap();
+        }
+    }
+
+    pub fn objects(&self) -> Vec<ObjectPtr> {
+        self.threads.lock().unwrap().keys().filter_map(|t| ObjectPtr::from_val(*t)).collect()
+    }
+
+    fn add(&self, thread: ObjectPtr, state: Arc<ThreadState>) {
+        let daemon = thread.get_field(ThreadFields::get().daemon) != 0;
+        self.threads.lock().unwrap().insert(thread.to_val(), JavaThread { daemon, state });
+    }
+
+    fn remove(&self, thread: ObjectPtr) {
+        self.threads.lock().unwrap().remove(&thread.to_val());
+        self.finished.notify_all();
+    }
+}
+
+fn find_virtual(class: ClassRef, name: &str) -> Option<(ClassRef, usize)> {
+    let descriptor = MethodDescriptor {
+        parameters: vec![],
+        ret: FieldType::V
+    };
+
+    let mut class = class;
+    loop {
+        if let Some(method) = class.find_method(name, &descriptor) {
+            return Some(method);
+        }
+
+        if class.data.superclass.ptr().is_null() {
+            return None;
+        }
+        class = class.data.superclass;
+    }
+}
@@ -1,28 +1,37 @@
+use std::cell::{Cell, RefCell};
 use std::cmp::max;
-use std::fmt::{Debug};
+use std::fmt::Debug;
 use std::sync::atomic::{AtomicU64, Ordering};
 use smallvec::{SmallVec, smallvec};
-use crate::{Class, initialize_class, Method, VM_HANDLER};
-use crate::class_parser::constants::{AccessFlagMethod};
-use crate::helper::{ftou2, has_flag, utof, utof2};
+use crate::{Class, initialize_class, VM_HANDLER};
+use crate::class_parser::constants::{AccessFlagClass, AccessFlagMethod};
+use crate::helper::{ftou, ftou2, has_flag, utof, utof2};
+use crate::helper::number::{double_to_string, float_to_string};
 use crate::vm::class::class::ClassRef;
-use crate::vm::class::constant_pool::{CPEntry, SymbolicReference};
+use crate::vm::class::constant_pool::{CallSite, ConcatElement, CPEntry, SymbolicReference, UnresolvedReference};
 use crate::vm::class::constant_pool::SymbolicReference::{ClassReference, FieldReference, MethodReference};
 use crate::vm::class::field::FieldType;
-use crate::vm::class::method::{Code, MAX_NO_OF_ARGS, MethodDescriptor, MethodRepr};
-use crate::vm::class_loader::resolve::resolve;
-use crate::vm::instructions::{Instruction, instruction_length, InstructionResult};
+use crate::vm::class::method::{Code, JvmMethod, MAX_NO_OF_ARGS, MethodDescriptor, MethodRepr};
+use crate::vm::class_loader::resolve::{InitializationError, instance_field_index, maximally_specific_methods, resolve};
+use crate::vm::instructions::{Instruction, instruction_length, InstructionResult, read_i32, switch_operands_start};
 use crate::vm::object::ObjectPtr;
 use crate::vm::thread::frame::Frame;
 use crate::vm::thread::thread::ThreadStatus::{FAILED, FINISHED, RUNNING};
-use num_enum::FromPrimitive;
 use crate::vm::instructions::InstructionResult::Exception;
-use crate::vm::pool::string::StrArena;
+use crate::vm::pool::object::ObjectArena;
+use crate::vm::pool::string::{get_string, string_value};
 
 pub type MethodRef = (ClassRef, usize);
 
 const STACK_SIZE: usize = 36;
 
+thread_local! {
+    // The GC mutator of the VMThreads running on this OS thread. The outermost one registers it,
+    // nested ones (e.g. running a static initializer on behalf of the outer one) join it, so that
+    // their frames are scanned as well and they stop at safepoints like the outer one.
+    static MUTATOR: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
 #[derive(Debug)]
 pub enum ThreadStatus {
     RUNNING,
@@ -30,9 +39,33 @@ pub enum ThreadStatus {
     FAILED(String),
 }
 
+/// Leaves the mutator once `VMThread::start` returns, even if it unwinds due to a panic. The
+/// outermost VMThread unregisters it.
+struct ThreadEntry {
+    mutator: usize,
+    nested: bool
+}
+
+impl Drop for ThreadEntry {
+    fn drop(&mut self) {
+        let gc = &VM_HANDLER.get().unwrap().gc;
+        if self.nested {
+            gc.leave(self.mutator);
+        } else {
+            gc.unregister(self.mutator);
+            MUTATOR.with(|m| m.set(None));
+        }
+    }
+}
+
 pub struct VMThread {
     pub status: ThreadStatus,
     pub stack: SmallVec<[Frame; STACK_SIZE]>,
+    /// The throwable the thread failed with
+    pub exception: Option<ObjectPtr>,
+    /// Objects only referenced from Rust code running on the thread, see `with_root`
+    roots: RefCell<Vec<ObjectPtr>>,
+    mutator: Option<usize>,
     print_trace: bool
 }
 
@@ -43,6 +76,9 @@ impl VMThread {
         VMThread {
             status: FINISHED(None),
             stack: Default::default(),
+            exception: None,
+            roots: Default::default(),
+            mutator: None,
             print_trace: vm.args.read().unwrap().print_trace
         }
     }
@@ -56,6 +92,20 @@ impl VMThread {
 
         self.stack.push(frame);
 
+        let vm = VM_HANDLER.get().unwrap();
+        let entry = match MUTATOR.with(|m| m.get()) {
+            Some(id) => {
+                vm.gc.join(id, self);
+                ThreadEntry { mutator: id, nested: true }
+            }
+            None => {
+                let id = vm.gc.register(self);
+                MUTATOR.with(|m| m.set(Some(id)));
+                ThreadEntry { mutator: id, nested: false }
+            }
+        };
+        self.mutator = Some(entry.mutator);
+
         self.status = RUNNING;
         let result = self.method(method_ref, arg_no);
 
@@ -75,13 +125,12 @@ impl VMThread {
             Err(obj) => {
                 use std::io::Write;
 
+                // Printed like Throwable.printStackTrace does
                 let mut buf: Vec<u8> = Vec::with_capacity(200);
-                let message = match ObjectPtr::from_val(obj.get_field(1)) {
-                    None => "".to_string(),
-                    Some(val) => StrArena::get_string(val)
-                };
-
-                let _ = write!(&mut buf, "Exception {}: {}\n", obj.get_class().data.name, message);
+                let _ = write!(&mut buf, "{}", obj.get_class().data.name.replace('/', "."));
+                if let Some(message) = ObjectPtr::from_val(obj.get_field(1)) {
+                    let _ = write!(&mut buf, ": {}", get_string(message));
+                }
 
                 let array = ObjectPtr::from_val(obj.get_field(0)).unwrap();
                 let length = array.get_field(0);
@@ -90,19 +139,160 @@ impl VMThread {
                         .unwrap();
                     let declaring_class = stack_elem.get_field(0);
                     let method_name = stack_elem.get_field(1);
-
-                    let _ = write!(&mut buf, "      at {}.{}\n",
-                           StrArena::get_string(ObjectPtr::from_val(declaring_class).unwrap()),
-                           StrArena::get_string(ObjectPtr::from_val(method_name).unwrap()));
+                    let file_name = ObjectPtr::from_val(stack_elem.get_field(2))
+                        .map(get_string);
+                    let line_number = stack_elem.get_field(3) as i32;
+
+                    let location = match (file_name, line_number) {
+                        (_, -2) => "Native Method".to_string(),
+                        (Some(file_name), line) if line >= 0 => format!("{}:{}", file_name, line),
+                        (Some(file_name), _) => file_name,
+                        (None, _) => "Unknown Source".to_string()
+                    };
+
+                    let _ = write!(&mut buf, "\n\tat {}.{}({})",
+                           get_string(ObjectPtr::from_val(declaring_class).unwrap()),
+                           get_string(ObjectPtr::from_val(method_name).unwrap()),
+                           location);
                 }
 
                 self.status = FAILED(String::from_utf8(buf).unwrap());
+                self.exception = Some(obj);
             }
         }
+
+        self.mutator = None;
+    }
+
+    /// Runs an operation that may block for long, like sleeping or joining another thread,
+    /// without holding up garbage collections
+    pub fn blocking<T>(&self, f: impl FnOnce() -> T) -> T {
+        let vm = VM_HANDLER.get().unwrap();
+        vm.gc.blocking(self.mutator, f)
+    }
+
+    /// Id of the GC mutator of the thread while it runs, shared by the VMThreads of an OS thread
+    pub fn mutator(&self) -> Option<usize> {
+        self.mutator
+    }
+
+    /// Allocates an instance of the class, see `allocate`
+    pub fn new_object(&self, class: ClassRef) -> Result<ObjectPtr, String> {
+        allocate(self.mutator, |arena| arena.try_new_object(class))
+    }
+
+    /// Allocates an array of the array class, see `allocate`
+    pub fn new_array(&self, class: ClassRef, length: usize) -> Result<ObjectPtr, String> {
+        allocate(self.mutator, |arena| arena.try_new_array(class, length))
+    }
+
+    /// Runs `f` with the object kept alive by the collector, for objects that are not referenced
+    /// from the frames of the thread while it allocates
+    pub fn with_root<T>(&self, object: ObjectPtr, f: impl FnOnce() -> T) -> T {
+        self.roots.borrow_mut().push(object);
+        let res = f();
+        self.roots.borrow_mut().pop();
+        res
+    }
+
+    /// The objects kept alive by `with_root`
+    ///
+    /// # Safety
+    /// The thread must be stopped for a collection, so that the roots do not change meanwhile
+    pub unsafe fn local_roots(&self) -> &[ObjectPtr] {
+        self.roots.try_borrow_unguarded().unwrap()
     }
 
     fn method(&mut self, method_ref: MethodRef, arg_no: usize) -> Result<(), ObjectPtr> {
-        let (class, method) = method_ref.clone();
+        let (class, method) = method_ref;
+        let method = &class.data.methods[method];
+
+        if !method.is_synchronized() {
+            return self.execute_method(method_ref, arg_no);
+        }
+
+        // The arguments are still on the caller's frame, keeping `this` reachable while blocked
+        let lock = if method.is_static() {
+            ObjectPtr { ptr: class.ptr().cast() }
+        } else {
+            ObjectPtr::from_val(self.stack.last().unwrap().peek_nth(arg_no - 1)).unwrap()
+        };
+
+        enter_monitor(self.mutator, lock);
+        let result = self.execute_method(method_ref, arg_no);
+
+        match (lock.monitor().exit(), result) {
+            (Err(()), Ok(())) => Err(create_throwable("java/lang/IllegalMonitorStateException",
+                                                     self)),
+            (_, result) => result
+        }
+    }
+
+    /// Pops the arguments of a string concatenation call site and concatenates them with the
+    /// constants, converting each argument like String.valueOf would
+    fn concat_strings(&mut self, elements: &[ConcatElement], types: &[FieldType])
+        -> Result<ObjectPtr, ObjectPtr> {
+        let vm = VM_HANDLER.get().unwrap();
+
+        // Converting from the top of the stack keeps the arguments not yet converted reachable
+        // while a toString method runs
+        let mut values = vec![vec![]; types.len()];
+        for (i, t) in types.iter().enumerate().rev() {
+            let val = self.stack.last_mut().unwrap().pop();
+            values[i] = match t {
+                FieldType::Z => utf16(&(val != 0).to_string()),
+                FieldType::C => vec![val as u16],
+                FieldType::J => utf16(&(val as i64).to_string()),
+                FieldType::F => utf16(&float_to_string(utof(val as u32))),
+                FieldType::D => utf16(&double_to_string(utof2(val))),
+                FieldType::L(_) | FieldType::A(_) => self.object_to_string(val)?,
+                _ => utf16(&(val as i32).to_string())
+            };
+        }
+
+        let mut chars = vec![];
+        for element in elements {
+            match element {
+                ConcatElement::Constant(constant) => chars.extend_from_slice(constant),
+                ConcatElement::Argument(i) => chars.extend_from_slice(&values[*i])
+            }
+        }
+
+        vm.string_pool.new_string(Some(self), &chars)
+            .map_err(|e| create_throwable_from_error(&e, self))
+    }
+
+    /// Calls the toString method of the object, unless it is null or a String, and returns the
+    /// characters of the result
+    fn object_to_string(&mut self, val: u64) -> Result<Vec<u16>, ObjectPtr> {
+        let vm = VM_HANDLER.get().unwrap();
+
+        let Some(object) = ObjectPtr::from_val(val) else {
+            return Ok(utf16("null"));
+        };
+        let class = object.get_class();
+        if class == vm.string_class {
+            return Ok(string_value(object).char_array().to_vec());
+        }
+
+        let descriptor = MethodDescriptor {
+            parameters: vec![],
+            ret: FieldType::L("java/lang/String".to_string())
+        };
+        let to_string = vm.object_class.find_method("toString", &descriptor).unwrap();
+        // Array classes have no methods of their own
+        let to_string = invoke_virtual(class, vm.object_class, to_string).unwrap_or(to_string);
+
+        self.stack.last_mut().unwrap().push(val);
+        self.method(to_string, 1)?;
+
+        let string = self.stack.last_mut().unwrap().pop();
+        Ok(ObjectPtr::from_val(string)
+            .map_or_else(|| utf16("null"), |string| string_value(string).char_array().to_vec()))
+    }
+
+    fn execute_method(&mut self, method_ref: MethodRef, arg_no: usize) -> Result<(), ObjectPtr> {
+        let (class, method) = method_ref;
         let class = &*class;
         let method = &class.data.methods[method];
 
@@ -141,7 +331,7 @@ impl VMThread {
                     let mut result = None;
                     'outer: loop {
                         loop {
-                            let res = self.interpreter_loop(&class, code, &mut result);
+                            let res = self.interpreter_loop(class, code, &mut result);
                             match res {
                                 InstructionResult::Continue => {}
                                 InstructionResult::Return => { break 'outer; }
@@ -150,7 +340,7 @@ impl VMThread {
                         }
 
                         if let Some(obj) = result {
-                            let mut frame = self.stack.last_mut().unwrap();
+                            let frame = self.stack.last_mut().unwrap();
 
                             let obj = ObjectPtr::from_val(obj).unwrap();
 
@@ -198,7 +388,13 @@ impl VMThread {
                 let prev_frame = self.stack.last_mut().unwrap();
                 let args = prev_frame.pop_args(arg_no);
                 let mut exception: Option<ObjectPtr> = None;
-                let frame = Frame::new(method_ref, 0, 0);
+
+                // The arguments are kept on the native frame, so that they stay reachable for the
+                // GC while the native method blocks
+                let mut frame = Frame::new(method_ref, 0, arg_no);
+                for arg in &args {
+                    frame.push(*arg);
+                }
                 self.stack.push(frame);
                 let res = fn_ptr(self, args, &mut exception);
                 self.stack.pop();
@@ -219,17 +415,36 @@ impl VMThread {
         }
     }
 
+    /// Throws the throwable described by a VM error of the form `Name: message` from the current
+    /// instruction
+    fn throw_error(&self, error: &str, result: &mut Option<u64>) -> InstructionResult {
+        *result = Some(create_throwable_from_error(error, self).to_val());
+        Exception
+    }
+
+    fn throw_negative_array_size(&self, count: i32, result: &mut Option<u64>) -> InstructionResult {
+        *result = Some(create_throwable_message("java/lang/NegativeArraySizeException", self,
+                                                &count.to_string()).to_val());
+        Exception
+    }
+
     #[inline(always)]
     fn interpreter_loop(&mut self, class: &Class, code: &Code, result: &mut Option<u64>) ->
                                                                                InstructionResult {
         use Instruction::*;
 
+        let vm = VM_HANDLER.get().unwrap();
+
+        if let Some(id) = self.mutator {
+            if vm.object_arena.gc_requested() {
+                vm.gc.safepoint(vm, id);
+            }
+        }
+
         let frame = self.stack.last_mut().unwrap();
         let instr = code.code[frame.pc];
         let instruction = unsafe { Instruction::from_unchecked(instr) };
 
-        let vm = VM_HANDLER.get().unwrap();
-
         if self.print_trace {
             println!("{}: {:?}", frame.pc, instruction);
         }
@@ -251,17 +466,9 @@ impl VMThread {
                 let val = instr as u64 - 9;
                 frame.push(val);
             }
-            fconst_0 => {
-                const VAL: f64 = 0.0f32 as f64;
-                frame.push(ftou2(VAL));
-            }
-            fconst_1 => {
-                const VAL: f64 = 1.0f32 as f64;
-                frame.push(ftou2(VAL));
-            }
-            fconst_2 => {
-                const VAL: f64 = 2.0f32 as f64;
-                frame.push(ftou2(VAL));
+            fconst_0 | fconst_1 | fconst_2 => {
+                let val = (instr - 11) as f32;
+                frame.push(ftou(val) as u64);
             }
             dconst_0 => {
                 const VAL: f64 = 0.0;
@@ -272,35 +479,40 @@ impl VMThread {
                 frame.push(ftou2(VAL));
             }
             bipush => {
-                let val = code.code[frame.pc + 1];
-                frame.push(val as u64);
+                let val = code.code[frame.pc + 1] as i8;
+                frame.push(val as i32 as u64);
             }
             sipush => {
-                let val = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
+                let val = i16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
                     .unwrap());
-                frame.push(val as u64);
+                frame.push(val as i32 as u64);
             }
-            ldc => {
-                let val = code.code[frame.pc + 1];
+            ldc | ldc_w | ldc2_w => {
+                let index = if instruction == ldc {
+                    code.code[frame.pc + 1] as u16
+                } else {
+                    u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into().unwrap())
+                };
 
-                let entry = class.get_cp_entry(val as usize);
+                if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                    return self.throw_error(&e, result);
+                }
+                let entry = class.get_cp_entry(index as usize);
 
                 match *entry {
                     CPEntry::ConstantString(ptr) => frame.push(ptr.ptr as u64),
+                    CPEntry::ConstantValue(val) if instruction == ldc2_w => frame.push(val),
                     CPEntry::ConstantValue(val) => frame.push((val as u32) as u64),
-                    // TODO: Reference to class/method can also be loaded
-                    _ => panic!("Unexpected entry {:?}", entry)
-                }
-            }
-            ldc_w | ldc2_w => {
-                let val = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
-                    .unwrap());
-
-                let entry = class.get_cp_entry(val as usize);
-
-                match *entry {
-                    CPEntry::ConstantValue(val) => frame.push(val),
-                    _ => panic!("")
+                    // Classes are their own java.lang.Class objects
+                    CPEntry::ResolvedSymbolicReference(
+                        SymbolicReference::ClassReference(other_class)) =>
+                        frame.push(other_class.ptr() as u64),
+                    CPEntry::UnresolvedSymbolicReference(
+                        UnresolvedReference::MethodTypeReference(_)
+                        | UnresolvedReference::MethodHandleReference(_, _)) =>
+                        return self.throw_error("LinkageError: MethodType and MethodHandle \
+                            constants are not supported", result),
+                    _ => return self.throw_error(&illegal_cp_entry(class, index), result)
                 }
             }
             aaload => {
@@ -341,6 +553,11 @@ impl VMThread {
                 let index = code.code[frame.pc + 1];
                 frame.set_d(index as usize, val);
             }
+            fstore => {
+                let val = frame.pop() as u32;
+                let index = code.code[frame.pc + 1];
+                frame.set_s(index as usize, val);
+            }
             dstore => {
                 let val = frame.pop();
                 let index = code.code[frame.pc + 1];
@@ -367,8 +584,8 @@ impl VMThread {
             }
             fload => {
                 let index = code.code[frame.pc + 1];
-                let val = frame.get_d(index as usize);
-                frame.push(val);
+                let val = frame.get_s(index as usize);
+                frame.push(val as u64);
             }
             dload => {
                 let index = code.code[frame.pc + 1];
@@ -389,7 +606,7 @@ impl VMThread {
                 frame.push(val);
             }
             fload_0 | fload_1 | fload_2 | fload_3 => {
-                let val = frame.get_s(instr as usize - 43);
+                let val = frame.get_s(instr as usize - 34);
                 frame.push(val as u64);
             }
             dload_0 | dload_1 | dload_2 | dload_3 => {
@@ -401,7 +618,7 @@ impl VMThread {
 
                 frame.push(objectref);
             }
-            iaload => {
+            iaload | laload | faload | daload | baload | caload | saload => {
                 let index = frame.pop() as usize;
                 let array = frame.pop();
 
@@ -421,7 +638,13 @@ impl VMThread {
                                 return InstructionResult::Exception;
                             }
                             Some(val) => {
-                                frame.push(val);
+                                // Bytes and shorts are sign-extended, chars zero-extended to int
+                                frame.push(match instruction {
+                                    baload => val as i8 as i32 as u64,
+                                    caload => val as u16 as i32 as u64,
+                                    saload => val as i16 as i32 as u64,
+                                    _ => val
+                                });
                             }
                         }
 
@@ -433,6 +656,11 @@ impl VMThread {
 
                 frame.set_d(instr as usize - 63, val);
             }
+            fstore_0 | fstore_1 | fstore_2 | fstore_3 => {
+                let val = frame.pop() as u32;
+
+                frame.set_s(instr as usize - 67, val);
+            }
             dstore_0 | dstore_1 | dstore_2 | dstore_3 => {
                 let val = frame.pop();
 
@@ -443,8 +671,8 @@ impl VMThread {
 
                 frame.set_d(instr as usize - 75, objectref);
             }
-            iastore => {
-                let val = frame.pop() as u32;
+            iastore | lastore | fastore | dastore | bastore | castore | sastore => {
+                let val = frame.pop();
                 let index = frame.pop() as usize;
                 let array = frame.pop();
 
@@ -456,15 +684,19 @@ impl VMThread {
                         return InstructionResult::Exception;
                     }
                     Some(array) => {
-                        match array.store_to_array(index, val as u64) {
-                            None => {
-                                let exc = create_throwable("java/lang/ArrayIndexOutOfBoundsException",
-                                                           self);
+                        // bastore also stores into boolean arrays, which only keep the lowest bit
+                        let val = if instruction == bastore && array.get_class().data.name == "[Z" {
+                            val & 1
+                        } else {
+                            val
+                        };
 
-                                *result = Some(exc.to_val());
-                                return InstructionResult::Exception;
-                            }
-                            Some(_) => {}
+                        if array.store_to_array(index, val).is_none() {
+                            let exc = create_throwable("java/lang/ArrayIndexOutOfBoundsException",
+                                                       self);
+
+                            *result = Some(exc.to_val());
+                            return InstructionResult::Exception;
                         }
 
                     }
@@ -485,15 +717,12 @@ impl VMThread {
                 };
 
                 let res = obj.store_to_array(index as usize, val);
-                match res {
-                    None => {
-                        let exc = create_throwable("java/lang/ArrayIndexOutOfBoundsException",
-                                                   self);
+                if res.is_none() {
+                    let exc = create_throwable("java/lang/ArrayIndexOutOfBoundsException",
+                                               self);
 
-                        *result = Some(exc.to_val());
-                        return InstructionResult::Exception;
-                    }
-                    Some(_) => {}
+                    *result = Some(exc.to_val());
+                    return InstructionResult::Exception;
                 }
             }
             pop => {
@@ -517,6 +746,13 @@ impl VMThread {
                 let (res, _) = a.overflowing_add(b);
                 frame.push(res as u64);
             }
+            fadd => {
+                let b = utof(frame.pop() as u32);
+                let a = utof(frame.pop() as u32);
+
+                let res = a + b;
+                frame.push(ftou(res) as u64);
+            }
             dadd => {
                 let b = utof2(frame.pop());
                 let a = utof2(frame.pop());
@@ -531,6 +767,13 @@ impl VMThread {
                 let (res, _) = a.overflowing_sub(b);
                 frame.push(res as u64);
             }
+            fsub => {
+                let b = utof(frame.pop() as u32);
+                let a = utof(frame.pop() as u32);
+
+                let res = a - b;
+                frame.push(ftou(res) as u64);
+            }
             dsub => {
                 let b = utof2(frame.pop());
                 let a = utof2(frame.pop());
@@ -552,6 +795,13 @@ impl VMThread {
                 let (res, _) = a.overflowing_mul(b);
                 frame.push(res);
             }
+            fmul => {
+                let b = utof(frame.pop() as u32);
+                let a = utof(frame.pop() as u32);
+
+                let res = a * b;
+                frame.push(ftou(res) as u64);
+            }
             dmul => {
                 let b = utof2(frame.pop());
                 let a = utof2(frame.pop());
@@ -573,6 +823,13 @@ impl VMThread {
                 let res = a / b;
                 frame.push(res);
             }
+            fdiv => {
+                let b = utof(frame.pop() as u32);
+                let a = utof(frame.pop() as u32);
+
+                let res = a / b;
+                frame.push(ftou(res) as u64);
+            }
             ddiv => {
                 let b = utof2(frame.pop());
                 let a = utof2(frame.pop());
@@ -587,6 +844,18 @@ impl VMThread {
                 let res = a - (a / b) * b;
                 frame.push(res as u64);
             }
+            frem => {
+                let b = utof(frame.pop() as u32);
+                let a = utof(frame.pop() as u32);
+
+                // Rust's % truncates like fmod, which is what Java specifies (not IEEE remainder)
+                let res = a % b;
+                frame.push(ftou(res) as u64);
+            }
+            fneg => {
+                let a = utof(frame.pop() as u32);
+                frame.push(ftou(-a) as u64);
+            }
             dneg => {
                 let a = utof2(frame.pop());
                 frame.push(ftou2(-a));
@@ -612,21 +881,47 @@ impl VMThread {
                 frame.set_s(index, (num + cons as i32) as u32);
             }
             i2l => {}
+            i2f => {
+                let val = frame.pop() as i32;
+
+                frame.push(ftou(val as f32) as u64);
+            }
             l2i => {
                 let val = frame.pop() as u32;
 
                 frame.push(val as u64)
             }
+            l2f => {
+                let val = frame.pop() as i64;
+
+                frame.push(ftou(val as f32) as u64);
+            }
             l2d => {
                 let val = frame.pop() as f64;
 
                 frame.push(ftou2(val));
             }
+            f2i => {
+                // `as` saturates and maps NaN to 0, exactly as the JVM specifies
+                let val = utof(frame.pop() as u32);
+
+                frame.push(val as i32 as u64);
+            }
+            f2l => {
+                let val = utof(frame.pop() as u32);
+
+                frame.push(val as i64 as u64);
+            }
             f2d => {
                 let val = utof(frame.pop() as u32);
 
                 frame.push(ftou2(val as f64));
             }
+            d2f => {
+                let val = utof2(frame.pop());
+
+                frame.push(ftou(val as f32) as u64);
+            }
             i2b => {
                 let val = frame.pop() as i32;
 
@@ -649,60 +944,75 @@ impl VMThread {
                     frame.push(0i64 as u64);
                 }
             }
-            ifeq | ifne | iflt | ifle | ifgt | ifge => {
-                let offset = i16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
-                    .unwrap()) as isize;
+            fcmpl | fcmpg => {
+                let b = utof(frame.pop() as u32);
+                let a = utof(frame.pop() as u32);
 
-                let a = frame.pop() as i32;
-                let cmp = match instruction {
-                    ifeq => a == 0,
-                    ifne => a != 0,
-                    iflt => a < 0,
-                    ifle => a <= 0,
-                    ifgt => a > 0,
-                    ifge => a >= 0,
-                    _ => panic!()
+                let res: i32 = match a.partial_cmp(&b) {
+                    Some(std::cmp::Ordering::Less) => -1,
+                    Some(std::cmp::Ordering::Equal) => 0,
+                    Some(std::cmp::Ordering::Greater) => 1,
+                    None if instruction == fcmpl => -1,
+                    None => 1
                 };
-                if cmp {
-                    frame.pc = (frame.pc as isize + offset) as usize;
-                } else {
-                    frame.pc += instruction_length(instruction);
-                }
+                frame.push(res as u64);
             }
-            if_icmpeq | if_icmpge | if_icmpgt => {
-                let offset = i16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
-                    .unwrap()) as isize;
+            ifeq => {
+                let taken = frame.pop() as i32 == 0;
+                branch(frame, instruction, &code.code, taken);
+            }
+            ifne => {
+                let taken = frame.pop() as i32 != 0;
+                branch(frame, instruction, &code.code, taken);
+            }
+            iflt => {
+                let taken = (frame.pop() as i32) < 0;
+                branch(frame, instruction, &code.code, taken);
+            }
+            ifle => {
+                let taken = frame.pop() as i32 <= 0;
+                branch(frame, instruction, &code.code, taken);
+            }
+            ifgt => {
+                let taken = frame.pop() as i32 > 0;
+                branch(frame, instruction, &code.code, taken);
+            }
+            ifge => {
+                let taken = frame.pop() as i32 >= 0;
+                branch(frame, instruction, &code.code, taken);
+            }
+            if_icmpeq => {
                 let b = frame.pop() as i32;
                 let a = frame.pop() as i32;
-
-                let cmp = match instruction {
-                    if_icmpeq => a == b,
-                    if_icmpge => a >= b,
-                    if_icmpgt => a > b,
-                    _ => panic!()
-                };
-                if cmp {
-                    frame.pc = (frame.pc as isize + offset) as usize;
-                } else {
-                    frame.pc += instruction_length(instruction);
-                }
+                branch(frame, instruction, &code.code, a == b);
             }
-            if_acmpeq | if_acmpne => {
-                let offset = i16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
-                    .unwrap()) as isize;
+            if_icmpge => {
                 let b = frame.pop() as i32;
                 let a = frame.pop() as i32;
-
-                let cmp = match instruction {
-                    if_acmpeq => a == b,
-                    if_acmpne => a != b,
-                    _ => panic!()
-                };
-                if cmp {
-                    frame.pc = (frame.pc as isize + offset) as usize;
-                } else {
-                    frame.pc += instruction_length(instruction);
-                }
+                branch(frame, instruction, &code.code, a >= b);
+            }
+            if_icmpgt => {
+                let b = frame.pop() as i32;
+                let a = frame.pop() as i32;
+                branch(frame, instruction, &code.code, a > b);
+            }
+            if_acmpeq => {
+                let b = frame.pop();
+                let a = frame.pop();
+                branch(frame, instruction, &code.code, a == b);
+            }
+            if_acmpne => {
+                let b = frame.pop();
+                let a = frame.pop();
+                branch(frame, instruction, &code.code, a != b);
+            }
+            ifnull => {
+                let taken = frame.pop() == 0;
+                branch(frame, instruction, &code.code, taken);
+            }
+            ifnonnull => {
+                let taken = frame.pop() != 0;
+                branch(frame, instruction, &code.code, taken);
             }
             goto => {
                 let offset = i16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
@@ -710,7 +1020,51 @@ impl VMThread {
 
                 frame.pc = (frame.pc as isize + offset) as usize;
             }
-            ireturn => {
+            tableswitch => {
+                let index = frame.pop() as i32;
+
+                // The verifier made sure that the operands are within the code
+                let operand = |index| read_i32(&code.code, index).unwrap();
+                let start = switch_operands_start(frame.pc);
+                let low = operand(start + 4);
+                let high = operand(start + 8);
+
+                let offset = if index < low || index > high {
+                    operand(start)
+                } else {
+                    operand(start + 12 + 4 * (index as i64 - low as i64) as usize)
+                } as isize;
+
+                frame.pc = (frame.pc as isize + offset) as usize;
+            }
+            lookupswitch => {
+                let key = frame.pop() as i32;
+
+                // The verifier made sure that the operands are within the code
+                let operand = |index| read_i32(&code.code, index).unwrap();
+                let start = switch_operands_start(frame.pc);
+                let npairs = operand(start + 4) as usize;
+                let pairs = start + 8;
+
+                // Pairs are sorted by their match value
+                let (mut lo, mut hi) = (0, npairs);
+                let mut offset = operand(start) as isize;
+                while lo < hi {
+                    let mid = (lo + hi) / 2;
+                    let value = operand(pairs + 8 * mid);
+                    match value.cmp(&key) {
+                        std::cmp::Ordering::Less => lo = mid + 1,
+                        std::cmp::Ordering::Greater => hi = mid,
+                        std::cmp::Ordering::Equal => {
+                            offset = operand(pairs + 8 * mid + 4) as isize;
+                            break;
+                        }
+                    }
+                }
+
+                frame.pc = (frame.pc as isize + offset) as usize;
+            }
+            ireturn | freturn => {
                 *result = Some(frame.pop());
                 return InstructionResult::Return;
             }
@@ -725,16 +1079,18 @@ impl VMThread {
                 let index = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
                     .unwrap());
 
-                let _ = resolve(ClassRef::new(class), index as usize);
+                if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                    return self.throw_error(&e, result);
+                }
                 let entry = class.get_cp_entry(index as usize);
 
                 match *entry {
                     CPEntry::ResolvedSymbolicReference(FieldReference(class, false, index))
                     => {
-                        match initialize_class(class) {
+                        match initialize_class(class, self.mutator) {
                             Ok(_) => {}
-                            Err(_) => {
-                                let ptr = create_throwable("java/lang/Error", self);
+                            Err(e) => {
+                                let ptr = create_initialization_throwable(e, self);
                                 *result = Some(ptr.to_val());
                                 return Exception;
                             }
@@ -742,23 +1098,27 @@ impl VMThread {
 
                         frame.push(class.data.static_fields[index].load(Ordering::Relaxed));
                     }
-                    _ => panic!("Unexpected pattern: {:?}", entry)
+                    CPEntry::ResolvedSymbolicReference(FieldReference(class, true, index)) =>
+                        return self.throw_error(&field_kind_error(class, true, index), result),
+                    _ => return self.throw_error(&illegal_cp_entry(class, index), result)
                 }
             }
             putstatic => {
                 let index = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
                     .unwrap());
 
-                let _ = resolve(ClassRef::new(class), index as usize);
+                if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                    return self.throw_error(&e, result);
+                }
                 let entry = class.get_cp_entry(index as usize);
 
                 match *entry {
                     CPEntry::ResolvedSymbolicReference(FieldReference(class, false, index))
                     => {
-                        match initialize_class(class) {
+                        match initialize_class(class, self.mutator) {
                             Ok(_) => {}
-                            Err(_) => {
-                                let ptr = create_throwable("java/lang/Error", self);
+                            Err(e) => {
+                                let ptr = create_initialization_throwable(e, self);
                                 *result = Some(ptr.to_val());
                                 return Exception;
                             }
@@ -766,14 +1126,18 @@ impl VMThread {
 
                         class.data.static_fields[index].store(frame.pop(), Ordering::Relaxed);
                     }
-                    _ => panic!("Unexpected pattern: {:?}", entry)
+                    CPEntry::ResolvedSymbolicReference(FieldReference(class, true, index)) =>
+                        return self.throw_error(&field_kind_error(class, true, index), result),
+                    _ => return self.throw_error(&illegal_cp_entry(class, index), result)
                 }
             }
             getfield => {
                 let index = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
                     .unwrap());
 
-                let _ = resolve(ClassRef::new(class), index as usize);
+                if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                    return self.throw_error(&e, result);
+                }
                 let entry = class.get_cp_entry(index as usize);
 
                 match entry {
@@ -783,14 +1147,18 @@ impl VMThread {
 
                         frame.push(obj.get_field(*index));
                     }
-                    _ => panic!("Unexpected pattern: {:?}", entry)
+                    CPEntry::ResolvedSymbolicReference(FieldReference(class, false, index)) =>
+                        return self.throw_error(&field_kind_error(*class, false, *index), result),
+                    _ => return self.throw_error(&illegal_cp_entry(class, index), result)
                 }
             }
             putfield => {
                 let index = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
                     .unwrap());
 
-                let _ = resolve(ClassRef::new(class), index as usize);
+                if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                    return self.throw_error(&e, result);
+                }
                 let entry = class.get_cp_entry(index as usize);
 
                 let value = frame.pop();
@@ -802,19 +1170,27 @@ impl VMThread {
                     CPEntry::ResolvedSymbolicReference(FieldReference(_class, true, index)) => {
                         obj.put_field(*index, value);
                     }
-                    _ => panic!("Unexpected pattern: {:?}", entry)
+                    CPEntry::ResolvedSymbolicReference(FieldReference(class, false, index)) =>
+                        return self.throw_error(&field_kind_error(*class, false, *index), result),
+                    _ => return self.throw_error(&illegal_cp_entry(class, index), result)
                 }
             }
             invokevirtual => {
                 let index = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
                     .unwrap());
 
-                let _ = resolve(ClassRef::new(class), index as usize);
+                if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                    return self.throw_error(&e, result);
+                }
                 let entry = class.get_cp_entry(index as usize);
 
                 match entry {
                     CPEntry::ResolvedSymbolicReference(MethodReference(other_class, index)) => {
                         let method = &other_class.data.methods[*index];
+                        if method.is_static() {
+                            return self.throw_error(&method_kind_error((*other_class, *index)),
+                                                    result);
+                        }
 
                         let arg_no = method.descriptor.parameters.len();
 
@@ -830,8 +1206,11 @@ impl VMThread {
                         };
                         let obj_class = obj.get_class();
 
-                        let res = invoke_virtual(obj_class, *other_class, (*other_class, *index))
-                            .unwrap();
+                        let res = match invoke_virtual(obj_class, *other_class,
+                                                       (*other_class, *index)) {
+                            Ok(res) => res,
+                            Err(e) => return self.throw_error(&e, result)
+                        };
 
                         let res = self.method(res, arg_no + 1);
                         match res {
@@ -842,22 +1221,31 @@ impl VMThread {
                             }
                         }
                     }
-                    _ => panic!("{:?}", entry)
+                    _ => return self.throw_error(&illegal_cp_entry(class, index), result)
                 }
             }
             invokespecial => {
                 let index = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
                     .unwrap());
 
-                let _ = resolve(ClassRef::new(class), index as usize);
+                if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                    return self.throw_error(&e, result);
+                }
                 let entry = class.get_cp_entry(index as usize);
 
                 match entry {
                     CPEntry::ResolvedSymbolicReference(MethodReference(other_class, index)) => {
                         // TODO: other_class may differ if direct superclass
                         let method = &other_class.data.methods[*index];
+                        if method.is_static() {
+                            return self.throw_error(&method_kind_error((*other_class, *index)),
+                                                    result);
+                        }
 
-                        let res = invoke_special(*other_class, method).unwrap();
+                        let res = match invoke_special((*other_class, *index)) {
+                            Ok(res) => res,
+                            Err(e) => return self.throw_error(&e, result)
+                        };
 
                         let res = self.method(res, method.descriptor.parameters.len() + 1);
                         match res {
@@ -868,29 +1256,35 @@ impl VMThread {
                             }
                         }
                     }
-                    _ => panic!()
+                    _ => return self.throw_error(&illegal_cp_entry(class, index), result)
                 }
             }
             invokestatic => {
                 let index = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
                     .unwrap());
 
-                let _ = resolve(ClassRef::new(class), index as usize);
+                if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                    return self.throw_error(&e, result);
+                }
                 let entry = class.get_cp_entry(index as usize);
 
                 match entry {
                     CPEntry::ResolvedSymbolicReference(MethodReference(other_class, index)) => {
-                        match initialize_class(*other_class) {
+                        if !other_class.data.methods[*index].is_static() {
+                            return self.throw_error(&method_kind_error((*other_class, *index)),
+                                                    result);
+                        }
+
+                        match initialize_class(*other_class, self.mutator) {
                             Ok(_) => {}
-                            Err(_) => {
-                                let ptr = create_throwable("java/lang/Error", self);
+                            Err(e) => {
+                                let ptr = create_initialization_throwable(e, self);
                                 *result = Some(ptr.to_val());
                                 return Exception;
                             }
                         }
 
                         let method = &other_class.data.methods[*index];
-                        assert!(method.is_static());
 
                         let res = self.method((*other_class, *index),
                                               method.descriptor.parameters.len());
@@ -902,19 +1296,25 @@ impl VMThread {
                             }
                         }
                     }
-                    _ => panic!("Unexpected CPEntry: {:?}", entry)
+                    _ => return self.throw_error(&illegal_cp_entry(class, index), result)
                 }
             }
             invokeinterface => {
                 let index = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
                     .unwrap());
 
-                let _ = resolve(ClassRef::new(class), index as usize);
+                if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                    return self.throw_error(&e, result);
+                }
                 let entry = class.get_cp_entry(index as usize);
 
                 match entry {
                     CPEntry::ResolvedSymbolicReference(MethodReference(other_class, index)) => {
                         let method = &other_class.data.methods[*index];
+                        if method.is_static() {
+                            return self.throw_error(&method_kind_error((*other_class, *index)),
+                                                    result);
+                        }
 
                         let arg_no = method.descriptor.parameters.len();
 
@@ -930,8 +1330,19 @@ impl VMThread {
                         };
                         let obj_class = obj.get_class();
 
-                        let res = invoke_virtual(obj_class, *other_class, (*other_class, *index))
-                            .unwrap();
+                        let res = match invoke_virtual(obj_class, *other_class,
+                                                       (*other_class, *index)) {
+                            Ok(res) => res,
+                            Err(e) => return self.throw_error(&e, result)
+                        };
+                        let selected = &res.0.data.methods[res.1];
+                        if !selected.is_public() && !selected.is_private() {
+                            return self.throw_error(&format!("IllegalAccessError: Receiver class \
+                                {} implements the interface method {} with a non-public method",
+                                obj_class.data.name.replace('/', "."),
+                                method.descriptor.describe(&other_class.data.name, &method.name)),
+                                                    result);
+                        }
 
                         let res = self.method(res, arg_no + 1);
                         match res {
@@ -942,52 +1353,118 @@ impl VMThread {
                             }
                         }
                     }
-                    _ => panic!("{:?}", entry)
+                    _ => return self.throw_error(&illegal_cp_entry(class, index), result)
+                }
+            }
+            invokedynamic => {
+                let index = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
+                    .unwrap());
+
+                if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                    let exc = create_throwable_message("java/lang/BootstrapMethodError", self,
+                                                       &e);
+
+                    *result = Some(exc.to_val());
+                    return InstructionResult::Exception;
+                }
+                let entry = class.get_cp_entry(index as usize);
+
+                match entry {
+                    CPEntry::ResolvedSymbolicReference(SymbolicReference::CallSite(
+                        CallSite::StringConcat(elements, types))) => {
+                        match self.concat_strings(elements, types) {
+                            Ok(string) => self.stack.last_mut().unwrap().push(string.to_val()),
+                            Err(obj) => {
+                                *result = Some(obj.to_val());
+                                return InstructionResult::Exception
+                            }
+                        }
+                    }
+                    CPEntry::ResolvedSymbolicReference(SymbolicReference::CallSite(
+                        CallSite::Lambda(lambda_class))) => {
+                        // The captured values stay on the stack while allocating
+                        let object = match allocate(self.mutator,
+                                                    |arena| arena.try_new_object(*lambda_class)) {
+                            Ok(object) => object,
+                            Err(e) => return self.throw_error(&e, result)
+                        };
+
+                        let captured = frame.pop_args(lambda_class.data.fields.len());
+                        let first_field = vm.object_class.data.instance_field_count;
+                        for (i, val) in captured.into_iter().enumerate() {
+                            object.put_field(first_field + i, val);
+                        }
+                        frame.push(object.to_val());
+                    }
+                    _ => return self.throw_error(&illegal_cp_entry(class, index), result)
                 }
             }
             new => {
                 let index = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
                     .unwrap());
 
-                let _ = resolve(ClassRef::new(class), index as usize);
+                if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                    return self.throw_error(&e, result);
+                }
                 let entry = class.get_cp_entry(index as usize);
 
                 match *entry {
                     CPEntry::ResolvedSymbolicReference(
                         SymbolicReference::ClassReference(other_class)) => {
-                        // TODO: It should not be an abstract class
+                        if other_class.is_interface() || has_flag(other_class.data.flag,
+                                                                  AccessFlagClass::ACC_ABSTRACT) {
+                            return self.throw_error(&format!("InstantiationError: {}",
+                                other_class.data.name.replace('/', ".")), result);
+                        }
 
-                        match initialize_class(other_class) {
+                        match initialize_class(other_class, self.mutator) {
                             Ok(_) => {}
-                            Err(_) => {
-                                let ptr = create_throwable("java/lang/Error", self);
+                            Err(e) => {
+                                let ptr = create_initialization_throwable(e, self);
                                 *result = Some(ptr.to_val());
                                 return Exception;
                             }
                         }
 
-                        let object = vm.object_arena.new_object(other_class);
+                        let object = match allocate(self.mutator,
+                                                    |arena| arena.try_new_object(other_class)) {
+                            Ok(object) => object,
+                            Err(e) => return self.throw_error(&e, result)
+                        };
                         frame.push(object.ptr as u64);
                     }
-                    _ => panic!("Unexpected pattern: {:?}", entry)
+                    _ => return self.throw_error(&illegal_cp_entry(class, index), result)
                 }
             }
             newarray => {
                 let atype = code.code[frame.pc + 1];
                 let name = FieldType::convert_newarray_type(atype);
                 let length = frame.pop();
+                if (length as i32) < 0 {
+                    return self.throw_negative_array_size(length as i32, result);
+                }
 
                 let array_class = vm.load_class(name).unwrap();
 
-                let object = vm.object_arena.new_array(array_class, length as usize);
+                let object = match allocate(self.mutator,
+                                            |arena| arena.try_new_array(array_class,
+                                                                        length as usize)) {
+                    Ok(object) => object,
+                    Err(e) => return self.throw_error(&e, result)
+                };
                 frame.push(object.ptr as u64);
             }
             anewarray => {
                 let index = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
                     .unwrap());
                 let length = frame.pop();
+                if (length as i32) < 0 {
+                    return self.throw_negative_array_size(length as i32, result);
+                }
 
-                let _ = resolve(ClassRef::new(class), index as usize);
+                if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                    return self.throw_error(&e, result);
+                }
                 let entry = class.get_cp_entry(index as usize);
 
                 match *entry {
@@ -995,14 +1472,61 @@ impl VMThread {
                         SymbolicReference::ClassReference(other_class)) => {
                         let mut array_class = other_class.data.name.clone();
                         array_class.insert(0, '[');
-                        let array_class = vm.load_class(array_class.as_str()).unwrap();
+                        let array_class = match vm.load_class_with(
+                            other_class.data.class_loader, array_class.as_str()) {
+                            Ok(array_class) => array_class,
+                            Err(e) => return self.throw_error(&e, result)
+                        };
 
-                        let object = vm.object_arena.new_array(array_class, length as usize);
+                        let object = match allocate(self.mutator,
+                                                    |arena| arena.try_new_array(array_class,
+                                                                                length as usize)) {
+                            Ok(object) => object,
+                            Err(e) => return self.throw_error(&e, result)
+                        };
                         frame.push(object.ptr as u64);
                     }
-                    _ => panic!("Unexpected pattern: {:?}", entry)
+                    _ => return self.throw_error(&illegal_cp_entry(class, index), result)
                 }
             },
+            multianewarray => {
+                let index = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
+                    .unwrap());
+                let dimensions = code.code[frame.pc + 3] as usize;
+
+                let mut counts: SmallVec<[i32; 4]> = smallvec![0; dimensions];
+                for count in counts.iter_mut().rev() {
+                    *count = frame.pop() as i32;
+                }
+                if let Some(count) = counts.iter().find(|c| **c < 0) {
+                    return self.throw_negative_array_size(*count, result);
+                }
+
+                if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                    return self.throw_error(&e, result);
+                }
+                let entry = class.get_cp_entry(index as usize);
+
+                match *entry {
+                    CPEntry::ResolvedSymbolicReference(
+                        SymbolicReference::ClassReference(array_class)) => {
+                        let array = match allocate(self.mutator,
+                                                   |arena| arena.try_new_array(array_class,
+                                                                               counts[0] as usize)) {
+                            Ok(array) => array,
+                            Err(e) => return self.throw_error(&e, result)
+                        };
+                        // The array stays reachable from the operand stack while its components
+                        // are allocated
+                        frame.push(array.ptr as u64);
+
+                        if let Err(e) = fill_multi_array(self.mutator, array, &counts[1..]) {
+                            return self.throw_error(&e, result);
+                        }
+                    }
+                    _ => return self.throw_error(&illegal_cp_entry(class, index), result)
+                }
+            }
             arraylength => {
                 let obj = frame.pop();
                 match ObjectPtr::from_val(obj) {
@@ -1034,24 +1558,27 @@ impl VMThread {
                 match ObjectPtr::from_val(object) {
                     None => {},
                     Some(object) => {
-                        let _ = resolve(ClassRef::new(class), index as usize);
+                        if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                            return self.throw_error(&e, result);
+                        }
 
                         let entry = class.get_cp_entry(index as usize);
 
                         match *entry {
                             CPEntry::ResolvedSymbolicReference(ClassReference(other_class)) => {
-                                if object.get_class().is_subclass(other_class) {
-                                } else {
-                                    // TODO: Array, interface implementation
-
-                                    let obj = create_throwable_message("java/lang/Exception",
-                                                     self, "ClassCastException: ");
+                                let object_class = object.get_class();
+                                if !object_class.is_subclass(other_class) {
+                                    let obj = create_throwable_message(
+                                        "java/lang/ClassCastException", self,
+                                        &format!("class {} cannot be cast to class {}",
+                                                 object_class.data.name.replace('/', "."),
+                                                 other_class.data.name.replace('/', ".")));
 
                                     *result = Some(obj.to_val());
                                     return InstructionResult::Exception;
                                 }
                             }
-                            _ => panic!("Unexpected pattern {:?}", entry)
+                            _ => return self.throw_error(&illegal_cp_entry(class, index), result)
                         }
                     }
                 }
@@ -1061,24 +1588,63 @@ impl VMThread {
                 let index = u16::from_be_bytes(code.code[frame.pc + 1..frame.pc + 3].try_into()
                     .unwrap());
 
-                let object = frame.pop();
+                // The reference stays on the stack while the class is resolved, which may run
+                // Java code of a class loader. A null reference is its own result, 0.
+                let object = frame.peek_nth(0);
                 match ObjectPtr::from_val(object) {
-                    None => frame.push(0),
+                    None => {},
                     Some(object) => {
-                        let _ = resolve(ClassRef::new(class), index as usize);
+                        if let Err(e) = resolve(ClassRef::new(class), index as usize) {
+                            return self.throw_error(&e, result);
+                        }
 
                         let entry = class.get_cp_entry(index as usize);
 
                         match *entry {
                             CPEntry::ResolvedSymbolicReference(ClassReference(other_class)) => {
+                                frame.pop();
                                 if object.get_class().is_subclass(other_class) {
                                     frame.push(1);
                                 } else {
-                                    // TODO: Array, interface implementation
                                     frame.push(0);
                                 }
                             }
-                            _ => panic!("Unexpected pattern {:?}", entry)
+                            _ => return self.throw_error(&illegal_cp_entry(class, index), result)
+                        }
+                    }
+                }
+            }
+            monitorenter => {
+                // The reference is only popped once the monitor is acquired, so that it stays
+                // reachable while the thread is blocked
+                match ObjectPtr::from_val(frame.safe_peek().unwrap()) {
+                    None => {
+                        let npe = create_throwable("java/lang/NullPointerException", self);
+
+                        *result = Some(npe.to_val());
+                        return InstructionResult::Exception;
+                    }
+                    Some(object) => {
+                        enter_monitor(self.mutator, object);
+                        frame.pop();
+                    }
+                }
+            }
+            monitorexit => {
+                match ObjectPtr::from_val(frame.pop()) {
+                    None => {
+                        let npe = create_throwable("java/lang/NullPointerException", self);
+
+                        *result = Some(npe.to_val());
+                        return InstructionResult::Exception;
+                    }
+                    Some(object) => {
+                        if object.monitor().exit().is_err() {
+                            let exc = create_throwable("java/lang/IllegalMonitorStateException",
+                                                       self);
+
+                            *result = Some(exc.to_val());
+                            return InstructionResult::Exception;
                         }
                     }
                 }
@@ -1092,25 +1658,50 @@ impl VMThread {
             }
         }
 
-        if instr < 153 || instr > 167 {
+        if !(153..=171).contains(&instr) && instruction != ifnull && instruction != ifnonnull {
             let frame = self.stack.last_mut().unwrap();
-            frame.pc += instruction_length(instruction);
+            frame.pc += instruction_length(instruction, &code.code, frame.pc).unwrap();
         }
 
         InstructionResult::Continue
     }
 }
 
-fn invoke_special(class: ClassRef, method: &Method) -> Result<MethodRef, String> {
+fn utf16(string: &str) -> Vec<u16> {
+    string.encode_utf16().collect()
+}
+
+/// Continues at the target of a conditional branch instruction if the branch is taken, after the
+/// instruction otherwise
+fn branch(frame: &mut Frame, instruction: Instruction, code: &[u8], taken: bool) {
+    if taken {
+        let offset = i16::from_be_bytes(code[frame.pc + 1..frame.pc + 3].try_into().unwrap());
+        frame.pc = (frame.pc as isize + offset as isize) as usize;
+    } else {
+        frame.pc += instruction_length(instruction, code, frame.pc).unwrap();
+    }
+}
+
+fn invoke_special(method_ref: MethodRef) -> Result<MethodRef, String> {
     // TODO: other_class may differ if direct superclass
 
     let vm = VM_HANDLER.get().unwrap();
+    let (class, index) = method_ref;
+    let method = &class.data.methods[index];
+
+    let mut current = class;
+    loop {
+        if let Some(res) = current.find_method(method.name.as_str(), &method.descriptor) {
+            if res.0.data.methods[res.1].is_abstract() {
+                return Err(abstract_method_error(class, method_ref));
+            }
+            return Ok(res)
+        }
 
-    if let Some(res) = class.find_method(method.name.as_str(), &method.descriptor) {
-        return Ok(res)
-    } else if !class.is_interface() &&
-        !class.data.superclass.ptr().is_null() {
-        return invoke_special(class.data.superclass, method)
+        if current.is_interface() || current.data.superclass.ptr().is_null() {
+            break;
+        }
+        current = current.data.superclass;
     }
 
     if class.is_interface() {
@@ -1122,9 +1713,7 @@ fn invoke_special(class: ClassRef, method: &Method) -> Result<MethodRef, String>
         }
     }
 
-    // TODO: Maximally specific non-abstract interface-method
-
-    Err(format!("Could not resolve method"))
+    select_default_method(class, method_ref)
 }
 
 fn invoke_virtual(class: ClassRef, resolved_class: ClassRef, method_ref: MethodRef) ->
@@ -1134,60 +1723,262 @@ fn invoke_virtual(class: ClassRef, resolved_class: ClassRef, method_ref: MethodR
         return Ok(method_ref);
     }
 
-    let search = class.data.methods.iter().enumerate()
-        .find(|(_i, m)| m.can_override(class, method, resolved_class));
-    return if let Some((i, _)) = search {
-        Ok((class, i))
-    } else {
-        invoke_virtual(class.data.superclass, resolved_class, method_ref)
+    let mut current = class;
+    while !current.ptr().is_null() {
+        let search = current.data.methods.iter().enumerate()
+            .find(|(_i, m)| !m.is_static() && m.can_override(current, method, resolved_class));
+        if let Some((i, m)) = search {
+            if m.is_abstract() {
+                return Err(abstract_method_error(class, method_ref));
+            }
+            return Ok((current, i));
+        }
+
+        current = current.data.superclass;
+    }
+
+    select_default_method(class, method_ref)
+}
+
+/// Error of invoking a method, for which the class of the receiver has no implementation
+fn abstract_method_error(class: ClassRef, (resolved_class, index): MethodRef) -> String {
+    let method = &resolved_class.data.methods[index];
+    format!("AbstractMethodError: Receiver class {} does not define or inherit an implementation \
+        of the resolved method {}", class.data.name.replace('/', "."),
+            method.descriptor.describe(&resolved_class.data.name, &method.name))
+}
+
+/// Selects the only non-abstract method among the maximally-specific superinterface methods of
+/// the class (JVMS §5.4.6)
+fn select_default_method(class: ClassRef, method_ref: MethodRef) -> Result<MethodRef, String> {
+    let method = &method_ref.0.data.methods[method_ref.1];
+    let candidates: Vec<MethodRef> = maximally_specific_methods(class, &method.name,
+                                                                &method.descriptor)
+        .into_iter().filter(|(c, i)| !c.data.methods[*i].is_abstract()).collect();
+
+    match candidates.as_slice() {
+        [res] => Ok(*res),
+        [] => Err(abstract_method_error(class, method_ref)),
+        _ => Err(format!("IncompatibleClassChangeError: Conflicting default methods: {}.{}",
+                         class.data.name, method.name))
+    }
+}
+
+/// Allocates through `alloc`, which every allocation of the VM goes through. If the heap is
+/// exhausted and the allocating thread is a GC mutator, a collection runs first, so every object
+/// the caller still needs has to be referenced from the thread's frames or local roots. Other
+/// threads can not collect here, they request a collection for the next safepoint instead.
+pub fn allocate(mutator: Option<usize>, alloc: impl Fn(&ObjectArena) -> Option<ObjectPtr>)
+    -> Result<ObjectPtr, String> {
+    let vm = VM_HANDLER.get().unwrap();
+    if let Some(object) = alloc(&vm.object_arena) {
+        return Ok(object);
+    }
+
+    vm.object_arena.request_gc();
+    if let Some(id) = mutator {
+        vm.gc.safepoint(vm, id);
     }
 
-    // TODO: Maximally specific superinterface
+    alloc(&vm.object_arena).ok_or_else(|| "OutOfMemoryError: Java heap space".to_string())
+}
+
+/// Creates the components of a multidimensional array, `counts` being the lengths of the remaining
+/// dimensions. Each component is stored before the next one is allocated, keeping it reachable.
+fn fill_multi_array(mutator: Option<usize>, array: ObjectPtr, counts: &[i32])
+    -> Result<(), String> {
+    let Some((&count, rest)) = counts.split_first() else {
+        return Ok(());
+    };
+
+    let vm = VM_HANDLER.get().unwrap();
+    let array_class = array.get_class();
+    let component_class = vm.load_class_with(array_class.data.class_loader,
+                                             &array_class.data.name[1..])?;
+
+    for i in 0..array.get_field(0) as usize {
+        let component = allocate(mutator,
+                                 |arena| arena.try_new_array(component_class, count as usize))?;
+        array.store_to_array(i, component.to_val());
+        fill_multi_array(mutator, component, rest)?;
+    }
+
+    Ok(())
+}
+
+/// Acquires the object's monitor. While blocked on a contended monitor the thread does not hold up
+/// garbage collections.
+fn enter_monitor(mutator: Option<usize>, object: ObjectPtr) {
+    let monitor = object.monitor();
+    if !monitor.try_enter() {
+        let vm = VM_HANDLER.get().unwrap();
+        vm.gc.blocking(mutator, || monitor.enter());
+    }
 }
 
 pub fn create_throwable(name: &str, thread: &VMThread) -> ObjectPtr {
     create_throwable_message(name, thread, "")
 }
 
-pub fn create_throwable_message(name: &str, thread: &VMThread, message: &str) -> ObjectPtr {
+/// Creates the throwable described by a VM error of the form `Name: message`, where `Name` is a
+/// class of `java.lang`. Other errors become a `java.lang.Error` with the error as message.
+pub fn create_throwable_from_error(error: &str, thread: &VMThread) -> ObjectPtr {
     let vm = VM_HANDLER.get().unwrap();
-    let class= vm.load_class(name).expect(&format!("Non-existing exception name: {}", name));
-    let obj = vm.object_arena.new_object(class);
 
-    let mut init_thread = VMThread::new();
-    let descriptor = MethodDescriptor {
-        parameters: vec![],
-        ret: FieldType::V
-    };
-    if let Some(method) = class.find_method("<init>", &descriptor) {
-        init_thread.start(method, smallvec![obj.to_val()]);
-    } else {
-        panic!();
+    if let Some((name, message)) = error.split_once(": ") {
+        let name = format!("java/lang/{}", name);
+        if !name.contains(' ') && vm.load_class(&name).is_ok() {
+            return create_throwable_message(&name, thread, message);
+        }
     }
 
-    let class = vm.load_class("java/lang/StackTraceElement").unwrap();
-    let array_class = vm.load_class("[java/lang/StackTraceElement").unwrap();
-    let array = vm.object_arena.new_array(array_class, thread.stack.len()-1);
+    create_throwable_message("java/lang/Error", thread, error)
+}
 
-    for i in 1..thread.stack.len() {
-        let obj = vm.object_arena.new_object(class);
-        let class_data = &thread.stack[i].methodref.0.data;
+/// Error of an instruction referring to a constant pool entry of the wrong kind
+fn illegal_cp_entry(class: &Class, index: u16) -> String {
+    format!("VerifyError: Illegal type at constant pool entry {} in class {}", index,
+            class.data.name.replace('/', "."))
+}
 
-        let declaring_class = vm.string_pool.intern_string(class_data.name.as_str());
-        obj.put_field(0, declaring_class.to_val());
-        let method_name = vm.string_pool.intern_string(
-            class_data.methods[thread.stack[i].methodref.1].name.as_str());
-        obj.put_field(1, method_name.to_val());
+/// Error of a field instruction referring to a field of the wrong kind
+fn field_kind_error(class: ClassRef, instance: bool, index: usize) -> String {
+    format!("IncompatibleClassChangeError: Expected {} field {}.{}",
+            if instance { "static" } else { "non-static" }, class.data.name.replace('/', "."),
+            class.resolved_field(instance, index).name)
+}
+
+/// Error of an invoke instruction referring to a method of the wrong kind
+fn method_kind_error((class, index): MethodRef) -> String {
+    let method = &class.data.methods[index];
+    let expected = if method.is_static() { "Expecting non-static" } else { "Expected static" };
+    format!("IncompatibleClassChangeError: {} method {}", expected,
+            method.descriptor.describe(&class.data.name, &method.name))
+}
 
-        array.store_to_array(thread.stack.len()-1-i, obj.to_val());
+/// Describes a throwable as a VM error of the form `Name: message`, the inverse of
+/// `create_throwable_from_error` for classes of `java.lang`
+pub fn error_from_throwable(throwable: ObjectPtr) -> String {
+    let class = throwable.get_class();
+    let name = class.data.name.strip_prefix("java/lang/")
+        .filter(|name| !name.contains('/'))
+        .map_or_else(|| class.data.name.replace('/', "."), str::to_string);
+    let message = ObjectPtr::from_val(throwable.get_field(1)).map(get_string)
+        .unwrap_or_default();
+
+    format!("{}: {}", name, message)
+}
+
+/// Creates the throwable describing a failed class initialization. Exceptions thrown by a static
+/// initializer, which are not errors, get wrapped into an ExceptionInInitializerError.
+pub fn create_initialization_throwable(error: InitializationError, thread: &VMThread)
+    -> ObjectPtr {
+    let vm = VM_HANDLER.get().unwrap();
+
+    match error {
+        InitializationError::Error(e) => create_throwable_from_error(&e, thread),
+        InitializationError::Thrown(throwable, _) => {
+            if throwable.get_class().is_subclass(vm.load_class("java/lang/Error").unwrap()) {
+                return throwable;
+            }
+
+            let error = thread.with_root(throwable, || {
+                create_throwable("java/lang/ExceptionInInitializerError", thread)
+            });
+            let cause = FieldType::L("java/lang/Throwable".to_string());
+            match instance_field_index(error.get_class(), "cause", &cause) {
+                Ok(index) => {
+                    error.put_field(index, throwable.to_val());
+                    error
+                }
+                Err(e) => create_throwable_from_error(&e, thread)
+            }
+        }
     }
+}
 
-    obj.put_field(0, array.to_val());
+/// Stores the stack trace of the first `depth` frames of the thread's stack into the throwable,
+/// innermost frame first. The throwable has to be reachable for the collector.
+pub fn fill_in_stack_trace(throwable: ObjectPtr, thread: &VMThread, depth: usize)
+    -> Result<(), String> {
+    let vm = VM_HANDLER.get().unwrap();
 
-    if !message.is_empty() {
-        let string = vm.string_pool.intern_string(message);
-        obj.put_field(1, string.to_val());
+    let class = vm.load_class("java/lang/StackTraceElement")?;
+    let array_class = vm.load_class("[java/lang/StackTraceElement")?;
+    // The first frame only holds the arguments of the thread's starting method
+    let array = thread.new_array(array_class, depth.saturating_sub(1))?;
+    throwable.put_field(0, array.to_val());
+
+    // Every object is stored before the next allocation, so that it stays reachable
+    for i in 1..depth {
+        let frame = &thread.stack[i];
+        let class_data = &frame.methodref.0.data;
+        let method = &class_data.methods[frame.methodref.1];
+        let obj = thread.new_object(class)?;
+        array.store_to_array(depth - 1 - i, obj.to_val());
+
+        let declaring_class = vm.string_pool.intern_string(Some(thread),
+                                                           &class_data.name.replace('/', "."))?;
+        obj.put_field(0, declaring_class.to_val());
+        let method_name = vm.string_pool.intern_string(Some(thread), method.name.as_str())?;
+        obj.put_field(1, method_name.to_val());
+        if let Some(source_file) = &class_data.source_file {
+            obj.put_field(2, vm.string_pool.intern_string(Some(thread), source_file)?.to_val());
+        }
+        // -1 for unknown lines and -2 for native methods, like in HotSpot
+        let line_number = match &method.repr {
+            MethodRepr::Native(_) => -2,
+            MethodRepr::Jvm(JvmMethod { code: Some(code) }) =>
+                code.line_number(frame.pc).map_or(-1, |l| l as i32),
+            MethodRepr::Jvm(_) => -1
+        };
+        obj.put_field(3, line_number as u64);
     }
 
-    obj
+    Ok(())
+}
+
+pub fn create_throwable_message(name: &str, thread: &VMThread, message: &str) -> ObjectPtr {
+    let vm = VM_HANDLER.get().unwrap();
+    let class= vm.load_class(name).unwrap_or_else(|_| panic!("Non-existing exception name: {}", name));
+    let Ok(obj) = thread.new_object(class) else {
+        return out_of_memory_error();
+    };
+
+    let init = || {
+        let mut init_thread = VMThread::new();
+        let descriptor = MethodDescriptor {
+            parameters: vec![],
+            ret: FieldType::V
+        };
+        // A throwable class without a no-argument constructor is left with the default values
+        // of its fields, the stack trace and message are filled in below either way
+        if let Some(method) = class.find_method("<init>", &descriptor) {
+            init_thread.start(method, smallvec![obj.to_val()]);
+        }
+
+        fill_in_stack_trace(obj, thread, thread.stack.len())?;
+
+        if !message.is_empty() {
+            let string = vm.string_pool.intern_string(Some(thread), message)?;
+            obj.put_field(1, string.to_val());
+        }
+        Ok::<_, String>(obj)
+    };
+
+    thread.with_root(obj, init).unwrap_or_else(|_| out_of_memory_error())
+}
+
+/// The OutOfMemoryError thrown when the heap has no room left for the throwable of an error
+fn out_of_memory_error() -> ObjectPtr {
+    let vm = VM_HANDLER.get().unwrap();
+    *vm.out_of_memory_error.get().expect("OutOfMemoryError: Java heap space")
+}
+
+/// Creates the OutOfMemoryError thrown when the heap is exhausted, while there is still room
+pub fn preallocate_out_of_memory_error() {
+    let vm = VM_HANDLER.get().unwrap();
+    let error = create_throwable_message("java/lang/OutOfMemoryError", &VMThread::new(),
+                                         "Java heap space");
+    let _ = vm.out_of_memory_error.set(error);
 }
\ No newline at end of file
@@ -1,15 +1,23 @@
 use std::collections::HashMap;
+use std::path::Path;
 use std::pin::Pin;
 use std::ptr::null;
 use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
-use std::sync::{Mutex, RwLock};
+use std::sync::{Condvar, Mutex, RwLock};
 
 use clap::Parser;
+use once_cell::sync::OnceCell;
 
 use crate::{Class};
 use crate::vm::class::class::ClassRef;
-use crate::vm::pool::object::ObjectArena;
+use crate::vm::class_loader::classpath::ClassPath;
+use crate::vm::class_loader::verifier::PendingVerification;
+use crate::vm::object::ObjectPtr;
+use crate::vm::pool::gc::GarbageCollector;
+use crate::vm::pool::object::{DEFAULT_HEAP_SIZE, ObjectArena};
 use crate::vm::pool::string::StringPool;
+use crate::vm::thread::monitor::WaitSets;
+use crate::vm::thread::registry::ThreadRegistry;
 
 pub struct VM {
     pub args: RwLock<VmArgs>,
@@ -20,9 +28,21 @@ pub struct VM {
                                             // can't move them around
     // TODO: Allocate in special class area.
 
-    pub bootstrap_cl_class_list: Mutex<HashMap<String, ClassRef>>,
+    /// Loaded classes by their initiating loader (null for the bootstrap loader) and name
+    pub loaded_classes: Mutex<HashMap<ObjectPtr, HashMap<String, ClassRef>>>,
+    /// Verification results of derived classes that are not linked yet
+    pub pending_verifications: Mutex<HashMap<ClassRef, PendingVerification>>,
+    /// Notified when the verification of a class completes
+    pub verification_done: Condvar,
+    pub class_path: ClassPath,
     pub object_arena: ObjectArena,
     pub string_pool: StringPool,
+    pub gc: GarbageCollector,
+    /// Thrown when not even the throwable of a failed allocation fits into the heap. It is
+    /// created before the main method starts.
+    pub out_of_memory_error: OnceCell<ObjectPtr>,
+    pub threads: ThreadRegistry,
+    pub wait_sets: WaitSets,
 
     pub object_class: ClassRef,
     pub classloader: ClassRef,
@@ -36,13 +56,66 @@ pub struct VM {
 #[derive(Parser, Debug)]
 #[clap(about)]
 pub struct VmArgs {
+    /// Directories and .jar/.zip archives to load classes from, separated by `:`
     #[clap(long = "cp")]
     pub classpath: Option<String>,
-    pub main_class: String,
+
+    /// Executable JAR to run, taking the main class and classpath from its manifest (`-jar` is
+    /// accepted as well)
+    #[clap(long)]
+    pub jar: Option<String>,
+
+    #[clap(required_unless_present = "jar")]
+    pub main_class: Option<String>,
     pub java_args: Vec<String>,
 
     #[clap(long)]
-    pub print_trace: bool
+    pub print_trace: bool,
+
+    /// Maximum size of the Java heap in megabytes
+    #[clap(long, default_value_t = 512)]
+    pub heap_size: usize,
+
+    #[clap(long)]
+    pub print_gc: bool
+}
+
+/// Options of the VM that take a value
+const VALUE_OPTIONS: [&str; 3] = ["--cp", "--jar", "--heap-size"];
+
+/// Command line arguments, with `java`'s single-dash `-jar` spelling mapped to `--jar`. Like
+/// `java`, only the options before the main class or JAR are options of the VM, so option values
+/// and the arguments of the program are passed on as they are, the latter after a `--`.
+fn launcher_args(args: impl IntoIterator<Item=String>) -> Vec<String> {
+    let mut args = args.into_iter();
+    let mut launcher_args: Vec<String> = args.next().into_iter().collect();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            launcher_args.push(arg);
+            break;
+        }
+        if !arg.starts_with('-') {
+            launcher_args.extend(["--".to_string(), arg]);
+            break;
+        }
+
+        let arg = if arg == "-jar" { "--jar".to_string() } else { arg };
+        let is_jar = arg == "--jar";
+        let takes_value = VALUE_OPTIONS.contains(&arg.as_str());
+        launcher_args.push(arg);
+        if takes_value {
+            launcher_args.extend(args.next());
+        }
+
+        if is_jar {
+            launcher_args.push("--".to_string());
+            break;
+        }
+    }
+
+    launcher_args.extend(args);
+    launcher_args
 }
 
 impl VM {
@@ -51,25 +124,54 @@ impl VM {
     }
 
     pub fn vm_init(parse_args: bool) -> VM {
-        const ZERO: AtomicU64 = AtomicU64::new(0);
+        let mut args = if parse_args { VmArgs::parse_from(launcher_args(std::env::args())) } else { VmArgs {
+            classpath: None,
+            jar: None,
+            main_class: None,
+            java_args: vec![],
+            print_trace: false,
+            heap_size: DEFAULT_HEAP_SIZE / (1024 * 1024),
+            print_gc: false
+        } };
+        let heap_size = args.heap_size * 1024 * 1024;
+        let class_path = match &args.jar {
+            // Like `java`, the manifest replaces any classpath given on the command line
+            Some(jar) => {
+                let (class_path, main_class) = ClassPath::from_jar(Path::new(jar))
+                    .unwrap_or_else(|e| {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1)
+                    });
+
+                // Every positional argument belongs to the program
+                if let Some(first_arg) = args.main_class.replace(main_class) {
+                    args.java_args.insert(0, first_arg);
+                }
+
+                class_path
+            }
+            None => ClassPath::new(args.classpath.as_deref().unwrap_or("."))
+        };
 
         let mut vm = VM {
-            args: RwLock::new(if parse_args { VmArgs::parse() } else { VmArgs {
-                classpath: None,
-                main_class: "".to_string(),
-                java_args: vec![],
-                print_trace: false
-            } }),
+            args: RwLock::new(args),
             classes: Mutex::new(vec![]),
-            bootstrap_cl_class_list: Default::default(),
-            object_arena: Default::default(),
+            loaded_classes: Default::default(),
+            pending_verifications: Default::default(),
+            verification_done: Default::default(),
+            class_path,
+            object_arena: ObjectArena::new(heap_size),
             string_pool: Default::default(),
+            gc: Default::default(),
+            out_of_memory_error: OnceCell::new(),
+            threads: Default::default(),
+            wait_sets: Default::default(),
 
             object_class: ClassRef::new(null()),
             classloader: ClassRef::new(null()),
             string_class: ClassRef::new(null()),
             last_instruction: AtomicU8::new(0),
-            instr_map: [ZERO; 256]
+            instr_map: std::array::from_fn(|_| AtomicU64::new(0))
         };
 
         vm.load_bootstrap_classes();
@@ -83,11 +185,32 @@ impl VM {
 
     pub fn stop(&self) {
         eprintln!("\n\n\nVM stats: ");
-        eprintln!("Loaded {} classes", self.bootstrap_cl_class_list.lock().unwrap().len());
-        eprintln!("Object arena allocated {} bytes of object",
-                 self.object_arena.last_index.load(Ordering::Relaxed));
-        eprintln!("String pool has {} block(s) of data and has {} strings interned",
-                 self.string_pool.buffers.read().unwrap().len(),
-                 self.string_pool.interned_string.read().unwrap().len());
+        eprintln!("Loaded {} classes", self.classes.lock().unwrap().len());
+        eprintln!("Object arena has {} bytes of live objects after {} collection(s)",
+                 self.object_arena.used_bytes(),
+                 self.gc.collections.load(Ordering::Relaxed));
+        eprintln!("String pool has {} strings interned",
+                 self.string_pool.interned_count());
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::vm::launcher_args;
+
+    fn rewrite(args: &[&str]) -> Vec<String> {
+        launcher_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn rewrites_jar_option_only() {
+        assert_eq!(rewrite(&["jvm", "--print-gc", "-jar", "app.jar", "-jar", "x"]),
+                   ["jvm", "--print-gc", "--jar", "app.jar", "--", "-jar", "x"]);
+        assert_eq!(rewrite(&["jvm", "--cp", "-jar", "Main", "-jar"]),
+                   ["jvm", "--cp", "-jar", "--", "Main", "-jar"]);
+        assert_eq!(rewrite(&["jvm", "--cp", "lib", "--", "Main", "-jar"]),
+                   ["jvm", "--cp", "lib", "--", "Main", "-jar"]);
+        assert_eq!(rewrite(&["jvm", "--heap-size", "64", "-jar", "app.jar"]),
+                   ["jvm", "--heap-size", "64", "--jar", "app.jar", "--"]);
+    }
+}
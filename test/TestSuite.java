@@ -95,6 +95,20 @@ public final class TestSuite {
 		tests.add(new Test("Multicast", List.of("hu.garaba.puzzlers.Multicast", "-1")));
 		tests.add(new Test("Multicast 2", List.of("hu.garaba.puzzlers.Multicast", "-255")));
 		tests.add(new Test("Swap", List.of("hu.garaba.puzzlers.Swap")));
+		tests.add(new Test("Switch", List.of("hu.garaba.Switch")));
+		tests.add(new Test("Floats", List.of("hu.garaba.Floats")));
+		tests.add(new Test("Synchronized", List.of("hu.garaba.Synchronized")));
+		tests.add(new Test("Threads", List.of("hu.garaba.Threads")));
+		tests.add(new Test("WaitNotify", List.of("hu.garaba.WaitNotify")));
+		tests.add(new Test("Interfaces", List.of("hu.garaba.Interfaces")));
+		tests.add(new Test("Lambdas", List.of("hu.garaba.Lambdas")));
+		tests.add(new Test("ClassLoaders", List.of("hu.garaba.ClassLoaders")));
+		tests.add(new Test("StackTraces", List.of("hu.garaba.StackTraces")));
+		tests.add(new Test("ClassInit", List.of("hu.garaba.ClassInit")));
+		tests.add(new Test("MultiArrays", List.of("hu.garaba.MultiArrays")));
+		tests.add(new Test("Strings", List.of("hu.garaba.Strings")));
+		tests.add(new Test("StringEquality", List.of("hu.garaba.StringEquality")));
+		tests.add(new Test("Formatting", List.of("hu.garaba.Formatting")));
 
 		var successfulTests = 0;
 
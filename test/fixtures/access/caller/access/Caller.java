@@ -0,0 +1,22 @@
+package access;
+
+import other.Holder;
+import other.Secret;
+
+public class Caller {
+	static int privateField() {
+		return Holder.secret;
+	}
+
+	static void privateMethod() {
+		Holder.hidden();
+	}
+
+	static void packageMethod() {
+		Holder.internal();
+	}
+
+	static void packageClass() {
+		new Secret();
+	}
+}
@@ -0,0 +1,12 @@
+package access;
+
+public class Outer {
+	private static int secret;
+
+	// A nestmate of Outer
+	static class Inner {
+		static int get() {
+			return secret;
+		}
+	}
+}
@@ -0,0 +1,12 @@
+package other;
+
+// The version Caller is compiled against, where everything is public
+public class Holder {
+	public static int secret;
+
+	public static void hidden() {
+	}
+
+	public static void internal() {
+	}
+}
@@ -0,0 +1,5 @@
+package other;
+
+// The version Caller is compiled against
+public class Secret {
+}
@@ -0,0 +1,11 @@
+package other;
+
+public class Holder {
+	private static int secret;
+
+	private static void hidden() {
+	}
+
+	static void internal() {
+	}
+}
@@ -0,0 +1,7 @@
+public class Constants {
+	// Only constant values, so the class has no <clinit>
+	static final int I = -7;
+	static final long J = 1L << 40;
+	static final double D = 2.5;
+	static final String S = "constant";
+}
@@ -0,0 +1,17 @@
+public class Allocating {
+	// Allocates far more than the heap holds while being initialized by main
+	static class Initializer {
+		static int total;
+
+		static {
+			for (int i = 0; i < 10000; i++) {
+				int[] garbage = new int[1000];
+				total += garbage.length;
+			}
+		}
+	}
+
+	public static void main(String[] args) {
+		System.out.println(Initializer.total);
+	}
+}
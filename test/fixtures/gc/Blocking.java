@@ -0,0 +1,29 @@
+public class Blocking {
+	static class Collector extends Thread {
+		public void run() {
+			for (int i = 0; i < 10000; i++) {
+				int[] garbage = new int[1000];
+			}
+		}
+	}
+
+	// Waits for another thread, which needs collections to finish, while being initialized by
+	// main
+	static class Initializer {
+		static String result = "done";
+
+		static {
+			Collector collector = new Collector();
+			collector.start();
+			try {
+				collector.join();
+			} catch (InterruptedException e) {
+				result = "interrupted";
+			}
+		}
+	}
+
+	public static void main(String[] args) {
+		System.out.println(Initializer.result);
+	}
+}
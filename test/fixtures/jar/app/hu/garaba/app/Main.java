@@ -0,0 +1,12 @@
+package hu.garaba.app;
+
+import hu.garaba.lib.Greeter;
+
+public class Main {
+	public static void main(String[] args) {
+		System.out.println(Greeter.greet("jar"));
+		for (int i = 0; i < args.length; i++) {
+			System.out.println(args[i]);
+		}
+	}
+}
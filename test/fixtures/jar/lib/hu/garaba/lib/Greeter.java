@@ -0,0 +1,7 @@
+package hu.garaba.lib;
+
+public class Greeter {
+	public static String greet(String name) {
+		return "Hello, " + name + "!";
+	}
+}
@@ -0,0 +1,19 @@
+package linkage;
+
+public class Caller {
+	static void missingMethod() {
+		Target.gone();
+	}
+
+	static int missingField() {
+		return Target.field;
+	}
+
+	static void missingClass() {
+		new Missing();
+	}
+
+	static void changedToInterface() {
+		Shape.area();
+	}
+}
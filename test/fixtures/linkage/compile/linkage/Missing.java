@@ -0,0 +1,5 @@
+package linkage;
+
+// Only exists when Caller is compiled
+public class Missing {
+}
@@ -0,0 +1,7 @@
+package linkage;
+
+// The version Caller is compiled against
+public class Shape {
+	public static void area() {
+	}
+}
@@ -0,0 +1,9 @@
+package linkage;
+
+// The version Caller is compiled against
+public class Target {
+	public static int field;
+
+	public static void gone() {
+	}
+}
@@ -0,0 +1,4 @@
+package linkage;
+
+public class Target {
+}
@@ -0,0 +1,9 @@
+public class Chars {
+	public static void main(String[] args) {
+		System.out.println('a');
+		System.out.println('\u00e1');
+		System.out.println('\u20ac');
+		// Half of a surrogate pair
+		System.out.println('\ud83d');
+	}
+}
@@ -0,0 +1,18 @@
+public class Uncaught {
+	static class Failing extends Thread {
+		Failing() {
+			super("worker");
+		}
+
+		public void run() {
+			throw new IllegalArgumentException("from worker");
+		}
+	}
+
+	public static void main(String[] args) throws InterruptedException {
+		Failing failing = new Failing();
+		failing.start();
+		failing.join();
+		throw new IllegalArgumentException();
+	}
+}
@@ -0,0 +1,12 @@
+public class Main {
+	public static void main(String[] args) {
+		for (int i = 0; i < 2; i++) {
+			try {
+				System.out.println(User.x);
+			} catch (NoClassDefFoundError e) {
+				System.out.println("caught " + e.getMessage());
+			}
+		}
+		System.out.println("done");
+	}
+}
@@ -0,0 +1,8 @@
+public class User {
+	static int x = 1;
+
+	// Verifying the return needs Derived to be loaded to check that it is a Base
+	static Base get() {
+		return new Derived();
+	}
+}
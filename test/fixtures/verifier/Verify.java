@@ -0,0 +1,10 @@
+public class Verify {
+	static int add(int a, int b) {
+		return a + b;
+	}
+
+	static int max(int a, int b) {
+		if (a < b) return b;
+		return a;
+	}
+}
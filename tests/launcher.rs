@@ -0,0 +1,133 @@
+//! Runs the VM binary the way a user would
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::OnceLock;
+use std::sync::mpsc;
+use std::time::Duration;
+
+// Shared with the unit tests, which use the rest of it
+#[allow(dead_code)]
+#[path = "../src/helper/fixtures.rs"]
+mod fixtures;
+
+/// The class library of the VM, compiled once for all tests
+fn jdk() -> &'static Path {
+    static JDK: OnceLock<PathBuf> = OnceLock::new();
+
+    JDK.get_or_init(|| {
+        let directory = fixtures::temp_dir("launcher-jdk");
+        fixtures::javac("jdk", &[], &directory);
+        directory
+    })
+}
+
+/// Runs the VM to completion. A VM that does not finish in time, e.g. due to a deadlock, is killed.
+fn output(command: &mut Command) -> Output {
+    let child = command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    let id = child.id();
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || sender.send(child.wait_with_output()));
+
+    let Ok(output) = receiver.recv_timeout(Duration::from_secs(120)) else {
+        let _ = Command::new("kill").arg(id.to_string()).status();
+        panic!("{:?} did not finish", command);
+    };
+    output.unwrap()
+}
+
+/// Runs the VM to completion and returns what the program printed
+fn run(command: &mut Command) -> String {
+    let output = output(command);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn vm() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_rust-jvm3"))
+}
+
+#[test]
+fn runs_executable_jar() {
+    let directory = fixtures::temp_dir("launcher-jar");
+    std::os::unix::fs::symlink(jdk(), directory.join("jdk")).unwrap();
+    fixtures::javac("test/fixtures/jar/lib", &[], &directory.join("lib"));
+    fixtures::javac("test/fixtures/jar/app", &[&directory.join("lib")], &directory.join("app"));
+
+    std::fs::create_dir(directory.join("lib dir")).unwrap();
+    fixtures::jar(&directory.join("lib dir/greeter.jar"), None, &directory.join("lib"), &["."],
+                  true);
+    let jar = directory.join("app.jar");
+    fixtures::jar(&jar, Some("Main-Class: hu.garaba.app.Main\nClass-Path: lib%20dir/greeter.jar \
+                              jdk/\n"), &directory.join("app"), &["."], true);
+
+    // Everything after the JAR belongs to the program, even if it looks like an option
+    let output = run(vm().args(["--heap-size", "64", "-jar"]).arg(&jar)
+        .args(["first", "-jar", "--cp"]));
+    assert_eq!(output, "Hello, jar!\nfirst\n-jar\n--cp\n");
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn rethrows_failed_verification() {
+    let directory = fixtures::temp_dir("launcher-verification");
+    fixtures::javac("test/fixtures/verification", &[], &directory);
+    // Verifying User needs Derived
+    std::fs::remove_file(directory.join("Derived.class")).unwrap();
+
+    let class_path = std::env::join_paths([jdk(), &directory]).unwrap();
+    let output = run(vm().arg("--cp").arg(class_path).arg("Main"));
+    assert_eq!(output, "caught Derived\ncaught Derived\ndone\n");
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn collects_during_static_initializers() {
+    let directory = fixtures::temp_dir("launcher-gc");
+    fixtures::javac("test/fixtures/gc", &[], &directory);
+    let class_path = std::env::join_paths([jdk(), &directory]).unwrap();
+
+    // Both allocate 40 MB in total
+    let output = run(vm().args(["--heap-size", "4", "--cp"]).arg(&class_path).arg("Allocating"));
+    assert_eq!(output, "10000000\n");
+    // The initializing thread has to let the other one collect while it waits
+    let output = run(vm().args(["--heap-size", "4", "--cp"]).arg(&class_path).arg("Blocking"));
+    assert_eq!(output, "done\n");
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn reports_uncaught_exceptions_like_java() {
+    let directory = fixtures::temp_dir("launcher-uncaught");
+    fixtures::javac("test/fixtures/uncaught", &[], &directory);
+    let class_path = std::env::join_paths([jdk(), &directory]).unwrap();
+
+    let output = output(vm().arg("--cp").arg(class_path).arg("Uncaught"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    for expected in [
+        "Exception in thread \"worker\" java.lang.IllegalArgumentException: from worker\n\
+         \tat Uncaught$Failing.run(Uncaught.java:8)\n",
+        // Without a message
+        "Exception in thread \"main\" java.lang.IllegalArgumentException\n\
+         \tat Uncaught.main(Uncaught.java:16)\n"
+    ] {
+        assert!(stderr.contains(expected), "{} not in:\n{}", expected, stderr);
+    }
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn prints_chars_as_unicode() {
+    let directory = fixtures::temp_dir("launcher-printing");
+    fixtures::javac("test/fixtures/printing", &[], &directory);
+    let class_path = std::env::join_paths([jdk(), &directory]).unwrap();
+
+    let output = run(vm().arg("--cp").arg(class_path).arg("Chars"));
+    assert_eq!(output, "a\n\u{E1}\n\u{20AC}\n\u{FFFD}\n");
+
+    std::fs::remove_dir_all(directory).unwrap();
+}